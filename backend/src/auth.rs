@@ -0,0 +1,306 @@
+use crate::system_db::{ApiToken, Permission};
+use keradb_labs_core::slow_log::{self, Caller};
+use crate::error::{ApiError, ErrorBody};
+use crate::AppState;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Authenticated caller, stored in request extensions by `require_token`
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+    pub token: ApiToken,
+}
+
+impl AuthContext {
+    /// `db_name` is a database name as resolved by `DatabaseRoots`
+    pub fn can_access_database(&self, db_name: &str) -> bool {
+        self.token.databases.is_empty() || self.token.databases.iter().any(|allowed| allowed == db_name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AuthError {
+    MissingToken,
+    InvalidToken,
+    InsufficientScope(Permission),
+    DatabaseNotAllowed(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::MissingToken => write!(f, "Missing bearer token"),
+            AuthError::InvalidToken => write!(f, "Invalid or revoked token"),
+            AuthError::InsufficientScope(permission) => {
+                write!(f, "Token scope does not allow {:?} access", permission)
+            }
+            AuthError::DatabaseNotAllowed(db) => {
+                write!(f, "Token is not allowed to access database: {}", db)
+            }
+        }
+    }
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AuthError::MissingToken | AuthError::InvalidToken => StatusCode::UNAUTHORIZED,
+            AuthError::InsufficientScope(_) | AuthError::DatabaseNotAllowed(_) => StatusCode::FORBIDDEN,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if self.status_code() == StatusCode::UNAUTHORIZED {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        response.json(ErrorBody { error: ApiError::from(self.clone()) })
+    }
+}
+
+/// Middleware: authenticate the bearer token, check its scope against the
+/// route and, for `/api/databases/{db}/...` style routes, its database allowlist
+pub async fn require_token(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let state = req
+        .app_data::<web::Data<AppState>>()
+        .cloned()
+        .expect("AppState is registered");
+
+    // Admin routes delete data or manage tokens and need a token even when
+    // authentication is otherwise off
+    let permission = match required_permission(req.method(), req.path()) {
+        Some(permission) if state.config.require_auth || permission == Permission::Admin => permission,
+        _ => return next.call(req).await.map(ServiceResponse::map_into_left_body),
+    };
+
+    match authenticate(&state, &req, permission) {
+        Ok(context) => {
+            req.extensions_mut().insert(context);
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
+        }
+        Err(e) => {
+            log::warn!("Rejected {} {}: {}", req.method(), req.path(), e);
+            let response = e.error_response().map_into_right_body();
+            Ok(req.into_response(response))
+        }
+    }
+}
+
+/// Middleware: make the request's token and peer address the caller that
+/// `LabsService` records in its slow operation log. Must run inside
+/// `require_token`.
+pub async fn scope_caller(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let token = req.extensions().get::<AuthContext>().map(|context| context.token.clone());
+    let caller = Caller {
+        token_id: token.as_ref().map(|token| token.id.clone()),
+        token_name: token.map(|token| token.name),
+        ip: req.peer_addr().map(|addr| addr.ip().to_string()),
+    };
+
+    WithCaller {
+        caller,
+        inner: Box::pin(next.call(req)),
+    }
+    .await
+}
+
+// Sets the caller around every poll rather than once, since requests on a
+// worker thread interleave at their await points
+struct WithCaller<F> {
+    caller: Caller,
+    inner: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for WithCaller<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = &mut *self;
+        slow_log::with_caller(&this.caller, || this.inner.as_mut().poll(cx))
+    }
+}
+
+fn authenticate(
+    state: &AppState,
+    req: &ServiceRequest,
+    permission: Permission,
+) -> Result<AuthContext, AuthError> {
+    let token = bearer_token(req).ok_or(AuthError::MissingToken)?;
+
+    let token = match state.service.system_db().verify_token(&token) {
+        Ok(Some(token)) => token,
+        Ok(None) => return Err(AuthError::InvalidToken),
+        Err(e) => {
+            log::error!("Failed to verify API token: {}", e);
+            return Err(AuthError::InvalidToken);
+        }
+    };
+
+    if !token.scope.allows(permission) {
+        return Err(AuthError::InsufficientScope(permission));
+    }
+
+    let context = AuthContext { token };
+    // Paths that fail to resolve are rejected by the handler itself
+    if let Some(db) = database_from_path(req.path()).and_then(|db| state.database_name(&db).ok()) {
+        if !context.can_access_database(&db) {
+            return Err(AuthError::DatabaseNotAllowed(db));
+        }
+    }
+
+    Ok(context)
+}
+
+/// Token from the Authorization header. Browsers cannot set headers on a
+/// WebSocket handshake, so upgrades may pass it as `access_token` instead.
+fn bearer_token(req: &ServiceRequest) -> Option<String> {
+    let header = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+
+    let query = || {
+        let is_upgrade = req
+            .headers()
+            .get(header::UPGRADE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+        if !is_upgrade {
+            return None;
+        }
+        web::Query::<HashMap<String, String>>::from_query(req.query_string())
+            .ok()
+            .and_then(|query| query.into_inner().remove("access_token"))
+    };
+
+    header.or_else(query).filter(|token| !token.is_empty())
+}
+
+/// Check the database allowlist for handlers that take the database path in
+/// the request body rather than the URL
+pub fn check_database_access(req: &HttpRequest, db_path: &str) -> Result<(), AuthError> {
+    match req.extensions().get::<AuthContext>() {
+        Some(context) if !context.can_access_database(db_path) => {
+            Err(AuthError::DatabaseNotAllowed(db_path.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Whether the request's token grants `permission`; always true when the
+/// route was not authenticated because authentication is off
+pub fn allows(req: &HttpRequest, permission: Permission) -> bool {
+    req.extensions()
+        .get::<AuthContext>()
+        .is_none_or(|context| context.token.scope.allows(permission))
+}
+
+/// Permission a request needs, or None for public routes
+fn required_permission(method: &Method, path: &str) -> Option<Permission> {
+    let protected = path.starts_with("/api/") || path == crate::prometheus::METRICS_PATH;
+    if *method == Method::OPTIONS || !protected || crate::openapi::is_docs_route(path) {
+        return None;
+    }
+
+    // Token management, the audit log, workspace bundles and deleting
+    // database files are admin-only
+    let admin_only = ["/api/system/tokens", "/api/system/audit", "/api/system/workspace"];
+    if admin_only.iter().any(|prefix| path.starts_with(prefix)) {
+        return Some(Permission::Admin);
+    }
+    if *method == Method::DELETE && is_database_route(path) {
+        return Some(Permission::Admin);
+    }
+
+    // POST endpoints that only read
+    let runs_query = database_from_path(path).is_some() && (path.ends_with("/query") || path.ends_with("/run"));
+    if path.ends_with("/vectors/search") || runs_query || path == "/api/parse-document" {
+        return Some(Permission::Read);
+    }
+
+    if *method == Method::GET || *method == Method::HEAD {
+        Some(Permission::Read)
+    } else {
+        Some(Permission::Write)
+    }
+}
+
+/// Whether `path` is `/api/databases/{db}` itself rather than a route below it
+fn is_database_route(path: &str) -> bool {
+    path.strip_prefix("/api/databases/")
+        .is_some_and(|db| !db.contains('/'))
+        && database_from_path(path).is_some()
+}
+
+/// Extract the `{db}` segment from database-scoped routes
+fn database_from_path(path: &str) -> Option<String> {
+    let rest = path
+        .strip_prefix("/api/databases/")
+        .or_else(|| path.strip_prefix("/api/system/connections/"))
+        .or_else(|| path.strip_prefix("/api/system/metrics/"))?;
+
+    let segment = rest.split('/').next().filter(|s| !s.is_empty())?;
+    // These are fixed routes, not database paths
+    if path.starts_with("/api/databases/") && matches!(segment, "open" | "create" | "close") && !rest.contains('/') {
+        return None;
+    }
+
+    Some(
+        percent_encoding::percent_decode_str(segment)
+            .decode_utf8_lossy()
+            .into_owned(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_permission() {
+        assert_eq!(required_permission(&Method::GET, "/health"), None);
+        assert_eq!(required_permission(&Method::OPTIONS, "/api/databases"), None);
+        assert_eq!(required_permission(&Method::GET, "/api/openapi.json"), None);
+        assert_eq!(required_permission(&Method::GET, "/api/databases/a.db/documents"), Some(Permission::Read));
+        assert_eq!(required_permission(&Method::POST, "/api/databases/a.db/vectors/search"), Some(Permission::Read));
+        assert_eq!(required_permission(&Method::POST, "/api/databases/a.db/documents"), Some(Permission::Write));
+        assert_eq!(required_permission(&Method::POST, "/api/databases/a.db/query"), Some(Permission::Read));
+        assert_eq!(required_permission(&Method::POST, "/api/databases/a.db/saved-queries/1/run"), Some(Permission::Read));
+        assert_eq!(required_permission(&Method::POST, "/api/databases/a.db/saved-queries"), Some(Permission::Write));
+        assert_eq!(required_permission(&Method::DELETE, "/api/databases/a.db/documents"), Some(Permission::Write));
+        assert_eq!(required_permission(&Method::DELETE, "/api/databases/a.db"), Some(Permission::Admin));
+        assert_eq!(required_permission(&Method::DELETE, "/api/databases/main%2Fa.db"), Some(Permission::Admin));
+        assert_eq!(required_permission(&Method::DELETE, "/api/databases/a.db/collections"), Some(Permission::Write));
+        assert_eq!(required_permission(&Method::DELETE, "/api/system/connections/a.db"), Some(Permission::Write));
+        assert_eq!(required_permission(&Method::GET, "/api/system/tokens"), Some(Permission::Admin));
+        assert_eq!(required_permission(&Method::GET, "/api/system/audit/export"), Some(Permission::Admin));
+        assert_eq!(required_permission(&Method::POST, "/api/system/workspace/import"), Some(Permission::Admin));
+        assert_eq!(required_permission(&Method::GET, "/api/changes"), Some(Permission::Read));
+        assert_eq!(required_permission(&Method::GET, "/metrics"), Some(Permission::Read));
+    }
+
+    #[test]
+    fn test_database_from_path() {
+        assert_eq!(database_from_path("/api/databases/data%2Fa.db/stats").as_deref(), Some("data/a.db"));
+        assert_eq!(database_from_path("/api/system/metrics/a.db").as_deref(), Some("a.db"));
+        assert_eq!(database_from_path("/api/databases/open"), None);
+        assert_eq!(database_from_path("/api/databases"), None);
+        assert_eq!(database_from_path("/api/system/stats"), None);
+    }
+}
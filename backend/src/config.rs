@@ -0,0 +1,452 @@
+use clap::Parser;
+use keradb_labs_core::document_parser::ArchiveLimits;
+use keradb_labs_core::metrics::MetricsRetention;
+use keradb_labs_core::system_db::{self, SystemDatabase, SystemDbLocation};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Config file looked up in the working directory when none is given
+const DEFAULT_CONFIG_FILE: &str = "keradb-labs.toml";
+
+/// Prefix for environment variable overrides, e.g. `KERADB_LABS_PORT`
+const ENV_PREFIX: &str = "KERADB_LABS_";
+
+/// Root used when none are configured
+const DEFAULT_ROOT: &str = "main";
+
+/// Server settings, layered from defaults, the TOML config file, environment
+/// variables and CLI flags (later layers win)
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Origins allowed by CORS; `*` allows any origin
+    pub cors_origins: Vec<String>,
+    /// Largest file accepted by `/api/parse-document`
+    pub max_upload_bytes: usize,
+    /// Most files an uploaded archive may hold, counting those inside Office
+    /// documents, EPUBs and email attachments
+    pub max_archive_entries: usize,
+    /// Most bytes an upload may unpack to, nested containers included
+    pub max_archive_bytes: u64,
+    /// System database file; overrides `home` and `profile`
+    pub system_db_path: Option<PathBuf>,
+    /// Directory holding the system database and its profiles; defaults to
+    /// `~/.keradb`
+    pub home: Option<PathBuf>,
+    /// Named profile with its own system database, e.g. `work`
+    pub profile: Option<String>,
+    /// Use a throwaway system database that is deleted on exit
+    pub ephemeral: bool,
+    /// Databases opened (and created if missing) at startup
+    pub open_databases: Vec<String>,
+    /// Directories databases must live in, keyed by the name used in the API;
+    /// when empty, `main` is the `databases` directory of the home
+    pub database_roots: BTreeMap<String, PathBuf>,
+    /// Default `env_logger` filter; `RUST_LOG` still takes precedence
+    pub log_level: String,
+    /// Require a bearer token on every `/api/` request. Admin routes need a
+    /// token either way.
+    pub require_auth: bool,
+    /// How long metric samples and rollups are kept (`[metrics]` table)
+    pub metrics: MetricsRetention,
+    /// Operations taking at least this many milliseconds go to the slow
+    /// operation log; 0 turns it off
+    pub slow_op_ms: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 5800,
+            cors_origins: vec!["*".to_string()],
+            max_upload_bytes: 100 * 1024 * 1024,
+            max_archive_entries: ArchiveLimits::default().max_entries,
+            max_archive_bytes: ArchiveLimits::default().max_total_bytes,
+            system_db_path: None,
+            home: None,
+            profile: None,
+            ephemeral: false,
+            open_databases: Vec::new(),
+            database_roots: BTreeMap::new(),
+            log_level: "info".to_string(),
+            require_auth: true,
+            metrics: MetricsRetention::default(),
+            slow_op_ms: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Default, Parser)]
+#[command(name = "keradb-labs", version, about = "keradb Labs API server")]
+pub struct CliArgs {
+    /// Path to a TOML config file (default: ./keradb-labs.toml if present)
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+    /// Address to bind to
+    #[arg(long)]
+    pub host: Option<String>,
+    /// Port to listen on
+    #[arg(short, long)]
+    pub port: Option<u16>,
+    /// Allowed CORS origin; repeat for several, `*` allows any
+    #[arg(long = "cors-origin")]
+    pub cors_origins: Vec<String>,
+    /// Largest file accepted by /api/parse-document, in bytes
+    #[arg(long)]
+    pub max_upload_bytes: Option<usize>,
+    /// Most files an uploaded archive may unpack, nested ones included
+    #[arg(long)]
+    pub max_archive_entries: Option<usize>,
+    /// Most bytes an uploaded archive may unpack to, nested ones included
+    #[arg(long)]
+    pub max_archive_bytes: Option<u64>,
+    /// System database file
+    #[arg(long)]
+    pub system_db: Option<PathBuf>,
+    /// Directory holding the system database and its profiles
+    #[arg(long, value_name = "DIR")]
+    pub home: Option<PathBuf>,
+    /// Named profile with its own system database
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+    /// Use a throwaway system database that is deleted on exit
+    #[arg(long)]
+    pub ephemeral: bool,
+    /// Print the profiles that have a system database and exit
+    #[arg(long)]
+    pub list_profiles: bool,
+    /// Database to open at startup; repeat for several
+    #[arg(long = "open")]
+    pub open_databases: Vec<String>,
+    /// Allowed database directory as NAME=DIR; repeat for several
+    #[arg(long = "database-root", value_name = "NAME=DIR")]
+    pub database_roots: Vec<String>,
+    /// Default log filter (debug, info, warn, error)
+    #[arg(long)]
+    pub log_level: Option<String>,
+    /// Require a bearer token on every /api/ request (the default)
+    #[arg(long, conflicts_with = "no_auth")]
+    pub require_auth: bool,
+    /// Only require a token for admin routes; needs explicit CORS origins
+    #[arg(long)]
+    pub no_auth: bool,
+    /// Log operations taking at least this many milliseconds; 0 turns it off
+    #[arg(long)]
+    pub slow_op_ms: Option<u64>,
+    /// Create an admin API token with this name, print it and exit
+    #[arg(long, value_name = "NAME")]
+    pub create_admin_token: Option<String>,
+}
+
+impl ServerConfig {
+    /// Build the effective config from parsed CLI arguments and the environment
+    pub fn load(args: &CliArgs) -> Result<Self, String> {
+        Self::from_sources(args, |key| std::env::var(key).ok())
+    }
+
+    fn from_sources(args: &CliArgs, env: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let explicit_path = args
+            .config
+            .clone()
+            .or_else(|| env(&format!("{}CONFIG", ENV_PREFIX)).map(PathBuf::from));
+
+        let mut config = match explicit_path {
+            Some(path) => Self::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => Self::default(),
+        };
+
+        config.apply_env(env)?;
+        config.apply_args(args)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+        toml::from_str(&contents)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+    }
+
+    fn apply_env(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<(), String> {
+        let var = |name: &str| env(&format!("{}{}", ENV_PREFIX, name));
+
+        if let Some(host) = var("HOST") {
+            self.host = host;
+        }
+        if let Some(port) = var("PORT") {
+            self.port = port
+                .parse()
+                .map_err(|_| format!("Invalid {}PORT: {}", ENV_PREFIX, port))?;
+        }
+        if let Some(origins) = var("CORS_ORIGINS") {
+            self.cors_origins = split_list(&origins, ',');
+        }
+        if let Some(max) = var("MAX_UPLOAD_BYTES") {
+            self.max_upload_bytes = max
+                .parse()
+                .map_err(|_| format!("Invalid {}MAX_UPLOAD_BYTES: {}", ENV_PREFIX, max))?;
+        }
+        if let Some(max) = var("MAX_ARCHIVE_ENTRIES") {
+            self.max_archive_entries = max
+                .parse()
+                .map_err(|_| format!("Invalid {}MAX_ARCHIVE_ENTRIES: {}", ENV_PREFIX, max))?;
+        }
+        if let Some(max) = var("MAX_ARCHIVE_BYTES") {
+            self.max_archive_bytes = max
+                .parse()
+                .map_err(|_| format!("Invalid {}MAX_ARCHIVE_BYTES: {}", ENV_PREFIX, max))?;
+        }
+        if let Some(path) = var("SYSTEM_DB") {
+            self.system_db_path = Some(PathBuf::from(path));
+        }
+        // Shared with the desktop app, hence without the prefix
+        if let Some(home) = env(system_db::HOME_ENV).filter(|home| !home.is_empty()) {
+            self.home = Some(PathBuf::from(home));
+        }
+        if let Some(profile) = env(system_db::PROFILE_ENV).filter(|profile| !profile.is_empty()) {
+            self.profile = Some(profile);
+        }
+        if let Some(paths) = var("OPEN_DATABASES") {
+            // Same separator as PATH, since Windows paths contain ':'
+            let separator = if cfg!(windows) { ';' } else { ':' };
+            self.open_databases = split_list(&paths, separator);
+        }
+        if let Some(roots) = var("DATABASE_ROOTS") {
+            let separator = if cfg!(windows) { ';' } else { ':' };
+            self.database_roots = parse_roots(&split_list(&roots, separator))?;
+        }
+        if let Some(level) = var("LOG") {
+            self.log_level = level;
+        }
+        if let Some(require) = var("REQUIRE_AUTH") {
+            self.require_auth = matches!(require.to_lowercase().as_str(), "1" | "true" | "yes");
+        }
+        if let Some(ms) = var("SLOW_OP_MS") {
+            self.slow_op_ms = ms
+                .parse()
+                .map_err(|_| format!("Invalid {}SLOW_OP_MS: {}", ENV_PREFIX, ms))?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_args(&mut self, args: &CliArgs) -> Result<(), String> {
+        if let Some(host) = &args.host {
+            self.host = host.clone();
+        }
+        if let Some(port) = args.port {
+            self.port = port;
+        }
+        if !args.cors_origins.is_empty() {
+            self.cors_origins = args.cors_origins.clone();
+        }
+        if let Some(max) = args.max_upload_bytes {
+            self.max_upload_bytes = max;
+        }
+        if let Some(max) = args.max_archive_entries {
+            self.max_archive_entries = max;
+        }
+        if let Some(max) = args.max_archive_bytes {
+            self.max_archive_bytes = max;
+        }
+        if let Some(path) = &args.system_db {
+            self.system_db_path = Some(path.clone());
+        }
+        if let Some(home) = &args.home {
+            self.home = Some(home.clone());
+        }
+        if let Some(profile) = &args.profile {
+            self.profile = Some(profile.clone());
+        }
+        if args.ephemeral {
+            self.ephemeral = true;
+        }
+        if !args.open_databases.is_empty() {
+            self.open_databases = args.open_databases.clone();
+        }
+        if let Some(level) = &args.log_level {
+            self.log_level = level.clone();
+        }
+        if args.require_auth {
+            self.require_auth = true;
+        }
+        if args.no_auth {
+            self.require_auth = false;
+        }
+        if !args.database_roots.is_empty() {
+            self.database_roots = parse_roots(&args.database_roots)?;
+        }
+        if let Some(ms) = args.slow_op_ms {
+            self.slow_op_ms = ms;
+        }
+
+        Ok(())
+    }
+
+    // Any web page could drive an unauthenticated server that allows every
+    // origin
+    fn validate(&self) -> Result<(), String> {
+        if !self.require_auth && self.allows_any_origin() {
+            return Err(
+                "cors_origins may not contain \"*\" when require_auth is off; list the allowed origins or enable authentication"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Home directory and profile of the system database, ignoring
+    /// `system_db_path` and `ephemeral`
+    pub fn system_db_location(&self) -> Result<SystemDbLocation, String> {
+        let location = match &self.home {
+            Some(home) => SystemDbLocation::new(home, system_db::SYSTEM_DB_NAME),
+            None => SystemDatabase::default_location().map_err(|e| e.to_string())?,
+        };
+        location.with_profile(self.profile.as_deref()).map_err(|e| e.to_string())
+    }
+
+    /// The configured database roots, or `main` in the home directory. The
+    /// server never accepts arbitrary paths.
+    pub fn effective_database_roots(&self) -> Result<BTreeMap<String, PathBuf>, String> {
+        if !self.database_roots.is_empty() {
+            return Ok(self.database_roots.clone());
+        }
+        let home = self.system_db_location()?.home;
+        Ok(BTreeMap::from([(DEFAULT_ROOT.to_string(), home.join("databases"))]))
+    }
+
+    pub fn allows_any_origin(&self) -> bool {
+        self.cors_origins.iter().any(|origin| origin == "*")
+    }
+
+    pub fn slow_op_threshold(&self) -> Option<Duration> {
+        (self.slow_op_ms > 0).then(|| Duration::from_millis(self.slow_op_ms))
+    }
+
+    pub fn archive_limits(&self) -> ArchiveLimits {
+        ArchiveLimits {
+            max_entries: self.max_archive_entries,
+            max_total_bytes: self.max_archive_bytes,
+        }
+    }
+}
+
+fn split_list(value: &str, separator: char) -> Vec<String> {
+    value
+        .split(separator)
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse `NAME=DIR` root entries
+fn parse_roots(entries: &[String]) -> Result<BTreeMap<String, PathBuf>, String> {
+    entries
+        .iter()
+        .map(|entry| match entry.split_once('=') {
+            Some((name, dir)) if !name.trim().is_empty() && !dir.trim().is_empty() => {
+                Ok((name.trim().to_string(), PathBuf::from(dir.trim())))
+            }
+            _ => Err(format!("Invalid database root {:?}, expected NAME=DIR", entry)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_layers_file_env_and_args() {
+        let dir = std::env::temp_dir().join(format!("keradb-labs-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("labs.toml");
+        std::fs::write(
+            &path,
+            "port = 6000\nhost = \"0.0.0.0\"\ncors_origins = [\"http://localhost:5173\"]\nopen_databases = [\"/data/a.db\"]\n\n[metrics]\nraw_hours = 6\n",
+        )
+        .unwrap();
+
+        let env: HashMap<&str, &str> = [
+            ("KERADB_LABS_PORT", "6100"),
+            ("KERADB_LABS_MAX_UPLOAD_BYTES", "1024"),
+            ("KERADB_LABS_MAX_ARCHIVE_ENTRIES", "50"),
+            ("KERADB_LABS_DATABASE_ROOTS", "main=/srv/keradb"),
+            ("KERADB_LABS_SLOW_OP_MS", "0"),
+            ("KERADB_HOME", "/srv/keradb-home"),
+            ("KERADB_PROFILE", "personal"),
+        ]
+        .into_iter()
+        .collect();
+        let args = CliArgs {
+            config: Some(path),
+            port: Some(6200),
+            max_archive_bytes: Some(4096),
+            profile: Some("work".to_string()),
+            ..Default::default()
+        };
+
+        let config = ServerConfig::from_sources(&args, |key| env.get(key).map(|v| v.to_string())).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(config.host, "0.0.0.0");
+        assert_eq!(config.port, 6200);
+        assert_eq!(config.max_upload_bytes, 1024);
+        let limits = config.archive_limits();
+        assert_eq!((limits.max_entries, limits.max_total_bytes), (50, 4096));
+        assert_eq!(config.cors_origins, vec!["http://localhost:5173"]);
+        assert_eq!(config.open_databases, vec!["/data/a.db"]);
+        assert_eq!(config.database_roots.get("main"), Some(&PathBuf::from("/srv/keradb")));
+        assert!(!config.allows_any_origin());
+        assert_eq!(config.metrics.raw_hours, 6);
+        assert_eq!(config.metrics.minute_days, MetricsRetention::default().minute_days);
+        assert_eq!(config.slow_op_threshold(), None);
+        let location = config.system_db_location().unwrap();
+        assert_eq!(location.home, PathBuf::from("/srv/keradb-home"));
+        assert_eq!(location.profile.as_deref(), Some("work"));
+        assert_eq!(config.effective_database_roots().unwrap(), config.database_roots);
+        let unrooted = ServerConfig {
+            home: Some(PathBuf::from("/srv/keradb-home")),
+            ..Default::default()
+        };
+        assert_eq!(
+            unrooted.effective_database_roots().unwrap().get("main"),
+            Some(&PathBuf::from("/srv/keradb-home/databases"))
+        );
+        assert_eq!(ServerConfig::default().slow_op_threshold(), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_rejects_invalid_values() {
+        let args = CliArgs::default();
+        let result = ServerConfig::from_sources(&args, |key| {
+            (key == "KERADB_LABS_PORT").then(|| "not-a-port".to_string())
+        });
+        assert!(result.is_err());
+
+        assert!(toml::from_str::<ServerConfig>("prot = 1").is_err());
+
+        let no_auth = CliArgs {
+            no_auth: true,
+            ..Default::default()
+        };
+        assert!(ServerConfig::from_sources(&no_auth, |_| None).is_err());
+        let no_auth = CliArgs {
+            cors_origins: vec!["http://localhost:5173".to_string()],
+            ..no_auth
+        };
+        assert!(!ServerConfig::from_sources(&no_auth, |_| None).unwrap().require_auth);
+        assert!(toml::from_str::<ServerConfig>("[metrics]\nraw_days = 1").is_err());
+    }
+}
@@ -0,0 +1,41 @@
+use crate::auth::AuthError;
+use actix_web::HttpResponse;
+
+pub use keradb_labs_core::error::{ApiError, ErrorBody, ErrorCode};
+
+pub type ApiResult<T = HttpResponse> = Result<T, ApiError>;
+
+impl From<AuthError> for ApiError {
+    fn from(e: AuthError) -> Self {
+        let code = match e {
+            AuthError::MissingToken | AuthError::InvalidToken => ErrorCode::Unauthorized,
+            AuthError::InsufficientScope(_) | AuthError::DatabaseNotAllowed(_) => ErrorCode::Forbidden,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::SandboxError;
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+
+    #[test]
+    fn test_error_body_and_status() {
+        let error = ApiError::db_not_open("main/users.db");
+        assert_eq!(error.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({ "code": "DB_NOT_OPEN", "message": "Database is not open: main/users.db" })
+        );
+
+        let error = ApiError::from(SandboxError::OutsideRoots("/etc".to_string()));
+        assert_eq!(error.code, ErrorCode::PathOutsideRoots);
+        assert_eq!(error.status_code(), StatusCode::FORBIDDEN);
+
+        let error = ApiError::from(AuthError::MissingToken);
+        assert_eq!(error.status_code(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -1,72 +1,139 @@
-mod document_parser;
-mod system_db;
+mod auth;
+mod config;
+mod error;
+mod openapi;
+mod prometheus;
 
 use actix_cors::Cors;
-use actix_web::{web, App, HttpResponse, HttpServer, Result};
+use actix_web::{http, web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer};
+use clap::Parser;
+use config::{CliArgs, ServerConfig};
+use error::{ApiError, ApiResult, ErrorBody, ErrorCode};
 use actix_multipart::Multipart;
 use futures_util::StreamExt;
-use keradb::{Database, VectorConfig, Distance};
-use parking_lot::RwLock;
+use keradb_labs_core::audit::{AuditEntry, AuditQuery};
+use keradb_labs_core::changes::{ChangeEvent, ResumePoint, Subscription};
+use keradb_labs_core::connections::{ConnectionProfileUpdate, ConnectionQuery};
+use keradb_labs_core::jobs::{JobManager, JobRequest};
+use keradb_labs_core::metrics::{self, MetricRollup};
+use keradb_labs_core::queries::{QueryHistoryQuery, QueryResults, QuerySpec, SavedQueryRequest};
+use keradb_labs_core::slow_log::SlowOperationQuery;
+use keradb_labs_core::types::{
+    CollectionInfo, DatabaseInfo, VectorCollectionInfoResponse, VectorDocumentResponse, VectorSearchResultResponse,
+};
+use keradb_labs_core::workspace::{ImportReport, WorkspaceBundle, WorkspaceImport};
+use keradb_labs_core::{parsing, sandbox, system_db, LabsService};
+use sandbox::{DatabaseRoots, SandboxError};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use utoipa::{IntoParams, ToSchema};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use system_db::SystemDatabase;
+use openapi::ParseDocumentUpload;
+use system_db::{
+    DatabaseConnection, Job, Permission, PerformanceMetric, QueryHistoryEntry, SavedQuery, SlowOperation, SystemDatabase,
+    TokenScope,
+};
 
 // App state
 struct AppState {
-    databases: Arc<RwLock<HashMap<String, Arc<Database>>>>,
-    system_db: Arc<SystemDatabase>,
+    service: Arc<LabsService>,
+    jobs: Arc<JobManager>,
+    config: Arc<ServerConfig>,
+    requests: prometheus::RequestMetrics,
 }
 
-// Request/Response types
-#[derive(Serialize, Deserialize)]
-struct OpenDatabaseRequest {
-    path: String,
+impl AppState {
+    /// Map a database reference from a request (connection id, alias, logical
+    /// name or path) to the name databases are tracked under
+    fn database_name(&self, db: &str) -> std::result::Result<String, SandboxError> {
+        self.service.database_name(db)
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-struct DatabaseInfo {
+// Request/Response types
+#[derive(Serialize, Deserialize, ToSchema)]
+struct OpenDatabaseRequest {
     path: String,
-    collections: Vec<CollectionInfo>,
+    /// Optional URL-safe alias to register for this database
+    #[serde(default)]
+    alias: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct CollectionInfo {
-    name: String,
-    count: usize,
-}
-
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct InsertRequest {
     collection: String,
+    #[schema(value_type = Object)]
     document: serde_json::Value,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct UpdateRequest {
     collection: String,
     id: String,
+    #[schema(value_type = Object)]
     document: serde_json::Value,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct DeleteRequest {
     collection: String,
     id: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct DropCollectionRequest {
     collection: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct CloseDatabaseRequest {
+    /// Database id, alias or path
     path: String,
 }
 
+#[derive(Serialize, Deserialize, ToSchema)]
+struct CreateTokenRequest {
+    name: String,
+    scope: TokenScope,
+    /// Databases the token may access; omitted or empty means all
+    #[serde(default)]
+    databases: Vec<String>,
+}
+
+/// Token as returned by the API; the hash is never exposed
+#[derive(Serialize, Deserialize, ToSchema)]
+struct TokenInfo {
+    id: String,
+    name: String,
+    scope: TokenScope,
+    databases: Vec<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl From<&system_db::ApiToken> for TokenInfo {
+    fn from(token: &system_db::ApiToken) -> Self {
+        Self {
+            id: token.id.clone(),
+            name: token.name.clone(),
+            scope: token.scope,
+            databases: token.databases.clone(),
+            created_at: token.created_at,
+            revoked_at: token.revoked_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+struct CreatedToken {
+    #[serde(flatten)]
+    info: TokenInfo,
+    /// Plaintext token; shown only once
+    token: String,
+}
+
 // Vector-related request/response types
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct CreateVectorCollectionRequest {
     name: String,
     dimensions: usize,
@@ -82,67 +149,86 @@ fn default_distance() -> String { "cosine".to_string() }
 fn default_m() -> usize { 16 }
 fn default_ef() -> usize { 200 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct InsertVectorRequest {
     collection: String,
     vector: Vec<f32>,
+    #[schema(value_type = Option<Object>)]
     metadata: Option<serde_json::Value>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct VectorSearchRequest {
     collection: String,
     vector: Vec<f32>,
     k: usize,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct DeleteVectorRequest {
     collection: String,
     id: u64,
 }
 
-#[derive(Serialize, Deserialize)]
-struct VectorCollectionInfoResponse {
-    name: String,
-    count: usize,
-    dimensions: usize,
-    distance: String,
-}
-
-#[derive(Serialize, Deserialize)]
-struct VectorSearchResultResponse {
-    id: u64,
-    score: f32,
-    vector: Vec<f32>,
-    metadata: Option<serde_json::Value>,
-}
-
-#[derive(Serialize, Deserialize)]
-struct VectorDocumentResponse {
-    id: u64,
-    vector: Vec<f32>,
-    metadata: Option<serde_json::Value>,
-    created_at: u64,
-}
-
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams)]
 struct GetAllVectorsRequest {
     collection: String,
     limit: Option<usize>,
     skip: Option<usize>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams)]
 struct QueryRequest {
     collection: String,
     limit: Option<usize>,
     skip: Option<usize>,
 }
 
+#[derive(Serialize, Deserialize, IntoParams)]
+struct JobsQuery {
+    /// Maximum number of jobs to return, most recent first (default 50)
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, IntoParams)]
+struct ChangesQuery {
+    /// Only changes to this database (id, alias, logical name or path)
+    db: Option<String>,
+    /// Only changes to this collection
+    collection: Option<String>,
+    /// Last sequence number received; missed events are replayed first
+    after: Option<u64>,
+    /// Epoch of the events received; required with `after`
+    epoch: Option<String>,
+}
+
+/// Changes buffered per change stream client, enough for a full replay of
+/// the feed's history; a client that falls further behind is sent `missed`
+/// and disconnected
+const CHANGE_BUFFER: usize = 1024;
+
+/// Messages sent over the change stream WebSocket
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ChangeMessage<'a> {
+    Subscribed(&'a Subscription),
+    Change(&'a ChangeEvent),
+    /// The client fell behind; `seq` is the last change it was sent
+    Missed { epoch: &'a str, seq: u64 },
+}
+
 // API Handlers
 
-async fn health_check() -> Result<HttpResponse> {
+/// Health check
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "system",
+    responses(
+        (status = 200, description = "Server is up", body = Object),
+    )
+)]
+async fn health_check() -> ApiResult {
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "status": "ok",
         "service": "keradb-labs",
@@ -150,322 +236,736 @@ async fn health_check() -> Result<HttpResponse> {
     })))
 }
 
-// Convert Windows path to WSL path if needed
-fn normalize_path(path: &str) -> String {
-    // Check if it's a Windows-style path (e.g., D:\path or D:/path)
-    if let Some(drive_and_rest) = path.strip_prefix(|c: char| c.is_ascii_alphabetic()) {
-        if let Some(rest) = drive_and_rest.strip_prefix(":\\") {
-            // Convert D:\path to /mnt/d/path
-            let drive = path.chars().next().unwrap().to_lowercase();
-            let wsl_path = format!("/mnt/{}/{}", drive, rest.replace('\\', "/"));
-            return wsl_path;
-        } else if let Some(rest) = drive_and_rest.strip_prefix(":/") {
-            // Convert D:/path to /mnt/d/path
-            let drive = path.chars().next().unwrap().to_lowercase();
-            let wsl_path = format!("/mnt/{}/{}", drive, rest);
-            return wsl_path;
-        }
-    }
-    // Return as-is if not a Windows path
-    path.to_string()
+/// Prometheus metrics
+///
+/// Request counters and latency histograms per operation, the number of open
+/// databases and per-database collection, document and vector counts.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "system",
+    responses(
+        (status = 200, description = "Prometheus text exposition format", body = String, content_type = "text/plain"),
+    )
+)]
+async fn export_metrics(data: web::Data<AppState>, http_req: HttpRequest) -> ApiResult {
+    let body = prometheus::render(&data.requests, &data.service, |db| {
+        auth::check_database_access(&http_req, db).is_ok()
+    });
+    Ok(HttpResponse::Ok().content_type(prometheus::CONTENT_TYPE).body(body))
+}
+
+async fn route_not_found(req: HttpRequest) -> ApiResult {
+    Err(ApiError::new(
+        ErrorCode::NotFound,
+        format!("No route for {} {}", req.method(), req.path()),
+    ))
 }
 
+/// Open a database, creating it if missing
+#[utoipa::path(
+    post,
+    path = "/api/databases/open",
+    tag = "databases",
+    request_body = OpenDatabaseRequest,
+    responses(
+        (status = 200, description = "Database opened, created if missing", body = DatabaseInfo),
+        (status = 403, description = "Path outside the allowed roots", body = ErrorBody),
+    )
+)]
 async fn open_database(
     data: web::Data<AppState>,
+    http_req: HttpRequest,
     req: web::Json<OpenDatabaseRequest>,
-) -> Result<HttpResponse> {
-    let db_path = normalize_path(&req.path);
-    let start = std::time::Instant::now();
-    
-    log::info!("Opening database: {} (original: {})", db_path, req.path);
-    
-    // Open or create database
-    let db = match Database::open(&db_path) {
-        Ok(db) => db,
-        Err(_) => Database::create(&db_path)
-            .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?,
-    };
-
-    let collections: Vec<CollectionInfo> = db
-        .list_collections()
-        .into_iter()
-        .map(|(name, count)| CollectionInfo { name, count })
-        .collect();
-
-    let total_docs: usize = collections.iter().map(|c| c.count).sum();
-
-    // Store database in state
-    let mut databases = data.databases.write();
-    databases.insert(db_path.clone(), Arc::new(db));
-
-    // Register in system database
-    if let Err(e) = data.system_db.register_connection(&db_path) {
-        log::warn!("Failed to register connection in system db: {}", e);
-    }
-    
-    // Update stats
-    if let Err(e) = data.system_db.update_connection_stats(&db_path, collections.len(), total_docs) {
-        log::warn!("Failed to update connection stats: {}", e);
-    }
-
-    // Record metric
-    let duration = start.elapsed().as_millis() as u64;
-    if let Err(e) = data.system_db.record_metric(&db_path, "open_database", duration) {
-        log::warn!("Failed to record metric: {}", e);
-    }
+) -> ApiResult {
+    let db = data.service.resolve(&req.path)?;
+    auth::check_database_access(&http_req, &db.name)?;
 
-    Ok(HttpResponse::Ok().json(DatabaseInfo {
-        path: db_path,
-        collections,
-    }))
+    let info = data.service.open_database(&db, req.alias.as_deref())?;
+    Ok(HttpResponse::Ok().json(info))
 }
 
+/// Create a database
+#[utoipa::path(
+    post,
+    path = "/api/databases/create",
+    tag = "databases",
+    request_body = OpenDatabaseRequest,
+    responses(
+        (status = 200, description = "Database created", body = DatabaseInfo),
+        (status = 403, description = "Path outside the allowed roots", body = ErrorBody),
+    )
+)]
 async fn create_database(
     data: web::Data<AppState>,
+    http_req: HttpRequest,
     req: web::Json<OpenDatabaseRequest>,
-) -> Result<HttpResponse> {
-    let db_path = req.path.clone();
-    let start = std::time::Instant::now();
-    
-    // Create new database
-    let db = Database::create(&db_path)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
-
-    let collections: Vec<CollectionInfo> = vec![];
-
-    // Store database in state
-    let mut databases = data.databases.write();
-    databases.insert(db_path.clone(), Arc::new(db));
-
-    // Register in system database
-    if let Err(e) = data.system_db.register_connection(&db_path) {
-        log::warn!("Failed to register connection in system db: {}", e);
-    }
+) -> ApiResult {
+    let db = data.service.resolve(&req.path)?;
+    auth::check_database_access(&http_req, &db.name)?;
 
-    // Record metric
-    let duration = start.elapsed().as_millis() as u64;
-    if let Err(e) = data.system_db.record_metric(&db_path, "create_database", duration) {
-        log::warn!("Failed to record metric: {}", e);
-    }
-
-    Ok(HttpResponse::Ok().json(DatabaseInfo {
-        path: db_path,
-        collections,
-    }))
+    let info = data.service.create_database(&db, req.alias.as_deref())?;
+    Ok(HttpResponse::Ok().json(info))
 }
 
-async fn list_databases(data: web::Data<AppState>) -> Result<HttpResponse> {
-    let databases = data.databases.read();
-    let db_list: Vec<String> = databases.keys().cloned().collect();
+/// List open databases
+#[utoipa::path(
+    get,
+    path = "/api/databases",
+    tag = "databases",
+    responses(
+        (status = 200, description = "Names of open databases", body = Vec<String>),
+    )
+)]
+async fn list_databases(data: web::Data<AppState>, http_req: HttpRequest) -> ApiResult {
+    let db_list: Vec<String> = data
+        .service
+        .list_databases()
+        .into_iter()
+        .filter(|path| auth::check_database_access(&http_req, path).is_ok())
+        .collect();
     Ok(HttpResponse::Ok().json(db_list))
 }
 
+/// List document collections
+#[utoipa::path(
+    get,
+    path = "/api/databases/{db}/collections",
+    tag = "databases",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    responses(
+        (status = 200, description = "Document collections", body = Vec<CollectionInfo>),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
 async fn get_collections(
     data: web::Data<AppState>,
     path: web::Path<String>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    let collections: Vec<CollectionInfo> = db
-        .list_collections()
-        .into_iter()
-        .map(|(name, count)| CollectionInfo { name, count })
-        .collect();
-
+) -> ApiResult {
+    let collections = data.service.collections(&path.into_inner())?;
     Ok(HttpResponse::Ok().json(collections))
 }
 
+/// Insert a document
+#[utoipa::path(
+    post,
+    path = "/api/databases/{db}/documents",
+    tag = "documents",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    request_body = InsertRequest,
+    responses(
+        (status = 200, description = "Id of the inserted document", body = Object),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
 async fn insert_document(
     data: web::Data<AppState>,
     path: web::Path<String>,
     req: web::Json<InsertRequest>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    let start = std::time::Instant::now();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    let id = db
-        .insert(&req.collection, req.document.clone())
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
-
-    // Sync to disk
-    db.sync()
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
-
-    // Record metric
-    let duration = start.elapsed().as_millis() as u64;
-    if let Err(e) = data.system_db.record_metric(&db_path, "insert_document", duration) {
-        log::warn!("Failed to record metric: {}", e);
-    }
-
+) -> ApiResult {
+    let req = req.into_inner();
+    let id = data.service.insert_document(&path.into_inner(), &req.collection, req.document)?;
     Ok(HttpResponse::Ok().json(serde_json::json!({ "id": id })))
 }
 
+/// List documents in a collection
+#[utoipa::path(
+    get,
+    path = "/api/databases/{db}/documents",
+    tag = "documents",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+        QueryRequest,
+    ),
+    responses(
+        (status = 200, description = "Matching documents", body = Vec<Object>),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
 async fn find_documents(
     data: web::Data<AppState>,
     path: web::Path<String>,
     query: web::Query<QueryRequest>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    let docs = db
-        .find_all(&query.collection, query.limit, query.skip)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
-
-    let docs_json: Vec<serde_json::Value> = docs
-        .into_iter()
-        .map(|doc| doc.to_value())
-        .collect();
-
-    Ok(HttpResponse::Ok().json(docs_json))
+) -> ApiResult {
+    let docs = data
+        .service
+        .find_documents(&path.into_inner(), &query.collection, query.limit, query.skip)?;
+    Ok(HttpResponse::Ok().json(docs))
 }
 
+/// Get a document by id
+#[utoipa::path(
+    get,
+    path = "/api/databases/{db}/documents/{collection}/{id}",
+    tag = "documents",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+        ("collection" = String, Path, description = "Collection name"),
+        ("id" = String, Path, description = "Document id"),
+    ),
+    responses(
+        (status = 200, description = "The document", body = Object),
+        (status = 404, description = "Database not open or document not found", body = ErrorBody),
+    )
+)]
 async fn find_by_id(
     data: web::Data<AppState>,
     path: web::Path<(String, String, String)>,
-) -> Result<HttpResponse> {
-    let (db_path, collection, doc_id) = path.into_inner();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    let doc = db
-        .find_by_id(&collection, &doc_id)
-        .map_err(|e| actix_web::error::ErrorNotFound(e.to_string()))?;
-
-    Ok(HttpResponse::Ok().json(doc.to_value()))
+) -> ApiResult {
+    let (db, collection, doc_id) = path.into_inner();
+    let doc = data.service.find_by_id(&db, &collection, &doc_id)?;
+    Ok(HttpResponse::Ok().json(doc))
 }
 
+/// Replace a document
+#[utoipa::path(
+    put,
+    path = "/api/databases/{db}/documents",
+    tag = "documents",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    request_body = UpdateRequest,
+    responses(
+        (status = 200, description = "The updated document", body = Object),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
 async fn update_document(
     data: web::Data<AppState>,
     path: web::Path<String>,
     req: web::Json<UpdateRequest>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    let doc = db
-        .update(&req.collection, &req.id, req.document.clone())
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
-
-    Ok(HttpResponse::Ok().json(doc.to_value()))
+) -> ApiResult {
+    let req = req.into_inner();
+    let doc = data
+        .service
+        .update_document(&path.into_inner(), &req.collection, &req.id, req.document)?;
+    Ok(HttpResponse::Ok().json(doc))
 }
 
+/// Delete a document
+#[utoipa::path(
+    delete,
+    path = "/api/databases/{db}/documents",
+    tag = "documents",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    request_body = DeleteRequest,
+    responses(
+        (status = 200, description = "The deleted document", body = Object),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
 async fn delete_document(
     data: web::Data<AppState>,
     path: web::Path<String>,
     req: web::Json<DeleteRequest>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
+) -> ApiResult {
+    let doc = data.service.delete_document(&path.into_inner(), &req.collection, &req.id)?;
+    Ok(HttpResponse::Ok().json(doc))
+}
 
-    let doc = db
-        .delete(&req.collection, &req.id)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+/// Run a query
+///
+/// Runs a document query (filter, sort, projection, paging) or a vector
+/// search and records it in the query history.
+#[utoipa::path(
+    post,
+    path = "/api/databases/{db}/query",
+    tag = "queries",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    request_body = QuerySpec,
+    responses(
+        (status = 200, description = "Matching documents or search results", body = QueryResults),
+        (status = 400, description = "Invalid filter, sort or search parameters", body = ErrorBody),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
+async fn run_query(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Json<QuerySpec>,
+) -> ApiResult {
+    let results = data.service.run_query(&path.into_inner(), &query)?;
+    Ok(HttpResponse::Ok().json(results))
+}
 
-    Ok(HttpResponse::Ok().json(doc.to_value()))
+/// List saved queries
+#[utoipa::path(
+    get,
+    path = "/api/databases/{db}/saved-queries",
+    tag = "queries",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    responses(
+        (status = 200, description = "Saved queries of the database, by name", body = Vec<SavedQuery>),
+    )
+)]
+async fn list_saved_queries(data: web::Data<AppState>, path: web::Path<String>) -> ApiResult {
+    let queries = data.service.saved_queries(&path.into_inner())?;
+    Ok(HttpResponse::Ok().json(queries))
 }
 
-async fn get_stats(
+/// Save a query
+#[utoipa::path(
+    post,
+    path = "/api/databases/{db}/saved-queries",
+    tag = "queries",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    request_body = SavedQueryRequest,
+    responses(
+        (status = 201, description = "The saved query", body = SavedQuery),
+        (status = 400, description = "Invalid query or duplicate name", body = ErrorBody),
+    )
+)]
+async fn create_saved_query(
     data: web::Data<AppState>,
     path: web::Path<String>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
+    req: web::Json<SavedQueryRequest>,
+) -> ApiResult {
+    let saved = data.service.create_saved_query(&path.into_inner(), &req)?;
+    Ok(HttpResponse::Created().json(saved))
+}
 
-    let collections = db.list_collections();
-    let total_docs: usize = collections.iter().map(|(_, count)| count).sum();
+/// Replace a saved query
+#[utoipa::path(
+    put,
+    path = "/api/databases/{db}/saved-queries/{id}",
+    tag = "queries",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+        ("id" = String, Path, description = "Saved query id"),
+    ),
+    request_body = SavedQueryRequest,
+    responses(
+        (status = 200, description = "The updated query", body = SavedQuery),
+        (status = 400, description = "Invalid query or duplicate name", body = ErrorBody),
+        (status = 404, description = "No such saved query for this database", body = ErrorBody),
+    )
+)]
+async fn update_saved_query(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    req: web::Json<SavedQueryRequest>,
+) -> ApiResult {
+    let (db, id) = path.into_inner();
+    let saved = data.service.update_saved_query(&db, &id, &req)?;
+    Ok(HttpResponse::Ok().json(saved))
+}
 
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "path": db_path,
-        "collections": collections.len(),
-        "total_documents": total_docs,
-        "collections_detail": collections
-    })))
+/// Delete a saved query
+#[utoipa::path(
+    delete,
+    path = "/api/databases/{db}/saved-queries/{id}",
+    tag = "queries",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+        ("id" = String, Path, description = "Saved query id"),
+    ),
+    responses(
+        (status = 200, description = "The deleted query", body = SavedQuery),
+        (status = 404, description = "No such saved query for this database", body = ErrorBody),
+    )
+)]
+async fn delete_saved_query(data: web::Data<AppState>, path: web::Path<(String, String)>) -> ApiResult {
+    let (db, id) = path.into_inner();
+    let saved = data.service.delete_saved_query(&db, &id)?;
+    Ok(HttpResponse::Ok().json(saved))
+}
+
+/// Run a saved query
+#[utoipa::path(
+    post,
+    path = "/api/databases/{db}/saved-queries/{id}/run",
+    tag = "queries",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+        ("id" = String, Path, description = "Saved query id"),
+    ),
+    responses(
+        (status = 200, description = "Matching documents or search results", body = QueryResults),
+        (status = 404, description = "No such saved query, or the database is not open", body = ErrorBody),
+    )
+)]
+async fn run_saved_query(data: web::Data<AppState>, path: web::Path<(String, String)>) -> ApiResult {
+    let (db, id) = path.into_inner();
+    let results = data.service.run_saved_query(&db, &id)?;
+    Ok(HttpResponse::Ok().json(results))
+}
+
+/// Query history
+#[utoipa::path(
+    get,
+    path = "/api/databases/{db}/query-history",
+    tag = "queries",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+        QueryHistoryQuery,
+    ),
+    responses(
+        (status = 200, description = "Queries run against the database, most recent first", body = Vec<QueryHistoryEntry>),
+    )
+)]
+async fn get_query_history(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<QueryHistoryQuery>,
+) -> ApiResult {
+    let history = data.service.query_history(&path.into_inner(), &query)?;
+    Ok(HttpResponse::Ok().json(history))
 }
 
-async fn get_system_stats(data: web::Data<AppState>) -> Result<HttpResponse> {
-    let stats = data
-        .system_db
-        .get_system_stats()
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+/// Clear the query history
+#[utoipa::path(
+    delete,
+    path = "/api/databases/{db}/query-history",
+    tag = "queries",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    responses(
+        (status = 200, description = "Number of entries removed", body = Object),
+    )
+)]
+async fn clear_query_history(data: web::Data<AppState>, path: web::Path<String>) -> ApiResult {
+    let removed = data.service.clear_query_history(&path.into_inner())?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "removed": removed })))
+}
 
+/// Re-run a query from the history
+#[utoipa::path(
+    post,
+    path = "/api/databases/{db}/query-history/{id}/run",
+    tag = "queries",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+        ("id" = String, Path, description = "Query history entry id"),
+    ),
+    responses(
+        (status = 200, description = "Matching documents or search results", body = QueryResults),
+        (status = 404, description = "No such history entry, or the database is not open", body = ErrorBody),
+    )
+)]
+async fn rerun_query(data: web::Data<AppState>, path: web::Path<(String, String)>) -> ApiResult {
+    let (db, id) = path.into_inner();
+    let results = data.service.rerun_query(&db, &id)?;
+    Ok(HttpResponse::Ok().json(results))
+}
+
+/// Database statistics
+#[utoipa::path(
+    get,
+    path = "/api/databases/{db}/stats",
+    tag = "databases",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    responses(
+        (status = 200, description = "Collection and document counts", body = Object),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
+async fn get_stats(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> ApiResult {
+    let stats = data.service.database_stats(&path.into_inner())?;
     Ok(HttpResponse::Ok().json(stats))
 }
 
-async fn get_connection_history(data: web::Data<AppState>) -> Result<HttpResponse> {
-    let connections = data
-        .system_db
-        .list_connections()
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+/// System statistics
+#[utoipa::path(
+    get,
+    path = "/api/system/stats",
+    tag = "system",
+    responses(
+        (status = 200, description = "Totals across the registered databases the token may access; schema version and malformed records for admin tokens", body = Object),
+    )
+)]
+async fn get_system_stats(data: web::Data<AppState>, http_req: HttpRequest) -> ApiResult {
+    let stats = data.service.system_stats(
+        |db| auth::check_database_access(&http_req, db).is_ok(),
+        auth::allows(&http_req, Permission::Admin),
+    )?;
+    Ok(HttpResponse::Ok().json(stats))
+}
 
+/// Connection history
+///
+/// Favorites come first, then the most recently used, unless `sort` or
+/// `favorites_first` say otherwise.
+#[utoipa::path(
+    get,
+    path = "/api/system/connections",
+    tag = "system",
+    params(ConnectionQuery),
+    responses(
+        (status = 200, description = "Registered databases matching the query", body = Vec<DatabaseConnection>),
+    )
+)]
+async fn get_connection_history(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    query: web::Query<ConnectionQuery>,
+) -> ApiResult {
+    let connections: Vec<_> = data
+        .service
+        .connection_history(&query)?
+        .into_iter()
+        .filter(|conn| auth::check_database_access(&http_req, &conn.path).is_ok())
+        .collect();
     Ok(HttpResponse::Ok().json(connections))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 struct MetricsQuery {
     limit: Option<usize>,
 }
 
+/// Query raw operation timings
+#[utoipa::path(
+    get,
+    path = "/api/system/metrics",
+    tag = "system",
+    params(metrics::MetricsQuery),
+    responses(
+        (status = 200, description = "Matching operation timings, most recent first", body = Vec<PerformanceMetric>),
+        (status = 400, description = "Invalid time range", body = ErrorBody),
+    )
+)]
+async fn query_metrics(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    query: web::Query<metrics::MetricsQuery>,
+) -> ApiResult {
+    if let Some(db) = &query.db {
+        auth::check_database_access(&http_req, &data.database_name(db)?)?;
+    }
+
+    let samples = data
+        .service
+        .query_metrics(&query, |db| auth::check_database_access(&http_req, db).is_ok())?;
+    Ok(HttpResponse::Ok().json(samples))
+}
+
+/// Operation timing rollups
+///
+/// Count, mean, p50/p95/p99 and max per database, operation and time bucket,
+/// oldest bucket first. The current bucket is included and still changing.
+#[utoipa::path(
+    get,
+    path = "/api/system/rollups",
+    tag = "system",
+    params(metrics::MetricsQuery),
+    responses(
+        (status = 200, description = "Rollups, oldest bucket first", body = Vec<MetricRollup>),
+        (status = 400, description = "Invalid time range", body = ErrorBody),
+    )
+)]
+async fn get_metric_rollups(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    query: web::Query<metrics::MetricsQuery>,
+) -> ApiResult {
+    if let Some(db) = &query.db {
+        auth::check_database_access(&http_req, &data.database_name(db)?)?;
+    }
+
+    let rollups: Vec<MetricRollup> = data
+        .service
+        .metric_rollups(&query)?
+        .into_iter()
+        .filter(|r| auth::check_database_access(&http_req, &r.database_path).is_ok())
+        .collect();
+    Ok(HttpResponse::Ok().json(rollups))
+}
+
+/// Slow operation log
+///
+/// Operations that took at least `slow_op_ms`, with their collection,
+/// parameters, result size and caller, most recent first.
+#[utoipa::path(
+    get,
+    path = "/api/system/slow-operations",
+    tag = "system",
+    params(SlowOperationQuery),
+    responses(
+        (status = 200, description = "Matching slow operations, most recent first", body = Vec<SlowOperation>),
+        (status = 400, description = "Invalid time range", body = ErrorBody),
+    )
+)]
+async fn get_slow_operations(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    query: web::Query<SlowOperationQuery>,
+) -> ApiResult {
+    if let Some(db) = &query.db {
+        auth::check_database_access(&http_req, &data.database_name(db)?)?;
+    }
+
+    let ops = data
+        .service
+        .slow_operations(&query, |db| auth::check_database_access(&http_req, db).is_ok())?;
+    Ok(HttpResponse::Ok().json(ops))
+}
+
+/// Audit log
+///
+/// Recorded writes with their caller and, for updates and deletes of
+/// documents and vectors, the state before the change, most recent first.
+#[utoipa::path(
+    get,
+    path = "/api/system/audit",
+    tag = "system",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Matching audit entries, most recent first", body = Vec<AuditEntry>),
+        (status = 400, description = "Invalid time range", body = ErrorBody),
+    )
+)]
+async fn get_audit_log(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    query: web::Query<AuditQuery>,
+) -> ApiResult {
+    if let Some(db) = &query.db {
+        auth::check_database_access(&http_req, &data.database_name(db)?)?;
+    }
+
+    let entries = data
+        .service
+        .audit_log(&query, |db| auth::check_database_access(&http_req, db).is_ok())?;
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+/// Export the audit log
+///
+/// Matching entries as newline-delimited JSON, oldest first. Unlike
+/// `/api/system/audit` the export is not limited unless `limit` is given.
+#[utoipa::path(
+    get,
+    path = "/api/system/audit/export",
+    tag = "system",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "One audit entry per line", body = String, content_type = "application/x-ndjson"),
+        (status = 400, description = "Invalid time range", body = ErrorBody),
+    )
+)]
+async fn export_audit_log(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    query: web::Query<AuditQuery>,
+) -> ApiResult {
+    if let Some(db) = &query.db {
+        auth::check_database_access(&http_req, &data.database_name(db)?)?;
+    }
+
+    let mut body = Vec::new();
+    data.service.export_audit_log(
+        &query,
+        |db| auth::check_database_access(&http_req, db).is_ok(),
+        &mut body,
+    )?;
+    Ok(HttpResponse::Ok().content_type("application/x-ndjson").body(body))
+}
+
+/// Export the workspace
+///
+/// Connections with their profile settings and saved queries as a portable
+/// bundle. Statistics, history, tokens and the audit log are not included.
+#[utoipa::path(
+    get,
+    path = "/api/system/workspace/export",
+    tag = "system",
+    responses(
+        (status = 200, description = "Workspace bundle", body = WorkspaceBundle),
+    )
+)]
+async fn export_workspace(data: web::Data<AppState>, http_req: HttpRequest) -> ApiResult {
+    let bundle = data
+        .service
+        .export_workspace(|db| auth::check_database_access(&http_req, db).is_ok())?;
+    Ok(HttpResponse::Ok()
+        .insert_header(("Content-Disposition", "attachment; filename=\"keradb-workspace.json\""))
+        .json(bundle))
+}
+
+/// Import a workspace
+///
+/// Registers the connections of a bundle under their remapped paths, applies
+/// their profile settings and adds their saved queries. Databases are not
+/// opened; items that cannot be imported are listed in the report.
+#[utoipa::path(
+    post,
+    path = "/api/system/workspace/import",
+    tag = "system",
+    request_body = WorkspaceImport,
+    responses(
+        (status = 200, description = "What was imported and what was skipped", body = ImportReport),
+        (status = 400, description = "Not a workspace bundle, or one from a newer version", body = ErrorBody),
+    )
+)]
+async fn import_workspace(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    import: web::Json<WorkspaceImport>,
+) -> ApiResult {
+    let report = data
+        .service
+        .import_workspace(&import, |db| auth::check_database_access(&http_req, db).is_ok())?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Performance metrics for a database
+#[utoipa::path(
+    get,
+    path = "/api/system/metrics/{db}",
+    tag = "system",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+        MetricsQuery,
+    ),
+    responses(
+        (status = 200, description = "Recent operation timings", body = Vec<PerformanceMetric>),
+    )
+)]
 async fn get_database_metrics(
     data: web::Data<AppState>,
     path: web::Path<String>,
     query: web::Query<MetricsQuery>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    
-    let metrics = data
-        .system_db
-        .get_metrics(&db_path, query.limit)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
-
+) -> ApiResult {
+    let metrics = data.service.metrics(&path.into_inner(), query.limit)?;
     Ok(HttpResponse::Ok().json(metrics))
 }
 
+/// Remove a connection from the history
+#[utoipa::path(
+    delete,
+    path = "/api/system/connections/{db}",
+    tag = "system",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    responses(
+        (status = 200, description = "Connection removed from the history", body = Object),
+    )
+)]
 async fn remove_connection(
     data: web::Data<AppState>,
     path: web::Path<String>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    
-    // Remove from active connections
-    {
-        let mut databases = data.databases.write();
-        databases.remove(&db_path);
-    }
-
-    // Remove from system database
-    data.system_db
-        .remove_connection(&db_path)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+) -> ApiResult {
+    let db_path = data.service.remove_connection(&path.into_inner())?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Connection removed",
@@ -473,19 +973,52 @@ async fn remove_connection(
     })))
 }
 
+/// Edit a connection profile
+///
+/// Sets the alias, color, favorite flag, notes or folder of a registered
+/// database. Omitted fields are unchanged; an empty string clears a field.
+#[utoipa::path(
+    patch,
+    path = "/api/system/connections/{db}",
+    tag = "system",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    request_body = ConnectionProfileUpdate,
+    responses(
+        (status = 200, description = "Updated connection", body = DatabaseConnection),
+        (status = 400, description = "Invalid or duplicate alias, color, notes or folder", body = ErrorBody),
+        (status = 404, description = "No connection for this database", body = ErrorBody),
+    )
+)]
+async fn update_connection_profile(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    update: web::Json<ConnectionProfileUpdate>,
+) -> ApiResult {
+    let connection = data.service.update_connection_profile(&path.into_inner(), &update)?;
+    Ok(HttpResponse::Ok().json(connection))
+}
+
+/// Close a database
+#[utoipa::path(
+    post,
+    path = "/api/databases/close",
+    tag = "databases",
+    request_body = CloseDatabaseRequest,
+    responses(
+        (status = 200, description = "Database closed", body = Object),
+    )
+)]
 async fn close_database(
     data: web::Data<AppState>,
+    http_req: HttpRequest,
     req: web::Json<CloseDatabaseRequest>,
-) -> Result<HttpResponse> {
-    let db_path = req.path.clone();
-    
-    // Remove from active connections
-    {
-        let mut databases = data.databases.write();
-        databases.remove(&db_path);
-    }
+) -> ApiResult {
+    let db_path = data.database_name(&req.path)?;
+    auth::check_database_access(&http_req, &db_path)?;
 
-    log::info!("Database closed: {}", db_path);
+    let db_path = data.service.close_database(&db_path)?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Database closed successfully",
@@ -493,124 +1026,180 @@ async fn close_database(
     })))
 }
 
+/// Drop a document collection
+#[utoipa::path(
+    delete,
+    path = "/api/databases/{db}/collections",
+    tag = "documents",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    request_body = DropCollectionRequest,
+    responses(
+        (status = 200, description = "Collection dropped", body = Object),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
 async fn drop_collection(
     data: web::Data<AppState>,
     path: web::Path<String>,
     req: web::Json<DropCollectionRequest>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    let collection = req.collection.clone();
-    let start = std::time::Instant::now();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    // Delete all documents in the collection
-    let docs = db
-        .find_all(&collection, None, None)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
-    
-    let mut deleted_count = 0;
-    for doc in docs {
-        if let Err(e) = db.delete(&collection, &doc.id) {
-            log::warn!("Failed to delete document {}: {}", doc.id, e);
-        } else {
-            deleted_count += 1;
-        }
-    }
-
-    // Sync to disk
-    db.sync()
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
-
-    // Record metric
-    let duration = start.elapsed().as_millis() as u64;
-    if let Err(e) = data.system_db.record_metric(&db_path, "drop_collection", duration) {
-        log::warn!("Failed to record metric: {}", e);
-    }
-
-    log::info!("Collection '{}' dropped from database: {} ({} documents deleted)", 
-               collection, db_path, deleted_count);
+) -> ApiResult {
+    let deleted_count = data.service.drop_collection(&path.into_inner(), &req.collection)?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Collection dropped successfully",
-        "collection": collection,
+        "collection": req.collection,
         "documents_deleted": deleted_count
     })))
 }
 
+/// Close a database and delete its file
+#[utoipa::path(
+    delete,
+    path = "/api/databases/{db}",
+    tag = "databases",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    responses(
+        (status = 200, description = "Database file deleted", body = Object),
+        (status = 500, description = "File could not be deleted", body = ErrorBody),
+    )
+)]
 async fn delete_database(
     data: web::Data<AppState>,
     path: web::Path<String>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    
-    // First, close the database connection
-    {
-        let mut databases = data.databases.write();
-        databases.remove(&db_path);
-    }
+) -> ApiResult {
+    let db_path = data.service.delete_database(&path.into_inner())?;
 
-    // Delete the database file
-    match std::fs::remove_file(&db_path) {
-        Ok(_) => {
-            // Remove from system database
-            if let Err(e) = data.system_db.remove_connection(&db_path) {
-                log::warn!("Failed to remove connection from system db: {}", e);
-            }
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Database deleted successfully",
+        "path": db_path
+    })))
+}
 
-            log::info!("Database deleted: {}", db_path);
+// ============================================================
+// API Token Handlers
+// ============================================================
 
-            Ok(HttpResponse::Ok().json(serde_json::json!({
-                "message": "Database deleted successfully",
-                "path": db_path
-            })))
-        }
-        Err(e) => {
-            log::error!("Failed to delete database file {}: {}", db_path, e);
-            Err(actix_web::error::ErrorInternalServerError(format!(
-                "Failed to delete database file: {}",
-                e
-            )))
-        }
+/// Create an API token
+#[utoipa::path(
+    post,
+    path = "/api/system/tokens",
+    tag = "tokens",
+    request_body = CreateTokenRequest,
+    responses(
+        (status = 200, description = "Token created; the plaintext token is only returned here", body = CreatedToken),
+    )
+)]
+async fn create_token(
+    data: web::Data<AppState>,
+    req: web::Json<CreateTokenRequest>,
+) -> ApiResult {
+    let databases = req
+        .databases
+        .iter()
+        .map(|db| data.database_name(db))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let (token, secret) = data
+        .service
+        .system_db()
+        .create_token(&req.name, req.scope, databases)?;
+
+    log::info!("API token created: {} ({:?})", token.name, token.scope);
+
+    Ok(HttpResponse::Ok().json(CreatedToken {
+        info: TokenInfo::from(&token),
+        token: secret,
+    }))
+}
+
+/// List API tokens
+#[utoipa::path(
+    get,
+    path = "/api/system/tokens",
+    tag = "tokens",
+    responses(
+        (status = 200, description = "All tokens, without secrets", body = Vec<TokenInfo>),
+    )
+)]
+async fn list_tokens(data: web::Data<AppState>) -> ApiResult {
+    let tokens = data
+        .service
+        .system_db()
+        .list_tokens()?;
+
+    let tokens: Vec<TokenInfo> = tokens.iter().map(TokenInfo::from).collect();
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+/// Revoke an API token
+#[utoipa::path(
+    delete,
+    path = "/api/system/tokens/{id}",
+    tag = "tokens",
+    params(
+        ("id" = String, Path, description = "Token id"),
+    ),
+    responses(
+        (status = 200, description = "Token revoked", body = Object),
+        (status = 404, description = "Token not found", body = ErrorBody),
+    )
+)]
+async fn revoke_token(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> ApiResult {
+    let token_id = path.into_inner();
+
+    let revoked = data
+        .service
+        .system_db()
+        .revoke_token(&token_id)?;
+
+    if !revoked {
+        return Err(ApiError::new(ErrorCode::NotFound, format!("Token not found: {}", token_id)));
     }
+
+    log::info!("API token revoked: {}", token_id);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Token revoked",
+        "id": token_id
+    })))
 }
 
 // ============================================================
 // Vector Database API Handlers
 // ============================================================
 
-fn parse_distance(s: &str) -> Distance {
-    match s.to_lowercase().as_str() {
-        "euclidean" | "l2" => Distance::Euclidean,
-        "dot" | "dot_product" | "dotproduct" => Distance::DotProduct,
-        "manhattan" | "l1" => Distance::Manhattan,
-        _ => Distance::Cosine, // Default
-    }
-}
-
+/// Create a vector collection
+#[utoipa::path(
+    post,
+    path = "/api/databases/{db}/vectors/collections",
+    tag = "vectors",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    request_body = CreateVectorCollectionRequest,
+    responses(
+        (status = 200, description = "Collection created", body = Object),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
 async fn create_vector_collection(
     data: web::Data<AppState>,
     path: web::Path<String>,
     req: web::Json<CreateVectorCollectionRequest>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    let config = VectorConfig::new(req.dimensions)
-        .with_distance(parse_distance(&req.distance))
-        .with_m(req.m);
-
-    db.create_vector_collection(&req.name, config)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
-
-    log::info!("Vector collection '{}' created in database: {}", req.name, db_path);
+) -> ApiResult {
+    data.service.create_vector_collection(
+        &path.into_inner(),
+        &req.name,
+        req.dimensions,
+        &req.distance,
+        Some(req.m),
+    )?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Vector collection created successfully",
@@ -620,232 +1209,177 @@ async fn create_vector_collection(
     })))
 }
 
+/// List vector collections
+#[utoipa::path(
+    get,
+    path = "/api/databases/{db}/vectors/collections",
+    tag = "vectors",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    responses(
+        (status = 200, description = "Vector collections", body = Vec<VectorCollectionInfoResponse>),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
 async fn list_vector_collections(
     data: web::Data<AppState>,
     path: web::Path<String>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    let collections = db.list_vector_collections();
-    
-    // Get detailed stats for each collection
-    let collection_infos: Vec<serde_json::Value> = collections
-        .iter()
-        .filter_map(|(name, count)| {
-            db.vector_stats(name).ok().map(|stats| {
-                serde_json::json!({
-                    "name": name,
-                    "count": count,
-                    "dimensions": stats.dimensions,
-                    "distance": stats.distance.name()
-                })
-            })
-        })
-        .collect();
-
-    Ok(HttpResponse::Ok().json(collection_infos))
+) -> ApiResult {
+    let collections = data.service.vector_collections(&path.into_inner())?;
+    Ok(HttpResponse::Ok().json(collections))
 }
 
+/// Vector collection statistics
+#[utoipa::path(
+    get,
+    path = "/api/databases/{db}/vectors/collections/{collection}/stats",
+    tag = "vectors",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+        ("collection" = String, Path, description = "Collection name"),
+    ),
+    responses(
+        (status = 200, description = "Index statistics", body = Object),
+        (status = 404, description = "Database not open or collection not found", body = ErrorBody),
+    )
+)]
 async fn get_vector_collection_stats(
     data: web::Data<AppState>,
     path: web::Path<(String, String)>,
-) -> Result<HttpResponse> {
-    let (db_path, collection_name) = path.into_inner();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    let stats = db.vector_stats(&collection_name)
-        .map_err(|e| actix_web::error::ErrorNotFound(e.to_string()))?;
-
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "name": stats.name,
-        "vector_count": stats.vector_count,
-        "dimensions": stats.dimensions,
-        "distance": stats.distance.name(),
-        "memory_bytes": stats.memory_bytes,
-        "hnsw_m": stats.hnsw_layers,
-        "lazy_embedding": stats.lazy_embedding,
-        "compression_mode": format!("{:?}", stats.compression_mode)
-    })))
+) -> ApiResult {
+    let (db, collection_name) = path.into_inner();
+    let stats = data.service.vector_collection_stats(&db, &collection_name)?;
+    Ok(HttpResponse::Ok().json(stats))
 }
 
+/// Insert a vector
+#[utoipa::path(
+    post,
+    path = "/api/databases/{db}/vectors",
+    tag = "vectors",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    request_body = InsertVectorRequest,
+    responses(
+        (status = 200, description = "Id of the inserted vector", body = Object),
+        (status = 400, description = "Vector length does not match the collection", body = ErrorBody),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
 async fn insert_vector(
     data: web::Data<AppState>,
     path: web::Path<String>,
     req: web::Json<InsertVectorRequest>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    let start = std::time::Instant::now();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    let id = db.insert_vector(&req.collection, req.vector.clone(), req.metadata.clone())
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
-
-    // Record metric
-    let duration = start.elapsed().as_millis() as u64;
-    if let Err(e) = data.system_db.record_metric(&db_path, "insert_vector", duration) {
-        log::warn!("Failed to record metric: {}", e);
-    }
+) -> ApiResult {
+    let req = req.into_inner();
+    let dimensions = req.vector.len();
+    let id = data
+        .service
+        .insert_vector(&path.into_inner(), &req.collection, req.vector, req.metadata)?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "id": id,
-        "dimensions": req.vector.len()
+        "dimensions": dimensions
     })))
 }
 
+/// Nearest-neighbour search
+#[utoipa::path(
+    post,
+    path = "/api/databases/{db}/vectors/search",
+    tag = "vectors",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    request_body = VectorSearchRequest,
+    responses(
+        (status = 200, description = "Nearest neighbours, best first", body = Vec<VectorSearchResultResponse>),
+        (status = 400, description = "Vector length does not match the collection", body = ErrorBody),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
 async fn vector_search(
     data: web::Data<AppState>,
     path: web::Path<String>,
     req: web::Json<VectorSearchRequest>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    let start = std::time::Instant::now();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    let results = db.vector_search(&req.collection, &req.vector, req.k)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
-
-    let response: Vec<VectorSearchResultResponse> = results
-        .into_iter()
-        .map(|r| VectorSearchResultResponse {
-            id: r.document.id,
-            score: r.score,
-            vector: r.document.embedding.clone().unwrap_or_default(),
-            metadata: if r.document.metadata == serde_json::Value::Null { 
-                None 
-            } else { 
-                Some(r.document.metadata.clone()) 
-            },
-        })
-        .collect();
-
-    // Record metric
-    let duration = start.elapsed().as_millis() as u64;
-    if let Err(e) = data.system_db.record_metric(&db_path, "vector_search", duration) {
-        log::warn!("Failed to record metric: {}", e);
-    }
-
-    Ok(HttpResponse::Ok().json(response))
+) -> ApiResult {
+    let results = data
+        .service
+        .vector_search(&path.into_inner(), &req.collection, &req.vector, req.k)?;
+    Ok(HttpResponse::Ok().json(results))
 }
 
+/// Get a vector by id
+#[utoipa::path(
+    get,
+    path = "/api/databases/{db}/vectors/{collection}/{id}",
+    tag = "vectors",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+        ("collection" = String, Path, description = "Collection name"),
+        ("id" = u64, Path, description = "Vector id"),
+    ),
+    responses(
+        (status = 200, description = "The vector", body = VectorDocumentResponse),
+        (status = 404, description = "Database not open or vector not found", body = ErrorBody),
+    )
+)]
 async fn get_vector(
     data: web::Data<AppState>,
     path: web::Path<(String, String, u64)>,
-) -> Result<HttpResponse> {
-    let (db_path, collection_name, vector_id) = path.into_inner();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    let doc = db.get_vector(&collection_name, vector_id)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Vector not found"))?;
-
-    let response = VectorDocumentResponse {
-        id: doc.id,
-        vector: doc.embedding.clone().unwrap_or_default(),
-        metadata: if doc.metadata == serde_json::Value::Null { 
-            None 
-        } else { 
-            Some(doc.metadata.clone()) 
-        },
-        created_at: 0, // VectorDocument doesn't have created_at, use 0
-    };
-
-    Ok(HttpResponse::Ok().json(response))
+) -> ApiResult {
+    let (db, collection_name, vector_id) = path.into_inner();
+    let vector = data.service.get_vector(&db, &collection_name, vector_id)?;
+    Ok(HttpResponse::Ok().json(vector))
 }
 
+/// List vectors in a collection
+#[utoipa::path(
+    get,
+    path = "/api/databases/{db}/vectors",
+    tag = "vectors",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+        GetAllVectorsRequest,
+    ),
+    responses(
+        (status = 200, description = "A page of vectors", body = Object),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
 async fn get_all_vectors(
     data: web::Data<AppState>,
     path: web::Path<String>,
     query: web::Query<GetAllVectorsRequest>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    // Get collection stats to get all IDs
-    let stats = db.vector_stats(&query.collection)
-        .map_err(|e| actix_web::error::ErrorNotFound(e.to_string()))?;
-    
-    let limit = query.limit.unwrap_or(100);
-    let skip = query.skip.unwrap_or(0);
-    
-    // Fetch vectors by iterating through IDs
-    let mut vectors: Vec<VectorDocumentResponse> = Vec::new();
-    let mut found = 0;
-    let mut skipped = 0;
-    
-    // We need to iterate through possible vector IDs
-    // This is a simple approach - in production you'd want an iterator
-    for id in 0..stats.vector_count as u64 + skip as u64 + 100 {
-        if let Ok(Some(doc)) = db.get_vector(&query.collection, id) {
-            if skipped < skip {
-                skipped += 1;
-                continue;
-            }
-            
-            vectors.push(VectorDocumentResponse {
-                id: doc.id,
-                vector: doc.embedding.clone().unwrap_or_default(),
-                metadata: if doc.metadata == serde_json::Value::Null { 
-                    None 
-                } else { 
-                    Some(doc.metadata.clone()) 
-                },
-                created_at: 0, // VectorDocument doesn't have created_at
-            });
-            
-            found += 1;
-            if found >= limit {
-                break;
-            }
-        }
-    }
-
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "vectors": vectors,
-        "total": stats.vector_count,
-        "limit": limit,
-        "skip": skip
-    })))
+) -> ApiResult {
+    let page = data
+        .service
+        .vectors(&path.into_inner(), &query.collection, query.limit, query.skip)?;
+    Ok(HttpResponse::Ok().json(page))
 }
 
+/// Delete a vector
+#[utoipa::path(
+    delete,
+    path = "/api/databases/{db}/vectors",
+    tag = "vectors",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+    ),
+    request_body = DeleteVectorRequest,
+    responses(
+        (status = 200, description = "Whether the vector existed", body = Object),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
 async fn delete_vector(
     data: web::Data<AppState>,
     path: web::Path<String>,
     req: web::Json<DeleteVectorRequest>,
-) -> Result<HttpResponse> {
-    let db_path = path.into_inner();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    let deleted = db.delete_vector(&req.collection, req.id)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+) -> ApiResult {
+    let deleted = data.service.delete_vector(&path.into_inner(), &req.collection, req.id)?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "deleted": deleted,
@@ -853,21 +1387,26 @@ async fn delete_vector(
     })))
 }
 
+/// Drop a vector collection
+#[utoipa::path(
+    delete,
+    path = "/api/databases/{db}/vectors/collections/{collection}",
+    tag = "vectors",
+    params(
+        ("db" = String, Path, description = "Database id, alias, logical name or percent-encoded path"),
+        ("collection" = String, Path, description = "Collection name"),
+    ),
+    responses(
+        (status = 200, description = "Collection dropped", body = Object),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
 async fn drop_vector_collection(
     data: web::Data<AppState>,
     path: web::Path<(String, String)>,
-) -> Result<HttpResponse> {
-    let (db_path, collection_name) = path.into_inner();
-    let databases = data.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| actix_web::error::ErrorNotFound("Database not found"))?;
-
-    let dropped = db.drop_vector_collection(&collection_name)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
-
-    log::info!("Vector collection '{}' dropped from database: {}", collection_name, db_path);
+) -> ApiResult {
+    let (db, collection_name) = path.into_inner();
+    let dropped = data.service.drop_vector_collection(&db, &collection_name)?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "dropped": dropped,
@@ -876,13 +1415,29 @@ async fn drop_vector_collection(
 }
 
 // Document parsing endpoint
-async fn parse_document(mut payload: Multipart) -> Result<HttpResponse> {
+/// Extract text from an uploaded document
+#[utoipa::path(
+    post,
+    path = "/api/parse-document",
+    tag = "documents",
+    request_body(content = ParseDocumentUpload, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Extracted text and metadata, or per-file results for archives", body = Object),
+        (status = 413, description = "Upload too large", body = ErrorBody),
+        (status = 422, description = "Document could not be parsed", body = ErrorBody),
+    )
+)]
+async fn parse_document(
+    data: web::Data<AppState>,
+    mut payload: Multipart,
+) -> ApiResult {
+    let max_upload_bytes = data.config.max_upload_bytes;
     let mut file_data: Vec<u8> = Vec::new();
     let mut filename = String::new();
 
     // Process multipart form data
     while let Some(item) = payload.next().await {
-        let mut field = item.map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+        let mut field = item.map_err(|e| ApiError::invalid_request(e.to_string()))?;
         
         // Get filename from content disposition
         if let Some(content_disposition) = field.content_disposition() {
@@ -893,78 +1448,431 @@ async fn parse_document(mut payload: Multipart) -> Result<HttpResponse> {
 
         // Read file data
         while let Some(chunk) = field.next().await {
-            let data = chunk.map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
-            file_data.extend_from_slice(&data);
+            let chunk = chunk.map_err(|e| ApiError::invalid_request(e.to_string()))?;
+            if file_data.len() + chunk.len() > max_upload_bytes {
+                return Err(ApiError::new(
+                    ErrorCode::PayloadTooLarge,
+                    format!("File exceeds the {} byte upload limit", max_upload_bytes),
+                ));
+            }
+            file_data.extend_from_slice(&chunk);
         }
     }
 
     if filename.is_empty() {
-        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "No filename provided"
-        })));
+        return Err(ApiError::invalid_request("No filename provided"));
     }
 
     if file_data.is_empty() {
-        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "No file data received"
-        })));
+        return Err(ApiError::invalid_request("No file data received"));
     }
 
-    log::info!("Parsing document: {} ({} bytes)", filename, file_data.len());
-
-    match document_parser::parse_document(&file_data, &filename) {
-        Ok(parsed) => {
-            log::info!("Successfully parsed {}: {} chars, {} pages", 
-                filename, parsed.text.len(), parsed.pages);
-            
-            Ok(HttpResponse::Ok().json(serde_json::json!({
-                "success": true,
-                "filename": filename,
-                "text": parsed.text,
-                "pages": parsed.pages,
-                "file_type": parsed.file_type,
-                "char_count": parsed.text.len()
-            })))
-        }
-        Err(e) => {
-            log::error!("Failed to parse {}: {}", filename, e);
-            Ok(HttpResponse::Ok().json(serde_json::json!({
-                "success": false,
-                "filename": filename,
-                "error": e
-            })))
+    // Parsing is CPU-bound and can take a while for large archives, so keep
+    // it off the worker threads
+    let limits = data.config.archive_limits();
+    let response = web::block(move || parsing::parse_bytes(&file_data, &filename, limits, |_| {}))
+        .await
+        .map_err(ApiError::internal)??;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Submit a background job
+#[utoipa::path(
+    post,
+    path = "/api/jobs",
+    tag = "jobs",
+    request_body = JobRequest,
+    responses(
+        (status = 202, description = "Job queued", body = Job),
+        (status = 404, description = "Database is not open", body = ErrorBody),
+    )
+)]
+async fn submit_job(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    req: web::Json<JobRequest>,
+) -> ApiResult {
+    auth::check_database_access(&http_req, &data.database_name(req.db())?)?;
+
+    let job = data.jobs.submit(req.into_inner())?;
+    Ok(HttpResponse::Accepted().json(job))
+}
+
+/// List job history
+#[utoipa::path(
+    get,
+    path = "/api/jobs",
+    tag = "jobs",
+    params(JobsQuery),
+    responses(
+        (status = 200, description = "Jobs, most recent first", body = Vec<Job>),
+    )
+)]
+async fn list_jobs(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    query: web::Query<JobsQuery>,
+) -> ApiResult {
+    let jobs: Vec<Job> = data
+        .jobs
+        .list(query.limit)?
+        .into_iter()
+        .filter(|job| auth::check_database_access(&http_req, &job.database).is_ok())
+        .collect();
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+/// Get a job's status and progress
+#[utoipa::path(
+    get,
+    path = "/api/jobs/{id}",
+    tag = "jobs",
+    params(
+        ("id" = String, Path, description = "Job id"),
+    ),
+    responses(
+        (status = 200, description = "The job", body = Job),
+        (status = 404, description = "Job not found", body = ErrorBody),
+    )
+)]
+async fn get_job(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResult {
+    let job = data.jobs.get(&path.into_inner())?;
+    auth::check_database_access(&http_req, &job.database)?;
+    Ok(HttpResponse::Ok().json(job))
+}
+
+/// Cancel a job
+///
+/// The job stops after the item it is working on and finishes as `cancelled`,
+/// keeping what it already did. Finished jobs are returned unchanged.
+#[utoipa::path(
+    post,
+    path = "/api/jobs/{id}/cancel",
+    tag = "jobs",
+    params(
+        ("id" = String, Path, description = "Job id"),
+    ),
+    responses(
+        (status = 200, description = "The job at the time of the request", body = Job),
+        (status = 404, description = "Job not found", body = ErrorBody),
+    )
+)]
+async fn cancel_job(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResult {
+    let id = path.into_inner();
+    auth::check_database_access(&http_req, &data.jobs.get(&id)?.database)?;
+    let job = data.jobs.cancel(&id)?;
+    Ok(HttpResponse::Ok().json(job))
+}
+
+/// Stream a job's progress
+///
+/// Server-sent events: one `job` event with the current state, then one per
+/// update until the job finishes, after which the stream ends.
+#[utoipa::path(
+    get,
+    path = "/api/jobs/{id}/events",
+    tag = "jobs",
+    params(
+        ("id" = String, Path, description = "Job id"),
+    ),
+    responses(
+        (status = 200, description = "`text/event-stream` of `job` events carrying a Job", body = Job),
+        (status = 404, description = "Job not found", body = ErrorBody),
+    )
+)]
+async fn job_events(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResult {
+    let id = path.into_inner();
+
+    // Subscribe before reading the current state so no update falls in between
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let job_id = id.clone();
+    let listener = data.jobs.subscribe(move |job| job.id != job_id || tx.send(job.clone()).is_ok());
+    let guard = JobListener { jobs: data.jobs.clone(), id: listener };
+
+    let job = data.jobs.get(&id)?;
+    auth::check_database_access(&http_req, &job.database)?;
+
+    // Ends right after the update that finishes the job
+    let events = futures_util::stream::unfold(Some((Some(job), rx, guard)), |state| async move {
+        let (current, mut rx, guard) = state?;
+        let job = match current {
+            Some(job) => job,
+            None => rx.recv().await?,
+        };
+
+        let event = format!("event: job\ndata: {}\n\n", serde_json::to_string(&job).unwrap_or_default());
+        let next = (!job.status.is_finished()).then_some((None, rx, guard));
+        Some((Ok::<_, actix_web::Error>(web::Bytes::from(event)), next))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events))
+}
+
+/// Removes a job listener when the event stream is dropped
+struct JobListener {
+    jobs: Arc<JobManager>,
+    id: u64,
+}
+
+impl Drop for JobListener {
+    fn drop(&mut self) {
+        self.jobs.unsubscribe(self.id);
+    }
+}
+
+/// Stream changes to documents and vectors
+///
+/// Upgrades to a WebSocket. The first message is `{"type": "subscribed", "epoch", "seq", "missed"}`;
+/// every change follows as `{"type": "change", ...}` with its epoch and sequence number. Reconnect
+/// with `epoch` and `after` set to the last ones received to resume; if `missed` is true, reload
+/// instead. A client that falls too far behind is sent `{"type": "missed", "epoch", "seq"}` and
+/// disconnected.
+/// Browsers that cannot set headers may pass the token as `access_token`.
+#[utoipa::path(
+    get,
+    path = "/api/changes",
+    tag = "changes",
+    params(ChangesQuery),
+    responses(
+        (status = 101, description = "Switching to the WebSocket protocol"),
+        (status = 400, description = "Not a WebSocket handshake", body = ErrorBody),
+        (status = 403, description = "Token may not access the database", body = ErrorBody),
+    )
+)]
+async fn stream_changes(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    query: web::Query<ChangesQuery>,
+    body: web::Payload,
+) -> ApiResult {
+    let db = query.db.as_deref().map(|db| data.database_name(db)).transpose()?;
+    if let Some(db) = &db {
+        auth::check_database_access(&http_req, db)?;
+    }
+    // Without a database filter, only forward databases the token may access
+    let context = http_req.extensions().get::<auth::AuthContext>().cloned();
+
+    let (response, session, messages) =
+        actix_ws::handle(&http_req, body).map_err(|e| ApiError::invalid_request(e.to_string()))?;
+
+    let (tx, rx) = tokio::sync::mpsc::channel(CHANGE_BUFFER);
+    let overflowed = Arc::new(AtomicBool::new(false));
+    let overflow = overflowed.clone();
+    let subscription = data.service.subscribe_changes(
+        db.as_deref(),
+        query.collection.as_deref(),
+        query.after.map(|seq| ResumePoint {
+            epoch: query.epoch.clone().unwrap_or_default(),
+            seq,
+        }),
+        move |event| {
+            if context.as_ref().is_some_and(|context| !context.can_access_database(&event.db)) {
+                return true;
+            }
+            match tx.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
+                    overflow.store(true, Ordering::Relaxed);
+                    false
+                }
+                Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => false,
+            }
+        },
+    )?;
+
+    let stream = ChangeStream { subscription, overflowed, events: rx };
+    actix_web::rt::spawn(forward_changes(data.service.clone(), stream, session, messages));
+    Ok(response)
+}
+
+/// A change subscription and the buffer its sink fills
+struct ChangeStream {
+    subscription: Subscription,
+    /// Set when the buffer was full and the sink unsubscribed
+    overflowed: Arc<AtomicBool>,
+    events: tokio::sync::mpsc::Receiver<ChangeEvent>,
+}
+
+// Relay events to the socket until either side goes away
+async fn forward_changes(
+    service: Arc<LabsService>,
+    mut stream: ChangeStream,
+    mut session: actix_ws::Session,
+    mut messages: actix_ws::MessageStream,
+) {
+    let send = |message: ChangeMessage| serde_json::to_string(&message).unwrap_or_default();
+    let subscription = &stream.subscription;
+    let mut last_seq = subscription.seq;
+
+    if session.text(send(ChangeMessage::Subscribed(subscription))).await.is_ok() {
+        loop {
+            tokio::select! {
+                event = stream.events.recv() => match event {
+                    Some(event) => {
+                        if session.text(send(ChangeMessage::Change(&event))).await.is_err() {
+                            break;
+                        }
+                        last_seq = event.seq;
+                    }
+                    // The sink is gone, after the buffered events were sent
+                    None => {
+                        if stream.overflowed.load(Ordering::Relaxed) {
+                            let missed = ChangeMessage::Missed { epoch: &subscription.epoch, seq: last_seq };
+                            let _ = session.text(send(missed)).await;
+                        }
+                        break;
+                    }
+                },
+                message = messages.next() => match message {
+                    Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(actix_ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+            }
         }
     }
+
+    service.changes().unsubscribe(subscription.id);
+    let _ = session.close(None).await;
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    let args = CliArgs::parse();
+    let config = ServerConfig::load(&args)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+    env_logger::init_from_env(env_logger::Env::new().default_filter_or(&config.log_level));
+
+    if args.list_profiles {
+        let location = config
+            .system_db_location()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let profiles = location.profiles().map_err(|e| std::io::Error::other(e.to_string()))?;
+        for profile in profiles {
+            println!("{}", profile);
+        }
+        return Ok(());
+    }
 
     // Initialize system database
-    let system_db = SystemDatabase::init()
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-    
+    let system_db = if config.ephemeral {
+        log::info!("Using an ephemeral system database");
+        SystemDatabase::ephemeral()
+    } else if let Some(path) = &config.system_db_path {
+        SystemDatabase::open_at(path)
+    } else {
+        let location = config
+            .system_db_location()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        SystemDatabase::open(&location)
+    }
+    .map_err(|e| std::io::Error::other(e.to_string()))?;
+
     log::info!("System database initialized");
 
+    if let Some(name) = &args.create_admin_token {
+        let (_, secret) = system_db
+            .create_token(name, TokenScope::Admin, Vec::new())
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        println!("{}", secret);
+        return Ok(());
+    }
+
+    if config.require_auth {
+        log::info!("Bearer token authentication is required for /api/ routes");
+    } else {
+        log::warn!("Authentication is off; only admin routes require a token");
+    }
+
+    let database_roots = config
+        .effective_database_roots()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let roots = DatabaseRoots::new(&database_roots)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    log::info!("Databases restricted to roots: {:?}", database_roots);
+
+    let service = Arc::new(LabsService::new(system_db, roots).with_slow_op_threshold(config.slow_op_threshold()));
+    let jobs = JobManager::new(service.clone());
+    metrics::spawn_compaction(service.clone(), config.metrics.clone());
+
+    for path in &config.open_databases {
+        let opened = service
+            .resolve(path)
+            .map_err(ApiError::from)
+            .and_then(|db| service.open_database(&db, None));
+        match opened {
+            Ok(info) => log::info!("Opened database at startup: {} ({} collections)", info.path, info.collections.len()),
+            Err(e) => log::error!("Failed to open database {} at startup: {}", path, e),
+        }
+    }
+
+    let config = Arc::new(config);
     let state = web::Data::new(AppState {
-        databases: Arc::new(RwLock::new(HashMap::new())),
-        system_db: Arc::new(system_db),
+        service,
+        jobs,
+        config: config.clone(),
+        requests: prometheus::RequestMetrics::new(&<openapi::ApiDoc as utoipa::OpenApi>::openapi()),
     });
 
-    log::info!("Starting keradb Labs API server on http://localhost:5800");
+    log::info!("Starting keradb Labs API server on http://{}:{}", config.host, config.port);
+
+    let bind_addr = (config.host.clone(), config.port);
 
-    HttpServer::new(move || {
-        let cors = Cors::default()
-            .allow_any_origin()
-            .allow_any_method()
-            .allow_any_header()
+    let flush_state = state.clone();
+    let server = HttpServer::new(move || {
+        let mut cors = Cors::default()
+            .allowed_methods(["GET", "POST", "PUT", "PATCH", "DELETE"])
+            .allowed_headers([http::header::AUTHORIZATION, http::header::CONTENT_TYPE, http::header::ACCEPT])
             .max_age(3600);
 
+        if config.allows_any_origin() {
+            cors = cors.allow_any_origin();
+        } else {
+            for origin in &config.cors_origins {
+                cors = cors.allowed_origin(origin);
+            }
+        }
+
         App::new()
+            .wrap(actix_web::middleware::from_fn(auth::scope_caller))
+            .wrap(actix_web::middleware::from_fn(auth::require_token))
             .wrap(cors)
+            .wrap(actix_web::middleware::from_fn(prometheus::track_requests))
             .app_data(state.clone())
+            .app_data(web::JsonConfig::default().error_handler(|err, _| {
+                ApiError::invalid_request(err.to_string()).into()
+            }))
+            .app_data(web::QueryConfig::default().error_handler(|err, _| {
+                ApiError::invalid_request(err.to_string()).into()
+            }))
+            .app_data(web::PathConfig::default().error_handler(|err, _| {
+                ApiError::invalid_request(err.to_string()).into()
+            }))
             .route("/health", web::get().to(health_check))
+            .route(prometheus::METRICS_PATH, web::get().to(export_metrics))
+            // API description
+            .service(openapi::docs_service())
+            .route("/api/docs", web::get().to(openapi::docs_redirect))
             // Database management
             .route("/api/databases", web::get().to(list_databases))
             .route("/api/databases/open", web::post().to(open_database))
@@ -980,11 +1888,32 @@ async fn main() -> std::io::Result<()> {
             .route("/api/databases/{db}/documents/{collection}/{id}", web::get().to(find_by_id))
             .route("/api/databases/{db}/documents", web::put().to(update_document))
             .route("/api/databases/{db}/documents", web::delete().to(delete_document))
+            // Queries
+            .route("/api/databases/{db}/query", web::post().to(run_query))
+            .route("/api/databases/{db}/saved-queries", web::get().to(list_saved_queries))
+            .route("/api/databases/{db}/saved-queries", web::post().to(create_saved_query))
+            .route("/api/databases/{db}/saved-queries/{id}", web::put().to(update_saved_query))
+            .route("/api/databases/{db}/saved-queries/{id}", web::delete().to(delete_saved_query))
+            .route("/api/databases/{db}/saved-queries/{id}/run", web::post().to(run_saved_query))
+            .route("/api/databases/{db}/query-history", web::get().to(get_query_history))
+            .route("/api/databases/{db}/query-history", web::delete().to(clear_query_history))
+            .route("/api/databases/{db}/query-history/{id}/run", web::post().to(rerun_query))
             // System database endpoints
             .route("/api/system/stats", web::get().to(get_system_stats))
             .route("/api/system/connections", web::get().to(get_connection_history))
+            .route("/api/system/connections/{db}", web::patch().to(update_connection_profile))
             .route("/api/system/connections/{db}", web::delete().to(remove_connection))
+            .route("/api/system/metrics", web::get().to(query_metrics))
             .route("/api/system/metrics/{db}", web::get().to(get_database_metrics))
+            .route("/api/system/rollups", web::get().to(get_metric_rollups))
+            .route("/api/system/slow-operations", web::get().to(get_slow_operations))
+            .route("/api/system/audit", web::get().to(get_audit_log))
+            .route("/api/system/audit/export", web::get().to(export_audit_log))
+            .route("/api/system/workspace/export", web::get().to(export_workspace))
+            .route("/api/system/workspace/import", web::post().to(import_workspace))
+            .route("/api/system/tokens", web::post().to(create_token))
+            .route("/api/system/tokens", web::get().to(list_tokens))
+            .route("/api/system/tokens/{id}", web::delete().to(revoke_token))
             // Vector database endpoints
             .route("/api/databases/{db}/vectors/collections", web::post().to(create_vector_collection))
             .route("/api/databases/{db}/vectors/collections", web::get().to(list_vector_collections))
@@ -997,8 +1926,53 @@ async fn main() -> std::io::Result<()> {
             .route("/api/databases/{db}/vectors", web::delete().to(delete_vector))
             // Document parsing endpoint
             .route("/api/parse-document", web::post().to(parse_document))
+            // Background jobs
+            .route("/api/jobs", web::post().to(submit_job))
+            .route("/api/jobs", web::get().to(list_jobs))
+            .route("/api/jobs/{id}", web::get().to(get_job))
+            .route("/api/jobs/{id}/cancel", web::post().to(cancel_job))
+            .route("/api/jobs/{id}/events", web::get().to(job_events))
+            // Change stream
+            .route("/api/changes", web::get().to(stream_changes))
+            .default_service(web::route().to(route_not_found))
     })
-    .bind(("127.0.0.1", 5800))?
-    .run()
-    .await
+    .bind(bind_addr)?
+    .disable_signals()
+    .run();
+
+    let handle = server.handle();
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        log::info!("Shutting down");
+        handle.stop(true).await;
+    });
+    server.await?;
+
+    // Deferred metric and connection writes would otherwise wait for the
+    // next start to be replayed from the journal
+    if let Err(e) = flush_state.service.system_db().flush() {
+        log::error!("Failed to flush the system database: {}", e);
+    }
+    Ok(())
+}
+
+/// Resolves on SIGINT (Ctrl+C) or SIGTERM
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(signal) => signal,
+            Err(e) => {
+                log::warn!("Failed to listen for SIGTERM: {}", e);
+                tokio::signal::ctrl_c().await.ok();
+                return;
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.ok();
 }
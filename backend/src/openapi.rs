@@ -0,0 +1,168 @@
+use crate::error::{ApiError, ErrorBody, ErrorCode};
+use crate::system_db::{DatabaseConnection, PerformanceMetric, TokenScope};
+use crate::*;
+use actix_web::{http::header, HttpResponse};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi, ToSchema};
+use utoipa_swagger_ui::{Config, SwaggerUi};
+
+const OPENAPI_PATH: &str = "/api/openapi.json";
+const DOCS_PATH: &str = "/api/docs";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "keradb Labs API", description = "REST API for keradb document and vector databases"),
+    paths(
+        health_check,
+        export_metrics,
+        list_databases,
+        open_database,
+        create_database,
+        close_database,
+        delete_database,
+        get_collections,
+        drop_collection,
+        get_stats,
+        insert_document,
+        find_documents,
+        find_by_id,
+        update_document,
+        delete_document,
+        run_query,
+        list_saved_queries,
+        create_saved_query,
+        update_saved_query,
+        delete_saved_query,
+        run_saved_query,
+        get_query_history,
+        clear_query_history,
+        rerun_query,
+        get_system_stats,
+        get_connection_history,
+        update_connection_profile,
+        remove_connection,
+        get_database_metrics,
+        query_metrics,
+        get_metric_rollups,
+        get_slow_operations,
+        get_audit_log,
+        export_audit_log,
+        export_workspace,
+        import_workspace,
+        create_token,
+        list_tokens,
+        revoke_token,
+        create_vector_collection,
+        list_vector_collections,
+        get_vector_collection_stats,
+        drop_vector_collection,
+        insert_vector,
+        get_all_vectors,
+        vector_search,
+        get_vector,
+        delete_vector,
+        parse_document,
+        submit_job,
+        list_jobs,
+        get_job,
+        cancel_job,
+        job_events,
+        stream_changes,
+    ),
+    components(schemas(
+        ErrorBody,
+        ApiError,
+        ErrorCode,
+        DatabaseConnection,
+        PerformanceMetric,
+        TokenScope,
+        ChangeEvent,
+        Subscription,
+    )),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "databases", description = "Open, create and close databases"),
+        (name = "documents", description = "Document collections and document parsing"),
+        (name = "vectors", description = "Vector collections and similarity search"),
+        (name = "queries", description = "Ad hoc and saved queries with run history"),
+        (name = "system", description = "System database: history, stats, metrics and audit log"),
+        (name = "tokens", description = "API token management (admin scope)"),
+        (name = "jobs", description = "Background jobs with progress and cancellation"),
+        (name = "changes", description = "Real-time change stream over WebSocket"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Declares the bearer token scheme and applies it to every operation
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        openapi.security = Some(vec![utoipa::openapi::security::SecurityRequirement::new(
+            "bearer_token",
+            Vec::<String>::new(),
+        )]);
+    }
+}
+
+/// Multipart form accepted by `/api/parse-document`; only used to describe it
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct ParseDocumentUpload {
+    #[schema(value_type = String, format = Binary)]
+    file: Vec<u8>,
+}
+
+/// The spec and docs pages are readable without a token
+pub fn is_docs_route(path: &str) -> bool {
+    path == OPENAPI_PATH || path == DOCS_PATH || path.strip_prefix(DOCS_PATH).is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// `/api/openapi.json` and the Swagger UI under `/api/docs/`. The UI's assets
+/// are embedded in the binary, so the page loads nothing from a CDN.
+pub fn docs_service() -> SwaggerUi {
+    SwaggerUi::new(format!("{}/{{_:.*}}", DOCS_PATH))
+        .url(OPENAPI_PATH, ApiDoc::openapi())
+        .config(Config::default().persist_authorization(true))
+}
+
+/// The UI loads its assets by relative path, so it needs the trailing slash
+pub async fn docs_redirect() -> HttpResponse {
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, format!("{}/", DOCS_PATH)))
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_covers_routes_and_schemas() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+        assert!(spec["paths"]["/api/databases/{db}/vectors/search"]["post"].is_object());
+        assert!(spec["paths"]["/api/parse-document"]["post"].is_object());
+        for schema in ["InsertRequest", "VectorSearchRequest", "DatabaseInfo", "ErrorBody"] {
+            assert!(spec["components"]["schemas"][schema].is_object(), "missing schema {}", schema);
+        }
+    }
+
+    #[test]
+    fn test_docs_assets_are_embedded() {
+        let config = std::sync::Arc::new(Config::new([OPENAPI_PATH]));
+        for file in ["", "swagger-ui.css", "swagger-ui-bundle.js", "swagger-initializer.js"] {
+            assert!(utoipa_swagger_ui::serve(file, config.clone()).unwrap().is_some(), "missing {}", file);
+        }
+
+        assert!(is_docs_route("/api/docs"));
+        assert!(is_docs_route("/api/docs/swagger-ui.css"));
+        assert!(!is_docs_route("/api/docsx"));
+    }
+}
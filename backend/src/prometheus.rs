@@ -0,0 +1,339 @@
+//! Prometheus text exposition at `/metrics`: request counters and latency
+//! histograms per route, plus database and collection sizes read at scrape
+//! time. Routes are labelled with the operation name `LabsService` records
+//! their timings under (`insert_document`, `get_vectors`, ...), so the labels
+//! line up with the system database metrics. Other routes are labelled with
+//! their handler's operation id.
+
+use crate::AppState;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::web;
+use keradb_labs_core::LabsService;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::time::Instant;
+
+pub const METRICS_PATH: &str = "/metrics";
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds of the request latency buckets, in seconds
+const LATENCY_BUCKETS: [f64; 12] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0];
+
+/// Label for requests that matched no route, so unknown paths cannot grow
+/// the label set without bound
+const UNMATCHED: &str = "unmatched";
+
+/// Handlers whose operation id differs from the name `LabsService` records
+/// their timings under. Queries are labelled `query_documents` even when
+/// they run a vector search.
+const RECORDED_AS: [(&str, &str); 4] = [
+    ("get_all_vectors", "get_vectors"),
+    ("run_query", "query_documents"),
+    ("run_saved_query", "query_documents"),
+    ("rerun_query", "query_documents"),
+];
+
+#[derive(Default)]
+struct Histogram {
+    /// Non-cumulative counts per bucket; the last entry is `+Inf`
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Series {
+    /// Requests by status code
+    statuses: BTreeMap<u16, u64>,
+    latency: Histogram,
+}
+
+/// Request counters and latency histograms keyed by operation name
+pub struct RequestMetrics {
+    /// `(method, route pattern)` to the operation name
+    operations: HashMap<(Method, String), String>,
+    series: Mutex<BTreeMap<String, Series>>,
+}
+
+impl RequestMetrics {
+    /// Operation names are taken from the `operationId`s in the API spec,
+    /// which are the handler function names, renamed per `RECORDED_AS`
+    pub fn new(spec: &utoipa::openapi::OpenApi) -> Self {
+        let mut operations = HashMap::new();
+        for (path, item) in &spec.paths.paths {
+            let methods = [
+                (Method::GET, &item.get),
+                (Method::POST, &item.post),
+                (Method::PUT, &item.put),
+                (Method::DELETE, &item.delete),
+                (Method::PATCH, &item.patch),
+            ];
+            for (method, operation) in methods {
+                if let Some(id) = operation.as_ref().and_then(|op| op.operation_id.as_deref()) {
+                    let name = RECORDED_AS
+                        .iter()
+                        .find(|(handler, _)| *handler == id)
+                        .map_or(id, |(_, recorded)| recorded);
+                    operations.insert((method, path.clone()), name.to_string());
+                }
+            }
+        }
+
+        Self {
+            operations,
+            series: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Routes outside the spec are labelled with their pattern
+    fn operation(&self, method: &Method, pattern: Option<String>) -> String {
+        match pattern {
+            Some(pattern) => self
+                .operations
+                .get(&(method.clone(), pattern.clone()))
+                .cloned()
+                .unwrap_or(pattern),
+            None => UNMATCHED.to_string(),
+        }
+    }
+
+    fn observe(&self, operation: String, status: u16, seconds: f64) {
+        let mut series = self.series.lock();
+        let series = series.entry(operation).or_default();
+        *series.statuses.entry(status).or_default() += 1;
+        series.latency.observe(seconds);
+    }
+
+    fn render(&self, out: &mut String) {
+        let series = self.series.lock();
+
+        family(out, "keradb_labs_http_requests_total", "counter", "HTTP requests by operation and status");
+        for (operation, s) in series.iter() {
+            for (status, count) in &s.statuses {
+                sample(
+                    out,
+                    "keradb_labs_http_requests_total",
+                    &[("operation", operation), ("status", &status.to_string())],
+                    *count,
+                );
+            }
+        }
+
+        family(
+            out,
+            "keradb_labs_http_request_duration_seconds",
+            "histogram",
+            "Time until the response headers were ready, by operation",
+        );
+        for (operation, s) in series.iter() {
+            let mut cumulative = 0;
+            for (i, count) in s.latency.buckets.iter().enumerate() {
+                cumulative += count;
+                let le = LATENCY_BUCKETS.get(i).map_or("+Inf".to_string(), |bound| bound.to_string());
+                sample(
+                    out,
+                    "keradb_labs_http_request_duration_seconds_bucket",
+                    &[("operation", operation), ("le", &le)],
+                    cumulative,
+                );
+            }
+            let labels = [("operation", operation.as_str())];
+            sample(out, "keradb_labs_http_request_duration_seconds_sum", &labels, s.latency.sum);
+            sample(out, "keradb_labs_http_request_duration_seconds_count", &labels, s.latency.count);
+        }
+    }
+}
+
+/// Middleware: count every request and time it until its response is ready.
+/// Streaming responses (job events, the change stream) are timed to the
+/// start of the stream.
+pub async fn track_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let state = req
+        .app_data::<web::Data<AppState>>()
+        .cloned()
+        .expect("AppState is registered");
+    let operation = state.requests.operation(req.method(), req.match_pattern());
+    let start = Instant::now();
+
+    let result = next.call(req).await;
+
+    let status = match &result {
+        Ok(response) => response.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    state
+        .requests
+        .observe(operation, status.as_u16(), start.elapsed().as_secs_f64());
+    result
+}
+
+/// The whole exposition: request metrics followed by sizes of the open
+/// databases `visible` accepts
+pub fn render(requests: &RequestMetrics, service: &LabsService, visible: impl Fn(&str) -> bool) -> String {
+    let mut out = String::new();
+    requests.render(&mut out);
+
+    let databases: Vec<String> = service.list_databases().into_iter().filter(|db| visible(db)).collect();
+
+    family(&mut out, "keradb_labs_open_databases", "gauge", "Databases currently open");
+    sample(&mut out, "keradb_labs_open_databases", &[], databases.len());
+
+    // A database closed between listing and reading is skipped
+    let collections: Vec<_> = databases
+        .iter()
+        .filter_map(|db| service.collections(db).ok().map(|c| (db, c)))
+        .collect();
+    let vectors: Vec<_> = databases
+        .iter()
+        .filter_map(|db| service.vector_collection_sizes(db).ok().map(|v| (db, v)))
+        .collect();
+
+    family(&mut out, "keradb_labs_collections", "gauge", "Document collections per database");
+    for (db, collections) in &collections {
+        sample(&mut out, "keradb_labs_collections", &[("db", db)], collections.len());
+    }
+
+    family(&mut out, "keradb_labs_documents", "gauge", "Documents per collection");
+    for (db, collections) in &collections {
+        for collection in collections {
+            sample(
+                &mut out,
+                "keradb_labs_documents",
+                &[("db", db), ("collection", &collection.name)],
+                collection.count,
+            );
+        }
+    }
+
+    family(&mut out, "keradb_labs_vectors", "gauge", "Vectors per vector collection");
+    for (db, sizes) in &vectors {
+        for size in sizes {
+            sample(&mut out, "keradb_labs_vectors", &[("db", db), ("collection", &size.name)], size.vector_count);
+        }
+    }
+
+    family(
+        &mut out,
+        "keradb_labs_vector_memory_bytes",
+        "gauge",
+        "Memory used by each vector collection",
+    );
+    for (db, sizes) in &vectors {
+        for size in sizes {
+            sample(
+                &mut out,
+                "keradb_labs_vector_memory_bytes",
+                &[("db", db), ("collection", &size.name)],
+                size.memory_bytes,
+            );
+        }
+    }
+
+    out
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openapi::ApiDoc;
+    use utoipa::OpenApi;
+
+    #[test]
+    fn test_operations_come_from_the_spec() {
+        let metrics = RequestMetrics::new(&ApiDoc::openapi());
+        let operation = |method, pattern: &str| metrics.operation(&method, Some(pattern.to_string()));
+
+        assert_eq!(operation(Method::GET, "/api/databases"), "list_databases");
+        assert_eq!(operation(Method::GET, "/api/docs"), "/api/docs");
+        assert_eq!(metrics.operation(&Method::GET, None), UNMATCHED);
+    }
+
+    #[test]
+    fn test_operations_match_recorded_metrics() {
+        let metrics = RequestMetrics::new(&ApiDoc::openapi());
+        let routes = [
+            (Method::POST, "/api/databases/open", "open_database"),
+            (Method::POST, "/api/databases/create", "create_database"),
+            (Method::DELETE, "/api/databases/{db}/collections", "drop_collection"),
+            (Method::POST, "/api/databases/{db}/documents", "insert_document"),
+            (Method::GET, "/api/databases/{db}/documents", "find_documents"),
+            (Method::PUT, "/api/databases/{db}/documents", "update_document"),
+            (Method::DELETE, "/api/databases/{db}/documents", "delete_document"),
+            (Method::POST, "/api/databases/{db}/query", "query_documents"),
+            (Method::POST, "/api/databases/{db}/saved-queries/{id}/run", "query_documents"),
+            (Method::POST, "/api/databases/{db}/query-history/{id}/run", "query_documents"),
+            (Method::POST, "/api/databases/{db}/vectors", "insert_vector"),
+            (Method::GET, "/api/databases/{db}/vectors", "get_vectors"),
+            (Method::POST, "/api/databases/{db}/vectors/search", "vector_search"),
+        ];
+        for (method, pattern, recorded) in routes {
+            assert_eq!(metrics.operation(&method, Some(pattern.to_string())), recorded, "{} {}", method, pattern);
+        }
+    }
+
+    #[test]
+    fn test_render_request_metrics() {
+        let metrics = RequestMetrics::new(&ApiDoc::openapi());
+        metrics.observe("insert_document".to_string(), 200, 0.002);
+        metrics.observe("insert_document".to_string(), 200, 0.2);
+        metrics.observe("insert_document".to_string(), 404, 30.0);
+
+        let mut out = String::new();
+        metrics.render(&mut out);
+
+        assert!(out.contains("# TYPE keradb_labs_http_requests_total counter\n"));
+        assert!(out.contains("keradb_labs_http_requests_total{operation=\"insert_document\",status=\"200\"} 2\n"));
+        assert!(out.contains("keradb_labs_http_requests_total{operation=\"insert_document\",status=\"404\"} 1\n"));
+        assert!(out.contains("keradb_labs_http_request_duration_seconds_bucket{operation=\"insert_document\",le=\"0.001\"} 0\n"));
+        assert!(out.contains("keradb_labs_http_request_duration_seconds_bucket{operation=\"insert_document\",le=\"0.25\"} 2\n"));
+        assert!(out.contains("keradb_labs_http_request_duration_seconds_bucket{operation=\"insert_document\",le=\"+Inf\"} 3\n"));
+        assert!(out.contains("keradb_labs_http_request_duration_seconds_count{operation=\"insert_document\"} 3\n"));
+    }
+
+    #[test]
+    fn test_label_values_are_escaped() {
+        let mut out = String::new();
+        sample(&mut out, "m", &[("db", "a\"b\\c\nd")], 1);
+        assert_eq!(out, "m{db=\"a\\\"b\\\\c\\nd\"} 1\n");
+    }
+}
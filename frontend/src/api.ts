@@ -1,7 +1,11 @@
 import { invoke } from '@tauri-apps/api/tauri';
 import { open, save } from '@tauri-apps/api/dialog';
+import { listen } from '@tauri-apps/api/event';
 
 export interface DatabaseInfo {
+  /** Stable handle accepted wherever a database path is */
+  id: string;
+  alias?: string;
   path: string;
   collections: CollectionInfo[];
 }
@@ -14,11 +18,40 @@ export interface CollectionInfo {
 export interface DatabaseConnection {
   id: string;
   path: string;
+  alias?: string;
   created_at: string;
   last_accessed: string;
   access_count: number;
   collections_count: number;
   total_documents: number;
+  /** `#rgb`, `#rrggbb` or a color name */
+  color?: string;
+  favorite: boolean;
+  notes?: string;
+  /** `/`-separated for nested folders */
+  folder?: string;
+}
+
+export type ConnectionSort = 'last_accessed' | 'created_at' | 'access_count' | 'name' | 'path' | 'folder';
+
+/** Favorites come first unless `favorites_first` is false */
+export interface ConnectionQuery {
+  sort?: ConnectionSort;
+  desc?: boolean;
+  favorites_first?: boolean;
+  favorite?: boolean;
+  color?: string;
+  folder?: string;
+  search?: string;
+}
+
+/** Omitted fields are unchanged; an empty string clears a field */
+export interface ConnectionProfileUpdate {
+  alias?: string;
+  color?: string;
+  favorite?: boolean;
+  notes?: string;
+  folder?: string;
 }
 
 export interface PerformanceMetric {
@@ -29,6 +62,138 @@ export interface PerformanceMetric {
   timestamp: string;
 }
 
+export type Granularity = 'minute' | 'hour' | 'day';
+
+/** Filter for raw samples and rollups; `db` accepts any database handle */
+export interface MetricsQuery {
+  db?: string;
+  operation?: string;
+  from?: string;
+  to?: string;
+  granularity?: Granularity;
+  limit?: number;
+}
+
+export interface MetricRollup {
+  database_path: string;
+  operation: string;
+  granularity: Granularity;
+  bucket_start: string;
+  count: number;
+  mean_ms: number;
+  p50_ms: number;
+  p95_ms: number;
+  p99_ms: number;
+  max_ms: number;
+}
+
+export interface Caller {
+  token_id?: string;
+  token_name?: string;
+  ip?: string;
+}
+
+/** An operation that took at least the slow operation threshold */
+export interface SlowOperation {
+  id: string;
+  database_path: string;
+  operation: string;
+  collection?: string | null;
+  params?: Record<string, any> | null;
+  result_size?: number | null;
+  duration_ms: number;
+  timestamp: string;
+  /** Unset for operations run by the desktop app */
+  caller?: Caller | null;
+}
+
+export interface SlowOperationQuery {
+  db?: string;
+  operation?: string;
+  collection?: string;
+  min_ms?: number;
+  from?: string;
+  to?: string;
+  limit?: number;
+}
+
+export type AuditAction =
+  | 'insert_document'
+  | 'update_document'
+  | 'delete_document'
+  | 'drop_collection'
+  | 'create_vector_collection'
+  | 'drop_vector_collection'
+  | 'insert_vector'
+  | 'delete_vector'
+  | 'delete_database';
+
+export interface AuditEntry {
+  id: string;
+  timestamp: string;
+  action: AuditAction;
+  database_path: string;
+  collection?: string | null;
+  /** Document or vector id */
+  target_id?: string | null;
+  /** Unset for operations run by the desktop app */
+  caller?: Caller | null;
+  /** Document or vector as it was before an update or delete */
+  before?: Record<string, any> | null;
+  details?: Record<string, any> | null;
+}
+
+export interface AuditQuery {
+  db?: string;
+  action?: AuditAction;
+  collection?: string;
+  /** Token id, token name, client IP, or 'local' for the desktop app */
+  actor?: string;
+  from?: string;
+  to?: string;
+  limit?: number;
+}
+
+// Workspace bundles
+export interface WorkspaceConnection {
+  path: string;
+  alias?: string | null;
+  color?: string | null;
+  favorite: boolean;
+  notes?: string | null;
+  folder?: string | null;
+}
+
+export interface WorkspaceQuery {
+  database_path: string;
+  name: string;
+  description?: string | null;
+  query: QuerySpec;
+}
+
+export interface WorkspaceBundle {
+  format: 'keradb-labs-workspace';
+  version: number;
+  exported_at: string;
+  connections: WorkspaceConnection[];
+  saved_queries: WorkspaceQuery[];
+}
+
+/** Replaces a leading `from` with `to`, e.g. `D:\projects` with `/home/ada/projects` */
+export interface PathRemap {
+  from: string;
+  to: string;
+}
+
+export interface ImportReport {
+  connections_created: number;
+  connections_updated: number;
+  saved_queries_created: number;
+  /** Imported databases whose file does not exist on this machine yet */
+  missing: string[];
+  skipped: { item: string; reason: string }[];
+}
+
 // Vector types
 export interface VectorCollectionInfo {
   name: string;
@@ -69,14 +234,226 @@ export interface VectorsResponse {
   skip: number;
 }
 
+// Query types
+export interface SortField {
+  /** Dotted path, e.g. `address.city` */
+  field: string;
+  desc?: boolean;
+}
+
+/**
+ * Filter keys are dotted field paths; values match exactly or are an object
+ * of `$eq`, `$ne`, `$gt`, `$gte`, `$lt`, `$lte`, `$in`, `$nin`, `$exists`
+ */
+export type QuerySpec =
+  | {
+      kind: 'documents';
+      collection: string;
+      filter?: Record<string, any>;
+      sort?: SortField[];
+      projection?: string[];
+      limit?: number;
+      skip?: number;
+    }
+  | { kind: 'vector_search'; collection: string; vector: number[]; k: number };
+
+export type QueryResults =
+  | { kind: 'documents'; documents: any[]; matched: number }
+  | { kind: 'vector_search'; results: VectorSearchResult[] };
+
+export interface SavedQueryRequest {
+  name: string;
+  description?: string;
+  query: QuerySpec;
+}
+
+export interface SavedQuery extends SavedQueryRequest {
+  id: string;
+  database_path: string;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface QueryHistoryEntry {
+  id: string;
+  database_path: string;
+  query: QuerySpec;
+  saved_query_id?: string;
+  executed_at: string;
+  duration_ms: number;
+  result_count: number;
+}
+
+// Document parsing types
+export interface DocumentMetadata {
+  title?: string;
+  author?: string;
+  created?: string;
+  modified?: string;
+  language?: string;
+  word_count: number;
+  content_hash: string;
+  // Format-specific fields such as email headers
+  [key: string]: string | number | undefined;
+}
+
+export interface ParsedDocumentPart {
+  text: string;
+  pages: number;
+  file_type: string;
+  title: string | null;
+  encoding: string | null;
+  encoding_confidence: number | null;
+  metadata: DocumentMetadata;
+  parts: ParsedDocumentPart[];
+}
+
+/** Error codes shared by the REST backend and the Tauri commands */
+export type ErrorCode =
+  | 'DB_NOT_OPEN'
+  | 'COLLECTION_NOT_FOUND'
+  | 'DOCUMENT_NOT_FOUND'
+  | 'VECTOR_NOT_FOUND'
+  | 'NOT_FOUND'
+  | 'DIMENSION_MISMATCH'
+  | 'INVALID_REQUEST'
+  | 'INVALID_PATH'
+  | 'PATH_OUTSIDE_ROOTS'
+  | 'PARSE_FAILED'
+  | 'PAYLOAD_TOO_LARGE'
+  | 'UNAUTHORIZED'
+  | 'FORBIDDEN'
+  | 'STORAGE_ERROR'
+  | 'INTERNAL';
+
+/** Rejection value of a Tauri command, and the `error` field of a REST error body */
+export interface ApiErrorBody {
+  code: ErrorCode;
+  message: string;
+}
+
+export function isApiError(err: unknown): err is ApiErrorBody {
+  return typeof err === 'object' && err !== null && 'code' in err && 'message' in err;
+}
+
+export class ApiRequestError extends Error implements ApiErrorBody {
+  code: ErrorCode;
+
+  constructor(body: ApiErrorBody) {
+    super(body.message);
+    this.code = body.code;
+  }
+}
+
+/** Human-readable message for anything thrown by `api` calls */
+export function errorMessage(err: unknown, fallback: string): string {
+  if (isApiError(err)) return err.message;
+  if (err instanceof Error && err.message) return err.message;
+  if (typeof err === 'string' && err) return err;
+  return fallback;
+}
+
+export interface ParseDocumentResponse {
+  success: boolean;
+  filename: string;
+  text?: string;
+  pages?: number;
+  file_type?: string;
+  title?: string | null;
+  encoding?: string | null;
+  encoding_confidence?: number | null;
+  metadata?: DocumentMetadata;
+  parts?: ParsedDocumentPart[];
+  char_count?: number;
+  error?: ApiErrorBody;
+}
+
+/** A parsed document, or for archives one result per contained file */
+export type ParseDocumentResult = ParseDocumentResponse & {
+  archive?: boolean;
+  files?: ParseDocumentResponse[];
+};
+
+/** Payload of the `parse-progress` event emitted while `parse_document_file` runs */
+export interface ParseProgress {
+  /** Path passed to `parseDocumentFile` */
+  source: string;
+  stage: 'reading' | 'parsing' | 'done';
+  bytes_read: number;
+  total_bytes: number;
+  /** Archive entries parsed so far and in total; both 0 for single documents */
+  files_done: number;
+  files_total: number;
+}
+
+/** A document or vector mutation, as published by the change stream */
+export interface ChangeEvent {
+  /** Changes when the app restarts; sequence numbers are per epoch */
+  epoch: string;
+  /** Increases by one per change within an epoch */
+  seq: number;
+  timestamp: string;
+  db: string;
+  collection: string;
+  target: 'document' | 'vector';
+  op: 'insert' | 'update' | 'delete' | 'drop';
+  id?: string;
+  document?: any;
+}
+
+export interface ChangeSubscription {
+  id: number;
+  epoch: string;
+  /** Latest sequence number when subscribing */
+  seq: number;
+  /** Changes after the requested resume point are gone; reload instead */
+  missed: boolean;
+}
+
+export interface ChangeFilter {
+  dbPath?: string;
+  collection?: string;
+  /** Last sequence number seen; missed changes are replayed first */
+  after?: number;
+  /** Epoch of the last change seen; required with `after` */
+  epoch?: string;
+}
+
+let changeChannels = 0;
+
+export type JobStatus = 'queued' | 'running' | 'completed' | 'failed' | 'cancelled';
+
+/** A background job, live while running and from history afterwards */
+export interface Job {
+  id: string;
+  kind: string;
+  database: string;
+  collection: string;
+  status: JobStatus;
+  done: number;
+  total: number;
+  result?: any;
+  error?: string;
+  created_at: string;
+  started_at?: string;
+  finished_at?: string;
+}
+
+export type JobRequest =
+  | { kind: 'drop_collection'; db: string; collection: string }
+  | { kind: 'import_documents'; db: string; collection: string; documents: any[] }
+  | { kind: 'import_vectors'; db: string; collection: string; vectors: { vector: number[]; metadata?: any }[] };
+
+const isFinished = (job: Job) => ['completed', 'failed', 'cancelled'].includes(job.status);
+
 export const api = {
   // Database Management
-  async openDatabase(path: string): Promise<DatabaseInfo> {
-    return invoke('open_database', { path });
+  async openDatabase(path: string, alias?: string): Promise<DatabaseInfo> {
+    return invoke('open_database', { path, alias });
   },
 
-  async createDatabase(path: string): Promise<DatabaseInfo> {
-    return invoke('create_database', { path });
+  async createDatabase(path: string, alias?: string): Promise<DatabaseInfo> {
+    return invoke('create_database', { path, alias });
   },
 
   async listDatabases(): Promise<string[]> {
@@ -152,8 +529,15 @@ export const api = {
     return invoke('get_system_stats');
   },
 
-  async getConnectionHistory(): Promise<DatabaseConnection[]> {
-    return invoke('get_connection_history');
+  async getConnectionHistory(query?: ConnectionQuery): Promise<DatabaseConnection[]> {
+    return invoke('get_connection_history', { query });
+  },
+
+  async updateConnectionProfile(
+    dbPath: string,
+    update: ConnectionProfileUpdate
+  ): Promise<DatabaseConnection> {
+    return invoke('update_connection_profile', { dbPath, update });
   },
 
   async getDatabaseMetrics(
@@ -163,6 +547,36 @@ export const api = {
     return invoke('get_database_metrics', { dbPath, limit });
   },
 
+  async queryMetrics(query: MetricsQuery): Promise<PerformanceMetric[]> {
+    return invoke('query_metrics', { query });
+  },
+
+  async getMetricRollups(query: MetricsQuery): Promise<MetricRollup[]> {
+    return invoke('get_metric_rollups', { query });
+  },
+
+  async getSlowOperations(query: SlowOperationQuery): Promise<SlowOperation[]> {
+    return invoke('get_slow_operations', { query });
+  },
+
+  async getAuditLog(query: AuditQuery): Promise<AuditEntry[]> {
+    return invoke('get_audit_log', { query });
+  },
+
+  /** Writes matching entries to `path` as NDJSON; returns how many */
+  async exportAuditLog(query: AuditQuery, path: string): Promise<number> {
+    return invoke('export_audit_log', { query, path });
+  },
+
+  /** Writes the workspace bundle to `path`; returns how many connections */
+  async exportWorkspace(path: string): Promise<number> {
+    return invoke('export_workspace', { path });
+  },
+
+  async importWorkspace(path: string, remap: PathRemap[] = []): Promise<ImportReport> {
+    return invoke('import_workspace', { path, remap });
+  },
+
   async removeConnection(dbPath: string): Promise<string> {
     return invoke('remove_connection', { dbPath });
   },
@@ -266,16 +680,146 @@ export const api = {
     return invoke('delete_vector', { dbPath, collection, id });
   },
 
-  // Document parsing - uses backend for better PDF/DOCX/Excel parsing
-  async parseDocument(file: File): Promise<{
-    success: boolean;
-    filename: string;
-    text?: string;
-    pages?: number;
-    file_type?: string;
-    char_count?: number;
-    error?: string;
-  }> {
+  // Queries
+  async runQuery(dbPath: string, query: QuerySpec): Promise<QueryResults> {
+    return invoke('run_query', { dbPath, query });
+  },
+
+  async listSavedQueries(dbPath: string): Promise<SavedQuery[]> {
+    return invoke('list_saved_queries', { dbPath });
+  },
+
+  async createSavedQuery(dbPath: string, request: SavedQueryRequest): Promise<SavedQuery> {
+    return invoke('create_saved_query', { dbPath, request });
+  },
+
+  async updateSavedQuery(dbPath: string, id: string, request: SavedQueryRequest): Promise<SavedQuery> {
+    return invoke('update_saved_query', { dbPath, id, request });
+  },
+
+  async deleteSavedQuery(dbPath: string, id: string): Promise<SavedQuery> {
+    return invoke('delete_saved_query', { dbPath, id });
+  },
+
+  async runSavedQuery(dbPath: string, id: string): Promise<QueryResults> {
+    return invoke('run_saved_query', { dbPath, id });
+  },
+
+  async getQueryHistory(dbPath: string, limit?: number): Promise<QueryHistoryEntry[]> {
+    return invoke('get_query_history', { dbPath, limit });
+  },
+
+  async rerunQuery(dbPath: string, id: string): Promise<QueryResults> {
+    return invoke('rerun_query', { dbPath, id });
+  },
+
+  async clearQueryHistory(dbPath: string): Promise<number> {
+    return invoke('clear_query_history', { dbPath });
+  },
+
+  // Background jobs
+  async submitJob(request: JobRequest): Promise<Job> {
+    return invoke('submit_job', { request });
+  },
+
+  async getJob(id: string): Promise<Job> {
+    return invoke('get_job', { id });
+  },
+
+  async listJobs(limit?: number): Promise<Job[]> {
+    return invoke('list_jobs', { limit });
+  },
+
+  async cancelJob(id: string): Promise<Job> {
+    return invoke('cancel_job', { id });
+  },
+
+  /** Submit a job and resolve with it once finished, reporting progress on the way */
+  async runJob(request: JobRequest, onProgress?: (job: Job) => void): Promise<Job> {
+    let settle: (job: Job) => void = () => {};
+    const finished = new Promise<Job>((resolve) => { settle = resolve; });
+    let jobId: string | undefined;
+    const pending: Job[] = [];
+
+    const handle = (job: Job) => {
+      onProgress?.(job);
+      if (isFinished(job)) settle(job);
+    };
+    const unlisten = await listen<Job>('job-progress', (event) => {
+      if (jobId === undefined) pending.push(event.payload);
+      else if (event.payload.id === jobId) handle(event.payload);
+    });
+
+    try {
+      const job: Job = await invoke('submit_job', { request });
+      jobId = job.id;
+      handle(job);
+      pending.filter((update) => update.id === job.id).forEach(handle);
+      // The job may have finished before its id was known
+      handle(await invoke('get_job', { id: job.id }));
+      return await finished;
+    } finally {
+      unlisten();
+    }
+  },
+
+  // Change stream - resolves to a function that unsubscribes
+  async subscribeChanges(
+    filter: ChangeFilter,
+    onChange: (event: ChangeEvent) => void
+  ): Promise<{ subscription: ChangeSubscription; unsubscribe: () => Promise<void> }> {
+    // A channel per subscription keeps events of other subscriptions out
+    const channel = `database-change/${++changeChannels}`;
+    const unlisten = await listen<ChangeEvent>(channel, (event) => onChange(event.payload));
+    try {
+      const subscription: ChangeSubscription = await invoke('subscribe_changes', {
+        dbPath: filter.dbPath,
+        collection: filter.collection,
+        after: filter.after,
+        epoch: filter.epoch,
+        channel
+      });
+      return {
+        subscription,
+        unsubscribe: async () => {
+          unlisten();
+          await invoke('unsubscribe_changes', { id: subscription.id });
+        }
+      };
+    } catch (err) {
+      unlisten();
+      throw err;
+    }
+  },
+
+  // Document parsing - desktop app parses local files in place by path
+  async pickDocumentFiles(extensions: string[]): Promise<string[]> {
+    const selected = await open({
+      multiple: true,
+      filters: [{ name: 'Documents', extensions }]
+    });
+    if (Array.isArray(selected)) return selected;
+    return typeof selected === 'string' ? [selected] : [];
+  },
+
+  async parseDocumentFile(
+    path: string,
+    onProgress?: (progress: ParseProgress) => void
+  ): Promise<ParseDocumentResult> {
+    const unlisten = onProgress
+      ? await listen<ParseProgress>('parse-progress', (event) => {
+          if (event.payload.source === path) onProgress(event.payload);
+        })
+      : undefined;
+    try {
+      return await invoke('parse_document_file', { path });
+    } finally {
+      unlisten?.();
+    }
+  },
+
+  // Browser uploads use the backend for better PDF/DOCX/Excel parsing
+  async parseDocument(file: File): Promise<ParseDocumentResult> {
     const formData = new FormData();
     formData.append('file', file);
 
@@ -285,7 +829,12 @@ export const api = {
     });
 
     if (!response.ok) {
-      throw new Error(`Failed to parse document: ${response.statusText}`);
+      const body = await response.json().catch(() => null);
+      throw new ApiRequestError(
+        isApiError(body?.error)
+          ? body.error
+          : { code: 'INTERNAL', message: `Failed to parse document: ${response.statusText}` }
+      );
     }
 
     return response.json();
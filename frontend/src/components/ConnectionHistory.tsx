@@ -1,28 +1,31 @@
 import { useEffect, useState } from 'react'
-import { Database, Clock, Folder } from 'lucide-react'
-import { api } from '../api'
-
-interface DatabaseConnection {
-  id: string
-  path: string
-  created_at: string
-  last_accessed: string
-  access_count: number
-  collections_count: number
-  total_documents: number
-}
+import { Database, Clock, Folder, Star, Pencil, Search } from 'lucide-react'
+import { api, errorMessage, ConnectionProfileUpdate, ConnectionSort, DatabaseConnection } from '../api'
 
 interface ConnectionHistoryProps {
   onSelectDatabase?: (path: string) => void
 }
 
+const SORT_OPTIONS: { value: ConnectionSort; label: string }[] = [
+  { value: 'last_accessed', label: 'Recent' },
+  { value: 'name', label: 'Name' },
+  { value: 'folder', label: 'Folder' },
+  { value: 'access_count', label: 'Most used' },
+  { value: 'created_at', label: 'Added' },
+]
+
 export default function ConnectionHistory({ onSelectDatabase }: ConnectionHistoryProps) {
   const [connections, setConnections] = useState<DatabaseConnection[]>([])
   const [loading, setLoading] = useState(true)
+  const [sort, setSort] = useState<ConnectionSort>('last_accessed')
+  const [search, setSearch] = useState('')
+  const [editing, setEditing] = useState<string | null>(null)
+  const [draft, setDraft] = useState<ConnectionProfileUpdate>({})
+  const [error, setError] = useState<string | null>(null)
 
   const fetchConnections = async () => {
     try {
-      const connections = await api.getConnectionHistory()
+      const connections = await api.getConnectionHistory({ sort, search: search || undefined })
       setConnections(connections)
     } catch (err) {
       console.error('Failed to fetch connections:', err)
@@ -33,13 +36,35 @@ export default function ConnectionHistory({ onSelectDatabase }: ConnectionHistor
 
   useEffect(() => {
     fetchConnections()
-  }, [])
+  }, [sort, search])
+
+  const updateProfile = async (conn: DatabaseConnection, update: ConnectionProfileUpdate) => {
+    try {
+      await api.updateConnectionProfile(conn.id, update)
+      setError(null)
+      setEditing(null)
+      fetchConnections()
+    } catch (err) {
+      setError(errorMessage(err, 'Failed to update connection'))
+    }
+  }
+
+  const startEditing = (conn: DatabaseConnection) => {
+    setEditing(conn.id)
+    setError(null)
+    setDraft({
+      alias: conn.alias ?? '',
+      color: conn.color ?? '',
+      folder: conn.folder ?? '',
+      notes: conn.notes ?? '',
+    })
+  }
 
   const formatDate = (dateString: string) => {
     const date = new Date(dateString)
     const now = new Date()
     const diff = now.getTime() - date.getTime()
-    
+
     const minutes = Math.floor(diff / 60000)
     const hours = Math.floor(minutes / 60)
     const days = Math.floor(hours / 24)
@@ -48,7 +73,7 @@ export default function ConnectionHistory({ onSelectDatabase }: ConnectionHistor
     if (minutes < 60) return `${minutes}m ago`
     if (hours < 24) return `${hours}h ago`
     if (days < 7) return `${days}d ago`
-    
+
     return date.toLocaleDateString()
   }
 
@@ -61,7 +86,7 @@ export default function ConnectionHistory({ onSelectDatabase }: ConnectionHistor
     )
   }
 
-  if (connections.length === 0) {
+  if (connections.length === 0 && !search) {
     return (
       <div className="text-center py-4 text-gray-400 text-xs">
         <Database className="w-8 h-8 mx-auto mb-1 opacity-50" />
@@ -72,33 +97,132 @@ export default function ConnectionHistory({ onSelectDatabase }: ConnectionHistor
 
   return (
     <div className="space-y-1">
-      <h3 className="text-xs font-semibold text-gray-400 mb-2 flex items-center gap-1">
-        <Clock className="w-3 h-3" />
-        Recent ({connections.length})
-      </h3>
-      
-      {connections.map((conn) => (
-        <button
-          key={conn.path}
-          onClick={() => onSelectDatabase?.(conn.path)}
-          className="w-full px-2 py-1.5 bg-gray-700 hover:bg-gray-600 rounded transition text-left"
-        >
-          <div className="flex items-start justify-between">
-            <div className="flex items-start gap-1 flex-1 min-w-0">
-              <Folder className="w-3 h-3 text-blue-400 mt-0.5 flex-shrink-0" />
-              <div className="flex-1 min-w-0">
-                <p className="font-mono text-xs truncate">{conn.path}</p>
-                <div className="flex items-center gap-2 mt-0.5 text-[10px] text-gray-400">
-                  <span>{conn.collections_count} colls</span>
-                  <span>{conn.total_documents} docs</span>
+      <div className="flex items-center justify-between mb-2 gap-2">
+        <h3 className="text-xs font-semibold text-gray-400 flex items-center gap-1">
+          <Clock className="w-3 h-3" />
+          Recent ({connections.length})
+        </h3>
+        <div className="flex items-center gap-1">
+          <div className="relative">
+            <Search className="w-3 h-3 text-gray-400 absolute left-1.5 top-1.5" />
+            <input
+              value={search}
+              onChange={(e) => setSearch(e.target.value)}
+              placeholder="Filter"
+              className="bg-gray-700 rounded pl-5 pr-1 py-0.5 text-xs w-28"
+            />
+          </div>
+          <select
+            value={sort}
+            onChange={(e) => setSort(e.target.value as ConnectionSort)}
+            className="bg-gray-700 rounded px-1 py-0.5 text-xs"
+          >
+            {SORT_OPTIONS.map((option) => (
+              <option key={option.value} value={option.value}>{option.label}</option>
+            ))}
+          </select>
+        </div>
+      </div>
+
+      {error && <p className="text-xs text-red-400">{error}</p>}
+
+      {connections.map((conn, i) => (
+        <div key={conn.path}>
+          {sort === 'folder' && conn.folder !== connections[i - 1]?.folder && (
+            <p className="text-[10px] uppercase text-gray-500 mt-2 mb-0.5">{conn.folder ?? 'No folder'}</p>
+          )}
+          <div
+            onClick={() => onSelectDatabase?.(conn.path)}
+            className="w-full px-2 py-1.5 bg-gray-700 hover:bg-gray-600 rounded transition text-left cursor-pointer"
+          >
+            <div className="flex items-start justify-between">
+              <div className="flex items-start gap-1 flex-1 min-w-0">
+                {conn.color ? (
+                  <span
+                    className="w-2 h-2 rounded-full mt-1 flex-shrink-0"
+                    style={{ backgroundColor: conn.color }}
+                  />
+                ) : (
+                  <Folder className="w-3 h-3 text-blue-400 mt-0.5 flex-shrink-0" />
+                )}
+                <div className="flex-1 min-w-0">
+                  {conn.alias && <p className="text-xs font-semibold truncate">{conn.alias}</p>}
+                  <p className="font-mono text-xs truncate">{conn.path}</p>
+                  <div className="flex items-center gap-2 mt-0.5 text-[10px] text-gray-400">
+                    <span>{conn.collections_count} colls</span>
+                    <span>{conn.total_documents} docs</span>
+                    {conn.folder && sort !== 'folder' && <span>{conn.folder}</span>}
+                  </div>
+                  {conn.notes && <p className="text-[10px] text-gray-400 truncate mt-0.5">{conn.notes}</p>}
                 </div>
               </div>
-            </div>
-            <div className="text-[10px] text-gray-400 flex-shrink-0 ml-1">
-              {formatDate(conn.last_accessed)}
+              <div className="flex items-center gap-1 text-[10px] text-gray-400 flex-shrink-0 ml-1">
+                {formatDate(conn.last_accessed)}
+                <button
+                  onClick={(e) => {
+                    e.stopPropagation()
+                    startEditing(conn)
+                  }}
+                  title="Edit profile"
+                  className="hover:text-white"
+                >
+                  <Pencil className="w-3 h-3" />
+                </button>
+                <button
+                  onClick={(e) => {
+                    e.stopPropagation()
+                    updateProfile(conn, { favorite: !conn.favorite })
+                  }}
+                  title={conn.favorite ? 'Remove from favorites' : 'Add to favorites'}
+                >
+                  <Star className={`w-3 h-3 ${conn.favorite ? 'text-yellow-400 fill-yellow-400' : 'hover:text-white'}`} />
+                </button>
+              </div>
             </div>
           </div>
-        </button>
+
+          {editing === conn.id && (
+            <form
+              onSubmit={(e) => {
+                e.preventDefault()
+                updateProfile(conn, draft)
+              }}
+              className="bg-gray-900 rounded p-2 mt-1 grid grid-cols-2 gap-1 text-xs"
+            >
+              <input
+                value={draft.alias}
+                onChange={(e) => setDraft({ ...draft, alias: e.target.value })}
+                placeholder="Alias"
+                className="bg-gray-700 rounded px-1 py-0.5"
+              />
+              <input
+                value={draft.color}
+                onChange={(e) => setDraft({ ...draft, color: e.target.value })}
+                placeholder="Color (#1e90ff or red)"
+                className="bg-gray-700 rounded px-1 py-0.5"
+              />
+              <input
+                value={draft.folder}
+                onChange={(e) => setDraft({ ...draft, folder: e.target.value })}
+                placeholder="Folder (e.g. clients/acme)"
+                className="bg-gray-700 rounded px-1 py-0.5 col-span-2"
+              />
+              <textarea
+                value={draft.notes}
+                onChange={(e) => setDraft({ ...draft, notes: e.target.value })}
+                placeholder="Notes"
+                rows={2}
+                className="bg-gray-700 rounded px-1 py-0.5 col-span-2"
+              />
+              <button type="button" onClick={() => setEditing(null)} className="px-2 py-0.5 bg-gray-700 rounded">
+                Cancel
+              </button>
+              <button type="submit" className="px-2 py-0.5 bg-blue-600 hover:bg-blue-700 rounded">
+                Save
+              </button>
+            </form>
+          )}
+        </div>
       ))}
     </div>
   )
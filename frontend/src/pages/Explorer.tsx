@@ -1,8 +1,8 @@
-import { useState, useRef, useCallback } from 'react'
+import { useState, useRef, useCallback, useEffect } from 'react'
 import { useQuery, useMutation, useQueryClient } from '@tanstack/react-query'
-import { api, VectorCollectionInfo, VectorDocument, VectorSearchResult, VectorCollectionStats } from '../api'
+import { api, ApiRequestError, errorMessage, ChangeEvent, Job, ParseDocumentResult, ParseProgress, VectorCollectionInfo, VectorDocument, VectorSearchResult, VectorCollectionStats } from '../api'
 import VectorVisualization from '../components/VectorVisualization'
-import { parseFiles, ACCEPT_STRING, ParsedFile } from '../utils/fileParser'
+import { parseFiles, ACCEPT_STRING, SUPPORTED_EXTENSIONS, ParsedFile } from '../utils/fileParser'
 import { 
   Database, 
   Plus, 
@@ -52,6 +52,7 @@ export default function Explorer({ database }: ExplorerProps) {
   const [searchQuery, setSearchQuery] = useState('')
   const [showNewCollectionDialog, setShowNewCollectionDialog] = useState(false)
   const [newCollectionName, setNewCollectionName] = useState('')
+  const [dropJob, setDropJob] = useState<Job | null>(null)
   
   // Vector collection state
   const [selectedVectorCollection, setSelectedVectorCollection] = useState<string | null>(null)
@@ -77,6 +78,7 @@ export default function Explorer({ database }: ExplorerProps) {
   const fileInputRef = useRef<HTMLInputElement>(null)
   const [uploadedFiles, setUploadedFiles] = useState<ParsedFile[]>([])
   const [isProcessingFiles, setIsProcessingFiles] = useState(false)
+  const [parseProgress, setParseProgress] = useState<ParseProgress | null>(null)
   const [chunkSize, setChunkSize] = useState(500)
   
   // Resizable panel state
@@ -107,6 +109,36 @@ export default function Explorer({ database }: ExplorerProps) {
   
   const queryClient = useQueryClient()
 
+  // Refresh whatever another window or teammate changed in this database
+  useEffect(() => {
+    if (!database) return
+
+    const refresh = (event: ChangeEvent) => {
+      if (event.target === 'document') {
+        queryClient.invalidateQueries({ queryKey: ['documents', database, event.collection] })
+        queryClient.invalidateQueries({ queryKey: ['collections', database] })
+      } else {
+        queryClient.invalidateQueries({ queryKey: ['vectors', database, event.collection] })
+        queryClient.invalidateQueries({ queryKey: ['vectorCollectionStats', database, event.collection] })
+        queryClient.invalidateQueries({ queryKey: ['vectorCollections', database] })
+      }
+    }
+
+    let cancelled = false
+    let unsubscribe: (() => Promise<void>) | undefined
+    api.subscribeChanges({ dbPath: database }, refresh)
+      .then((subscribed) => {
+        if (cancelled) subscribed.unsubscribe()
+        else unsubscribe = subscribed.unsubscribe
+      })
+      .catch((err) => console.warn(`Live updates unavailable: ${errorMessage(err, 'Unknown error')}`))
+
+    return () => {
+      cancelled = true
+      unsubscribe?.()
+    }
+  }, [database, queryClient])
+
   // Fetch collections
   const { data: collections, isLoading: loadingCollections } = useQuery<Collection[]>({
     queryKey: ['collections', database],
@@ -161,15 +193,21 @@ export default function Explorer({ database }: ExplorerProps) {
     },
   })
 
-  // Drop collection mutation
+  // Drop collection mutation - runs as a background job, one delete per document
   const dropCollectionMutation = useMutation({
     mutationFn: async (collectionName: string) => {
-      await api.dropCollection(database!, collectionName)
+      const job = await api.runJob(
+        { kind: 'drop_collection', db: database!, collection: collectionName },
+        setDropJob
+      )
+      if (job.status === 'failed') throw new Error(job.error || 'Failed to drop collection')
     },
     onSuccess: () => {
       queryClient.invalidateQueries({ queryKey: ['collections'] })
       setSelectedCollection(null)
     },
+    onError: (err) => alert(errorMessage(err, 'Failed to drop collection')),
+    onSettled: () => setDropJob(null),
   })
 
   // =====================
@@ -282,7 +320,7 @@ export default function Explorer({ database }: ExplorerProps) {
       setSelectedCollection(newCollectionName)
     } catch (err: any) {
       console.error('Failed to create collection:', err)
-      alert(err || 'Failed to create collection')
+      alert(errorMessage(err, 'Failed to create collection'))
     }
   }
 
@@ -402,7 +440,7 @@ export default function Explorer({ database }: ExplorerProps) {
       setQueryVector(vector)
       setShowSearchVectorDialog(false)
     } catch (err: any) {
-      alert(`Search failed: ${err.message || err}`)
+      alert(`Search failed: ${errorMessage(err, 'unknown error')}`)
     }
   }
 
@@ -418,6 +456,73 @@ export default function Explorer({ database }: ExplorerProps) {
     setQueryVector(undefined)
   }
 
+  const archiveEntries = (result: ParseDocumentResult): ParsedFile[] => {
+    const entries: ParsedFile[] = []
+    for (const entry of result.files || []) {
+      if (entry.success && entry.text) {
+        entries.push({
+          name: entry.filename,
+          content: entry.text,
+          size: entry.text.length,
+          type: '.' + (entry.file_type || ''),
+          metadata: entry.metadata
+        })
+      } else {
+        console.warn(`Skipped ${entry.filename} in ${result.filename}: ${entry.error?.message}`)
+      }
+    }
+    return entries
+  }
+
+  // Desktop app: pick files natively and parse them in place by path, falling
+  // back to the browser file input when the native dialog is unavailable
+  const handleBrowseFiles = async () => {
+    let paths: string[]
+    try {
+      paths = await api.pickDocumentFiles(SUPPORTED_EXTENSIONS.map(ext => ext.slice(1)))
+    } catch {
+      fileInputRef.current?.click()
+      return
+    }
+    if (paths.length === 0) return
+
+    setIsProcessingFiles(true)
+    try {
+      const loadedFiles: ParsedFile[] = []
+      for (const path of paths) {
+        const name = path.split(/[\\/]/).pop() || path
+        const ext = name.split('.').pop()?.toLowerCase() || ''
+        try {
+          const result = await api.parseDocumentFile(path, setParseProgress)
+          if (result.archive) {
+            loadedFiles.push(...archiveEntries(result))
+          } else {
+            loadedFiles.push({
+              name,
+              content: result.text || '',
+              size: result.char_count || 0,
+              type: '.' + ext,
+              metadata: result.metadata
+            })
+          }
+        } catch (err) {
+          const message = errorMessage(err, 'Unknown error')
+          console.error(`Failed to parse ${name}: ${message}`)
+          loadedFiles.push({
+            name,
+            content: `[Error parsing ${name}: ${message}]`,
+            size: 0,
+            type: '.' + ext
+          })
+        }
+      }
+      setUploadedFiles(loadedFiles)
+    } finally {
+      setIsProcessingFiles(false)
+      setParseProgress(null)
+    }
+  }
+
   // File upload handlers - use backend for PDF/DOCX/Excel, frontend for text files
   const handleFileSelect = async (e: React.ChangeEvent<HTMLInputElement>) => {
     const files = e.target.files
@@ -429,32 +534,42 @@ export default function Explorer({ database }: ExplorerProps) {
       
       for (const file of Array.from(files)) {
         const ext = file.name.split('.').pop()?.toLowerCase() || ''
-        
+        const isArchive = /\.(zip|tar\.gz|tgz)$/i.test(file.name)
+
+        // Archives are unpacked by the backend, one parsed entry per file
+        if (isArchive) {
+          try {
+            const result = await api.parseDocument(file)
+            loadedFiles.push(...archiveEntries(result))
+          } catch (err) {
+            console.error(`Error reading archive ${file.name}:`, err)
+          }
         // Use backend for complex document formats
-        if (['pdf', 'docx', 'doc', 'xlsx', 'xls', 'pptx', 'ppt'].includes(ext)) {
+        } else if (['pdf', 'docx', 'doc', 'xlsx', 'xls', 'pptx', 'ppt', 'html', 'htm', 'epub', 'eml', 'mbox'].includes(ext)) {
           try {
             const result = await api.parseDocument(file)
-            if (result.success && result.text) {
+            loadedFiles.push({
+              name: file.name,
+              content: result.text || '',
+              size: file.size,
+              type: '.' + ext,
+              metadata: result.metadata
+            })
+          } catch (err) {
+            if (err instanceof ApiRequestError && err.code === 'PARSE_FAILED') {
+              console.error(`Failed to parse ${file.name}: ${err.message}`)
               loadedFiles.push({
                 name: file.name,
-                content: result.text,
+                content: `[Error parsing ${file.name}: ${err.message}]`,
                 size: file.size,
                 type: '.' + ext
               })
             } else {
-              console.error(`Failed to parse ${file.name}: ${result.error}`)
-              loadedFiles.push({
-                name: file.name,
-                content: `[Error parsing ${file.name}: ${result.error}]`,
-                size: file.size,
-                type: '.' + ext
-              })
+              console.error(`Error parsing ${file.name}:`, err)
+              // Fallback to frontend parsing
+              const parsed = await parseFiles([file])
+              loadedFiles.push(...parsed)
             }
-          } catch (err) {
-            console.error(`Error parsing ${file.name}:`, err)
-            // Fallback to frontend parsing
-            const parsed = await parseFiles([file])
-            loadedFiles.push(...parsed)
           }
         } else {
           // Use frontend for text-based files
@@ -508,6 +623,7 @@ export default function Explorer({ database }: ExplorerProps) {
           const vector = textToVector(chunk, vectorCollectionStats.dimensions)
           
           await api.insertVector(database!, selectedVectorCollection!, vector, {
+            ...file.metadata,
             source: file.name,
             chunk_index: i,
             total_chunks: chunks.length,
@@ -527,7 +643,7 @@ export default function Explorer({ database }: ExplorerProps) {
       setShowUploadDialog(false)
       setUploadedFiles([])
     } catch (err: any) {
-      alert(`Failed to process files: ${err.message || err}`)
+      alert(`Failed to process files: ${errorMessage(err, 'unknown error')}`)
     } finally {
       setIsProcessingFiles(false)
     }
@@ -601,6 +717,21 @@ export default function Explorer({ database }: ExplorerProps) {
                 </div>
               </div>
 
+              {/* Running drop job */}
+              {dropJob && (
+                <div className="flex items-center justify-between mb-2 text-[10px] text-gray-400">
+                  <span className="truncate">
+                    Dropping {dropJob.collection}: {dropJob.done}/{dropJob.total || '?'}
+                  </span>
+                  <button
+                    onClick={() => api.cancelJob(dropJob.id)}
+                    className="text-red-400 hover:text-red-300"
+                  >
+                    Cancel
+                  </button>
+                </div>
+              )}
+
               {loadingCollections ? (
                 <p className="text-gray-400 text-xs">Loading...</p>
               ) : collections && collections.length > 0 ? (
@@ -1457,7 +1588,7 @@ export default function Explorer({ database }: ExplorerProps) {
             <div className="flex-1 overflow-auto p-3 space-y-2">
               {/* File input */}
               <div
-                onClick={() => fileInputRef.current?.click()}
+                onClick={handleBrowseFiles}
                 className="border-2 border-dashed border-gray-600 rounded p-4 text-center cursor-pointer hover:border-purple-500 transition"
               >
                 <Upload className="w-8 h-8 mx-auto mb-2 text-gray-500" />
@@ -1473,6 +1604,17 @@ export default function Explorer({ database }: ExplorerProps) {
                 />
               </div>
 
+              {/* Progress of the file being parsed */}
+              {parseProgress && (
+                <p className="text-[10px] text-gray-400 truncate">
+                  {parseProgress.stage === 'reading'
+                    ? `Reading ${parseProgress.source}: ${Math.round((parseProgress.bytes_read / Math.max(parseProgress.total_bytes, 1)) * 100)}%`
+                    : parseProgress.files_total > 0
+                      ? `Parsing ${parseProgress.source}: ${parseProgress.files_done}/${parseProgress.files_total} files`
+                      : `Parsing ${parseProgress.source}...`}
+                </p>
+              )}
+
               {/* Selected files */}
               {uploadedFiles.length > 0 && (
                 <div className="space-y-1">
@@ -1,7 +1,7 @@
 import { useState } from 'react'
 import { useNavigate, Link } from 'react-router-dom'
 import { Database, FolderOpen, Plus, Activity } from 'lucide-react'
-import { api } from '../api'
+import { api, errorMessage } from '../api'
 import ConnectionHistory from '../components/ConnectionHistory'
 
 interface HomeProps {
@@ -50,7 +50,7 @@ export default function Home({ onOpenDatabase }: HomeProps) {
       onOpenDatabase(dbPath)
       navigate('/explorer')
     } catch (err: any) {
-      setError(err || 'Failed to open database')
+      setError(errorMessage(err, 'Failed to open database'))
     } finally {
       setLoading(false)
     }
@@ -70,7 +70,7 @@ export default function Home({ onOpenDatabase }: HomeProps) {
       onOpenDatabase(dbPath)
       navigate('/explorer')
     } catch (err: any) {
-      setError(err || 'Failed to create database')
+      setError(errorMessage(err, 'Failed to create database'))
     } finally {
       setLoading(false)
     }
@@ -183,7 +183,7 @@ export default function Home({ onOpenDatabase }: HomeProps) {
                 onOpenDatabase(path)
                 navigate('/explorer')
               } catch (err: any) {
-                setError(err || 'Failed to open database')
+                setError(errorMessage(err, 'Failed to open database'))
               }
             }}
           />
@@ -1,6 +1,6 @@
 import { useEffect, useState } from 'react'
-import { Activity, Database, Layers, FileText, TrendingUp, Zap, AlertCircle } from 'lucide-react'
-import { api } from '../api'
+import { Activity, Database, Layers, FileText, TrendingUp, Zap, AlertCircle, Clock } from 'lucide-react'
+import { api, errorMessage, Granularity, MetricRollup, SlowOperation } from '../api'
 
 interface SystemStats {
   total_databases: number
@@ -27,10 +27,67 @@ interface PerformanceMetric {
   timestamp: string
 }
 
+// Window of history charted for each bucket size
+const TREND_WINDOW_MS: Record<Granularity, number> = {
+  minute: 60 * 60 * 1000,
+  hour: 2 * 24 * 60 * 60 * 1000,
+  day: 30 * 24 * 60 * 60 * 1000,
+}
+
+const TREND_SERIES = [
+  { key: 'p50_ms', label: 'p50', color: '#4ade80' },
+  { key: 'p95_ms', label: 'p95', color: '#facc15' },
+  { key: 'max_ms', label: 'max', color: '#f87171' },
+] as const
+
+function TrendChart({ rollups }: { rollups: MetricRollup[] }) {
+  const width = 560
+  const height = 120
+  const pad = 4
+  const top = Math.max(1, ...rollups.map((r) => r.max_ms))
+  const x = (i: number) => pad + (rollups.length === 1 ? 0 : (i * (width - 2 * pad)) / (rollups.length - 1))
+  const y = (ms: number) => height - pad - (ms / top) * (height - 2 * pad)
+
+  return (
+    <div>
+      <svg viewBox={`0 0 ${width} ${height}`} className="w-full h-28 bg-gray-800 rounded">
+        {TREND_SERIES.map((series) => (
+          <polyline
+            key={series.key}
+            fill="none"
+            stroke={series.color}
+            strokeWidth={1.5}
+            points={rollups.map((r, i) => `${x(i)},${y(r[series.key])}`).join(' ')}
+          />
+        ))}
+        {rollups.length === 1 &&
+          TREND_SERIES.map((series) => (
+            <circle key={series.key} cx={x(0)} cy={y(rollups[0][series.key])} r={2} fill={series.color} />
+          ))}
+      </svg>
+      <div className="flex items-center justify-between text-[10px] text-gray-500 mt-0.5">
+        <span>{new Date(rollups[0].bucket_start).toLocaleString()}</span>
+        <span className="flex gap-2">
+          {TREND_SERIES.map((series) => (
+            <span key={series.key} style={{ color: series.color }}>{series.label}</span>
+          ))}
+          <span>peak {top}ms</span>
+        </span>
+        <span>{new Date(rollups[rollups.length - 1].bucket_start).toLocaleString()}</span>
+      </div>
+    </div>
+  )
+}
+
 export default function SystemMonitor() {
   const [stats, setStats] = useState<SystemStats | null>(null)
   const [metrics, setMetrics] = useState<PerformanceMetric[]>([])
   const [selectedDb, setSelectedDb] = useState<string | null>(null)
+  const [granularity, setGranularity] = useState<Granularity>('minute')
+  const [rollups, setRollups] = useState<MetricRollup[]>([])
+  const [trendOperation, setTrendOperation] = useState<string | null>(null)
+  const [slowOps, setSlowOps] = useState<SlowOperation[]>([])
+  const [expandedSlowOp, setExpandedSlowOp] = useState<string | null>(null)
   const [loading, setLoading] = useState(true)
   const [error, setError] = useState<string | null>(null)
   const [autoRefresh, setAutoRefresh] = useState(true)
@@ -41,21 +98,44 @@ export default function SystemMonitor() {
       setStats(stats)
       setError(null)
     } catch (err: any) {
-      setError(err || 'Failed to fetch system stats')
+      setError(errorMessage(err, 'Failed to fetch system stats'))
     } finally {
       setLoading(false)
     }
   }
 
-  const fetchMetrics = async (dbPath: string) => {
+  const fetchMetrics = async (dbPath: string, granularity: Granularity) => {
     try {
-      const metrics = await api.getDatabaseMetrics(dbPath, 50)
+      const from = new Date(Date.now() - TREND_WINDOW_MS[granularity]).toISOString()
+      const [metrics, rollups] = await Promise.all([
+        api.getDatabaseMetrics(dbPath, 50),
+        api.getMetricRollups({ db: dbPath, granularity, from }),
+      ])
       setMetrics(metrics)
+      setRollups(rollups)
     } catch (err: any) {
       console.error('Failed to fetch metrics:', err)
     }
   }
 
+  // Slow operations of the selected database, or of all databases
+  const fetchSlowOps = async (dbPath: string | null) => {
+    try {
+      setSlowOps(await api.getSlowOperations({ db: dbPath ?? undefined, limit: 20 }))
+    } catch (err: any) {
+      console.error('Failed to fetch slow operations:', err)
+    }
+  }
+
+  useEffect(() => {
+    fetchSlowOps(selectedDb)
+
+    if (autoRefresh) {
+      const interval = setInterval(() => fetchSlowOps(selectedDb), 5000)
+      return () => clearInterval(interval)
+    }
+  }, [selectedDb, autoRefresh])
+
   useEffect(() => {
     fetchSystemStats()
 
@@ -67,14 +147,14 @@ export default function SystemMonitor() {
 
   useEffect(() => {
     if (selectedDb) {
-      fetchMetrics(selectedDb)
+      fetchMetrics(selectedDb, granularity)
       
       if (autoRefresh) {
-        const interval = setInterval(() => fetchMetrics(selectedDb), 5000)
+        const interval = setInterval(() => fetchMetrics(selectedDb, granularity), 5000)
         return () => clearInterval(interval)
       }
     }
-  }, [selectedDb, autoRefresh])
+  }, [selectedDb, granularity, autoRefresh])
 
   const formatDate = (dateString: string) => {
     const date = new Date(dateString)
@@ -94,6 +174,19 @@ export default function SystemMonitor() {
     return Math.round(sum / dbMetrics.length)
   }
 
+  // Operations with rollups, busiest first; the chart defaults to the busiest
+  const trendOperations = Object.entries(
+    rollups.reduce<Record<string, number>>((acc, r) => {
+      acc[r.operation] = (acc[r.operation] || 0) + r.count
+      return acc
+    }, {})
+  )
+    .sort((a, b) => b[1] - a[1])
+    .map(([operation]) => operation)
+  const chartedOperation =
+    trendOperation && trendOperations.includes(trendOperation) ? trendOperation : trendOperations[0]
+  const trend = rollups.filter((r) => r.operation === chartedOperation)
+
   const getOperationColor = (operation: string) => {
     const colors: Record<string, string> = {
       open_database: 'bg-blue-500',
@@ -258,7 +351,38 @@ export default function SystemMonitor() {
                         <TrendingUp className="w-3 h-3 text-green-400" />
                         <span className="text-[10px] font-semibold">Metrics</span>
                       </div>
-                      
+
+                      <div className="mb-2" onClick={(e) => e.stopPropagation()}>
+                        <div className="flex items-center gap-2 mb-1 text-[10px]">
+                          <span className="text-gray-400">Trend</span>
+                          <select
+                            value={granularity}
+                            onChange={(e) => setGranularity(e.target.value as Granularity)}
+                            className="bg-gray-800 border border-gray-600 rounded px-1 py-0.5"
+                          >
+                            <option value="minute">Per minute (1h)</option>
+                            <option value="hour">Per hour (48h)</option>
+                            <option value="day">Per day (30d)</option>
+                          </select>
+                          {trendOperations.length > 0 && (
+                            <select
+                              value={chartedOperation}
+                              onChange={(e) => setTrendOperation(e.target.value)}
+                              className="bg-gray-800 border border-gray-600 rounded px-1 py-0.5"
+                            >
+                              {trendOperations.map((operation) => (
+                                <option key={operation} value={operation}>{operation}</option>
+                              ))}
+                            </select>
+                          )}
+                        </div>
+                        {trend.length === 0 ? (
+                          <p className="text-gray-400 text-[10px]">No samples in this window</p>
+                        ) : (
+                          <TrendChart rollups={trend} />
+                        )}
+                      </div>
+
                       {metrics.length === 0 ? (
                         <p className="text-gray-400 text-[10px]">No metrics</p>
                       ) : (
@@ -300,6 +424,61 @@ export default function SystemMonitor() {
           )}
         </div>
 
+        {/* Slow Operations */}
+        <div className="bg-gray-800 rounded p-3 mb-4 border border-gray-700">
+          <h2 className="text-sm font-semibold mb-2 flex items-center gap-1">
+            <Clock className="w-3.5 h-3.5 text-yellow-400" />
+            Slow Operations
+            <span className="text-gray-400 text-[10px] font-normal font-mono truncate">
+              {selectedDb ?? 'all databases'}
+            </span>
+          </h2>
+
+          {slowOps.length === 0 ? (
+            <p className="text-gray-400 text-xs text-center py-4">No slow operations</p>
+          ) : (
+            <div className="space-y-0.5 max-h-64 overflow-y-auto">
+              {slowOps.map((op) => (
+                <div key={op.id} className="text-[10px] bg-gray-700 rounded">
+                  <div
+                    onClick={() => setExpandedSlowOp(expandedSlowOp === op.id ? null : op.id)}
+                    className="grid grid-cols-6 gap-2 px-2 py-1 cursor-pointer hover:bg-gray-600 rounded"
+                  >
+                    <span className="text-gray-400">{formatDate(op.timestamp)}</span>
+                    <span className="flex items-center gap-1">
+                      <div className={`w-1.5 h-1.5 rounded-full ${getOperationColor(op.operation)}`} />
+                      {op.operation}
+                    </span>
+                    <span className="font-mono truncate">{op.collection ?? '-'}</span>
+                    <span className="text-yellow-400 font-semibold">{formatDuration(op.duration_ms)}</span>
+                    <span className="text-gray-400">
+                      {op.result_size != null ? `${op.result_size} results` : '-'}
+                    </span>
+                    <span className="text-gray-400 truncate">
+                      {op.caller?.token_name ?? op.caller?.ip ?? 'local'}
+                    </span>
+                  </div>
+                  {expandedSlowOp === op.id && (
+                    <div className="px-2 pb-1 space-y-0.5 text-gray-300">
+                      <p className="font-mono truncate">{op.database_path}</p>
+                      {op.caller && (
+                        <p>
+                          Caller: {op.caller.token_name ?? 'no token'}
+                          {op.caller.token_id && ` (${op.caller.token_id})`}
+                          {op.caller.ip && ` from ${op.caller.ip}`}
+                        </p>
+                      )}
+                      <pre className="font-mono bg-gray-800 rounded p-1 overflow-x-auto">
+                        {JSON.stringify(op.params ?? {}, null, 2)}
+                      </pre>
+                    </div>
+                  )}
+                </div>
+              ))}
+            </div>
+          )}
+        </div>
+
         {/* Legend */}
         <div className="bg-gray-800 rounded p-2 border border-gray-700">
           <h3 className="text-[10px] font-semibold mb-1">Operations</h3>
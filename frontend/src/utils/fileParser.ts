@@ -3,6 +3,8 @@ export interface ParsedFile {
   content: string
   size: number
   type: string
+  // Document metadata extracted by the backend parser, stored with each vector
+  metadata?: Record<string, string | number | undefined>
 }
 
 // Supported file extensions
@@ -20,7 +22,11 @@ export const SUPPORTED_EXTENSIONS = [
   // Config files
   '.toml', '.ini', '.env', '.gitignore', '.dockerfile',
   // Documents
-  '.pdf', '.docx', '.doc', '.xlsx', '.xls', '.pptx', '.ppt',
+  '.pdf', '.docx', '.doc', '.xlsx', '.xls', '.pptx', '.ppt', '.epub',
+  // Email
+  '.eml', '.mbox',
+  // Archives (unpacked by the backend)
+  '.zip', '.tgz', '.gz',
   // Log files
   '.log'
 ]
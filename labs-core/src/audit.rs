@@ -0,0 +1,185 @@
+//! Append-only audit log of write operations. Every insert, update and
+//! delete of documents and vectors, every dropped collection and every
+//! deleted database is recorded with the caller current on the thread and,
+//! for document and vector updates and deletes, the state before the change.
+//! A dropped document collection keeps every deleted document as its
+//! before-image.
+//!
+//! Deleted databases and dropped vector collections are the exception: they
+//! record what was there (collections and document counts, or the vector
+//! configuration and count) but not the data itself, which can be far
+//! larger than the log is meant to hold. Keep backups for those.
+
+use crate::slow_log::Caller;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Entries returned when no limit is given
+pub const DEFAULT_AUDIT_LIMIT: usize = 100;
+
+/// Matches `AuditQuery::actor` for operations without a caller, such as
+/// those of the desktop app
+pub const LOCAL_ACTOR: &str = "local";
+
+/// Named like the operations `LabsService` records timings under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    InsertDocument,
+    UpdateDocument,
+    DeleteDocument,
+    DropCollection,
+    CreateVectorCollection,
+    DropVectorCollection,
+    InsertVector,
+    DeleteVector,
+    DeleteDatabase,
+}
+
+/// One recorded write
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AuditEntry {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub action: AuditAction,
+    pub database_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    /// Document or vector id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
+    /// Token and address of the request; unset for the desktop app
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caller: Option<Caller>,
+    /// Document or vector as it was before an update or delete; the
+    /// deleted documents, in an array, for a dropped collection; what was
+    /// there for a deleted database or vector collection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub before: Option<serde_json::Value>,
+    /// Counts for bulk operations, e.g. `documents_deleted`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub details: Option<serde_json::Value>,
+}
+
+impl AuditEntry {
+    /// An entry for `action` on `database_path`, made now by the current caller
+    pub fn new(action: AuditAction, database_path: &str) -> Self {
+        Self {
+            id: String::new(),
+            timestamp: Utc::now(),
+            action,
+            database_path: database_path.to_string(),
+            collection: None,
+            target_id: None,
+            caller: crate::slow_log::current_caller(),
+            before: None,
+            details: None,
+        }
+    }
+
+    pub fn collection(mut self, collection: &str) -> Self {
+        self.collection = Some(collection.to_string());
+        self
+    }
+
+    pub fn target(mut self, id: impl ToString) -> Self {
+        self.target_id = Some(id.to_string());
+        self
+    }
+
+    pub fn before(mut self, before: Option<serde_json::Value>) -> Self {
+        self.before = before;
+        self
+    }
+
+    pub fn details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+/// Selects audit entries; unset fields match everything. `db` accepts any
+/// database handle.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct AuditQuery {
+    /// Database id, alias, logical name or path
+    pub db: Option<String>,
+    pub action: Option<AuditAction>,
+    pub collection: Option<String>,
+    /// Token id, token name or client IP; `local` for operations without a
+    /// caller
+    pub actor: Option<String>,
+    /// Inclusive start (RFC 3339)
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive end (RFC 3339)
+    pub to: Option<DateTime<Utc>>,
+    /// Maximum number of entries, most recent first (default 100); exports
+    /// are not limited unless this is set
+    pub limit: Option<usize>,
+}
+
+/// `AuditQuery` with the database resolved to the name entries are
+/// recorded under
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub database_path: Option<String>,
+    pub action: Option<AuditAction>,
+    pub collection: Option<String>,
+    pub actor: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.database_path.as_ref().is_none_or(|db| *db == entry.database_path)
+            && self.action.is_none_or(|action| action == entry.action)
+            && self.collection.as_ref().is_none_or(|c| entry.collection.as_ref() == Some(c))
+            && self.actor.as_deref().is_none_or(|actor| is_actor(entry.caller.as_ref(), actor))
+            && self.from.is_none_or(|from| entry.timestamp >= from)
+            && self.to.is_none_or(|to| entry.timestamp < to)
+    }
+}
+
+fn is_actor(caller: Option<&Caller>, actor: &str) -> bool {
+    match caller {
+        None => actor == LOCAL_ACTOR,
+        Some(caller) => [&caller.token_id, &caller.token_name, &caller.ip]
+            .into_iter()
+            .any(|field| field.as_deref() == Some(actor)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_actor_filter() {
+        let local = AuditEntry::new(AuditAction::DeleteDocument, "a.db");
+        let remote = AuditEntry {
+            caller: Some(Caller {
+                token_id: Some("t1".to_string()),
+                token_name: Some("ci".to_string()),
+                ip: Some("10.0.0.7".to_string()),
+            }),
+            ..local.clone()
+        };
+
+        let filter = |actor: &str| AuditFilter {
+            actor: Some(actor.to_string()),
+            ..Default::default()
+        };
+        assert!(filter(LOCAL_ACTOR).matches(&local));
+        assert!(!filter(LOCAL_ACTOR).matches(&remote));
+        for actor in ["t1", "ci", "10.0.0.7"] {
+            assert!(filter(actor).matches(&remote));
+        }
+        assert!(!filter("other").matches(&remote));
+    }
+}
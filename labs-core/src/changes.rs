@@ -0,0 +1,293 @@
+//! Change feed for documents and vectors. `LabsService` publishes an event
+//! after every successful mutation; the REST backend forwards them over a
+//! WebSocket and the desktop app as window events.
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Events kept for clients resuming after a reconnect
+const DEFAULT_HISTORY: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeTarget {
+    Document,
+    Vector,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeOp {
+    Insert,
+    Update,
+    Delete,
+    /// The whole collection was dropped; `id` is not set
+    Drop,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ChangeEvent {
+    /// Identifies the feed that published the event; changes whenever the
+    /// process restarts
+    pub epoch: String,
+    /// Increases by one per event within an epoch, starting at 1
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    /// Database name as resolved by `DatabaseRoots`
+    pub db: String,
+    pub collection: String,
+    pub target: ChangeTarget,
+    pub op: ChangeOp,
+    /// Document id, or the vector id as a string
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Document as inserted or stored after an update; vector metadata for
+    /// vector inserts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub document: Option<serde_json::Value>,
+}
+
+/// Which events a subscriber receives; unset fields match everything
+#[derive(Debug, Clone, Default)]
+pub struct ChangeFilter {
+    pub db: Option<String>,
+    pub collection: Option<String>,
+}
+
+impl ChangeFilter {
+    pub fn matches(&self, event: &ChangeEvent) -> bool {
+        self.db.as_ref().is_none_or(|db| *db == event.db)
+            && self.collection.as_ref().is_none_or(|c| *c == event.collection)
+    }
+}
+
+/// Returned when subscribing
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Subscription {
+    pub id: u64,
+    /// Epoch of the feed; pass it back with `seq` to resume
+    pub epoch: String,
+    /// Sequence number of the latest event published so far
+    pub seq: u64,
+    /// Events after the requested resume point are no longer available, or
+    /// it belongs to another epoch; the client should reload instead of
+    /// replaying
+    pub missed: bool,
+}
+
+/// Last event a reconnecting client saw
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumePoint {
+    pub epoch: String,
+    pub seq: u64,
+}
+
+/// Receives matching events; returning false unsubscribes. Called while the
+/// feed is locked, so it must hand the event off rather than block.
+type Sink = Box<dyn Fn(&ChangeEvent) -> bool + Send + Sync>;
+
+struct Subscriber {
+    filter: ChangeFilter,
+    sink: Sink,
+}
+
+struct FeedState {
+    last_seq: u64,
+    history: VecDeque<ChangeEvent>,
+    subscribers: HashMap<u64, Subscriber>,
+    next_subscriber: u64,
+}
+
+pub struct ChangeFeed {
+    state: Mutex<FeedState>,
+    capacity: usize,
+    epoch: String,
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY)
+    }
+}
+
+impl ChangeFeed {
+    /// `capacity` is how many past events are kept for resuming
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(FeedState {
+                last_seq: 0,
+                history: VecDeque::with_capacity(capacity),
+                subscribers: HashMap::new(),
+                next_subscriber: 1,
+            }),
+            capacity,
+            epoch: format!("{:016x}", rand::random::<u64>()),
+        }
+    }
+
+    /// Random per feed, so sequence numbers from before a restart are never
+    /// mistaken for current ones
+    pub fn epoch(&self) -> &str {
+        &self.epoch
+    }
+
+    /// Sequence number of the latest event, 0 before the first
+    pub fn last_seq(&self) -> u64 {
+        self.state.lock().last_seq
+    }
+
+    /// Assign the next sequence number and deliver to matching subscribers
+    pub fn publish(
+        &self,
+        db: &str,
+        collection: &str,
+        target: ChangeTarget,
+        op: ChangeOp,
+        id: Option<String>,
+        document: Option<serde_json::Value>,
+    ) -> u64 {
+        let mut state = self.state.lock();
+        state.last_seq += 1;
+
+        let event = ChangeEvent {
+            epoch: self.epoch.clone(),
+            seq: state.last_seq,
+            timestamp: Utc::now(),
+            db: db.to_string(),
+            collection: collection.to_string(),
+            target,
+            op,
+            id,
+            document,
+        };
+
+        state
+            .subscribers
+            .retain(|_, subscriber| !subscriber.filter.matches(&event) || (subscriber.sink)(&event));
+
+        state.history.push_back(event);
+        if state.history.len() > self.capacity {
+            state.history.pop_front();
+        }
+        state.last_seq
+    }
+
+    /// Register a sink. With `resume_after` set to the last event the client
+    /// saw, the events it missed are replayed to the sink before this
+    /// returns, so nothing is delivered twice or skipped.
+    pub fn subscribe(
+        &self,
+        filter: ChangeFilter,
+        resume_after: Option<ResumePoint>,
+        sink: impl Fn(&ChangeEvent) -> bool + Send + Sync + 'static,
+    ) -> Subscription {
+        let mut state = self.state.lock();
+
+        let mut missed = false;
+        if let Some(ResumePoint { epoch, seq: after }) = resume_after {
+            let oldest = state.history.front().map_or(state.last_seq + 1, |event| event.seq);
+            missed = epoch != self.epoch || after > state.last_seq || after + 1 < oldest;
+
+            if !missed {
+                let replay = state
+                    .history
+                    .iter()
+                    .filter(|event| event.seq > after && filter.matches(event));
+                for event in replay {
+                    if !sink(event) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let id = state.next_subscriber;
+        state.next_subscriber += 1;
+        state.subscribers.insert(id, Subscriber { filter, sink: Box::new(sink) });
+
+        Subscription {
+            id,
+            epoch: self.epoch.clone(),
+            seq: state.last_seq,
+            missed,
+        }
+    }
+
+    /// Returns whether the subscription existed
+    pub fn unsubscribe(&self, id: u64) -> bool {
+        self.state.lock().subscribers.remove(&id).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn collect(feed: &ChangeFeed, filter: ChangeFilter, after: Option<u64>) -> (Subscription, Arc<Mutex<Vec<u64>>>) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let after = after.map(|seq| ResumePoint {
+            epoch: feed.epoch().to_string(),
+            seq,
+        });
+        let subscription = feed.subscribe(filter, after, move |event| {
+            sink.lock().push(event.seq);
+            true
+        });
+        (subscription, seen)
+    }
+
+    #[test]
+    fn test_filters_and_resume() {
+        let feed = ChangeFeed::new(3);
+        let filter = ChangeFilter {
+            db: Some("a.db".to_string()),
+            collection: Some("users".to_string()),
+        };
+        let (live, seen) = collect(&feed, filter.clone(), None);
+        assert_eq!(live.seq, 0);
+
+        feed.publish("a.db", "users", ChangeTarget::Document, ChangeOp::Insert, Some("1".into()), None);
+        feed.publish("a.db", "posts", ChangeTarget::Document, ChangeOp::Insert, Some("2".into()), None);
+        feed.publish("b.db", "users", ChangeTarget::Vector, ChangeOp::Delete, Some("3".into()), None);
+        feed.publish("a.db", "users", ChangeTarget::Document, ChangeOp::Drop, None, None);
+        assert_eq!(*seen.lock(), vec![1, 4]);
+
+        // Events 2..=4 are still buffered
+        let (resumed, replayed) = collect(&feed, filter.clone(), Some(1));
+        assert!(!resumed.missed);
+        assert_eq!(resumed.seq, 4);
+        assert_eq!(*replayed.lock(), vec![4]);
+
+        // Event 1 has been evicted, and event 9 was never published
+        assert!(collect(&feed, filter.clone(), Some(0)).0.missed);
+        assert!(collect(&feed, filter.clone(), Some(9)).0.missed);
+
+        // A sequence number from another run is never replayed, even when the
+        // current run has reached it
+        let restarted = ChangeFeed::new(3);
+        assert_ne!(restarted.epoch(), feed.epoch());
+        let other_run = ResumePoint {
+            epoch: restarted.epoch().to_string(),
+            seq: 3,
+        };
+        assert!(feed.subscribe(filter, Some(other_run), |_| true).missed);
+    }
+
+    #[test]
+    fn test_sink_returning_false_unsubscribes() {
+        let feed = ChangeFeed::default();
+        let subscription = feed.subscribe(ChangeFilter::default(), None, |_| false);
+
+        feed.publish("a.db", "users", ChangeTarget::Document, ChangeOp::Delete, Some("1".into()), None);
+        assert!(!feed.unsubscribe(subscription.id));
+    }
+}
@@ -0,0 +1,247 @@
+//! Connection profiles: the user-editable alias, color, favorite flag, notes
+//! and folder stored with each registered database, and the sorting and
+//! filtering applied when listing connections.
+
+use crate::system_db::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+
+const MAX_NOTES_LEN: usize = 4096;
+const MAX_FOLDER_LEN: usize = 256;
+
+/// Changes to a connection profile. Omitted fields are left as they are; an
+/// empty string clears the field.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConnectionProfileUpdate {
+    /// URL-safe name usable in place of the id
+    pub alias: Option<String>,
+    /// `#rgb`, `#rrggbb` or a lowercase color name such as `red`
+    pub color: Option<String>,
+    pub favorite: Option<bool>,
+    pub notes: Option<String>,
+    /// Grouping folder; `/` separates nested folders, e.g. `clients/acme`
+    pub folder: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionSort {
+    #[default]
+    LastAccessed,
+    CreatedAt,
+    AccessCount,
+    /// Alias, falling back to the path
+    Name,
+    Path,
+    /// Folder, then name; connections without a folder come last
+    Folder,
+}
+
+/// Sorting and filtering for the connection list; by default favorites come
+/// first, then the most recently used
+#[derive(Debug, Clone, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct ConnectionQuery {
+    pub sort: Option<ConnectionSort>,
+    /// Reverse the sort order; defaults to descending for dates and counts
+    pub desc: Option<bool>,
+    /// Keep favorites at the top regardless of the sort; defaults to true
+    pub favorites_first: Option<bool>,
+    pub favorite: Option<bool>,
+    pub color: Option<String>,
+    /// Connections in this folder or one of its subfolders
+    pub folder: Option<String>,
+    /// Case-insensitive match on path, alias and notes
+    pub search: Option<String>,
+}
+
+impl ConnectionQuery {
+    pub fn matches(&self, conn: &DatabaseConnection) -> bool {
+        let folder_matches = |folder: &str| {
+            conn.folder.as_deref().is_some_and(|f| {
+                f == folder || f.strip_prefix(folder).is_some_and(|rest| rest.starts_with('/'))
+            })
+        };
+        let search_matches = |search: &str| {
+            let search = search.to_lowercase();
+            [Some(conn.path.as_str()), conn.alias.as_deref(), conn.notes.as_deref()]
+                .into_iter()
+                .flatten()
+                .any(|text| text.to_lowercase().contains(&search))
+        };
+
+        self.favorite.is_none_or(|favorite| conn.favorite == favorite)
+            && self.color.as_ref().is_none_or(|color| conn.color.as_ref() == Some(color))
+            && self.folder.as_deref().is_none_or(folder_matches)
+            && self.search.as_deref().is_none_or(search_matches)
+    }
+
+    /// Filter and sort `connections` in place
+    pub fn apply(&self, connections: &mut Vec<DatabaseConnection>) {
+        connections.retain(|conn| self.matches(conn));
+
+        let sort = self.sort.unwrap_or_default();
+        let desc = self.desc.unwrap_or(matches!(
+            sort,
+            ConnectionSort::LastAccessed | ConnectionSort::CreatedAt | ConnectionSort::AccessCount
+        ));
+        let name = |conn: &DatabaseConnection| conn.alias.as_deref().unwrap_or(&conn.path).to_lowercase();
+
+        connections.sort_by(|a, b| {
+            let order = match sort {
+                ConnectionSort::LastAccessed => a.last_accessed.cmp(&b.last_accessed),
+                ConnectionSort::CreatedAt => a.created_at.cmp(&b.created_at),
+                ConnectionSort::AccessCount => a.access_count.cmp(&b.access_count),
+                ConnectionSort::Name => name(a).cmp(&name(b)),
+                ConnectionSort::Path => a.path.cmp(&b.path),
+                ConnectionSort::Folder => {
+                    (a.folder.is_none(), &a.folder, name(a)).cmp(&(b.folder.is_none(), &b.folder, name(b)))
+                }
+            };
+            if desc {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+
+        if self.favorites_first.unwrap_or(true) {
+            // Stable, so the order within each group is kept
+            connections.sort_by_key(|conn| !conn.favorite);
+        }
+    }
+}
+
+/// Apply `update` to `conn`, except the alias which needs a uniqueness check
+pub fn apply_profile_update(conn: &mut DatabaseConnection, update: &ConnectionProfileUpdate) -> Result<(), String> {
+    if let Some(color) = &update.color {
+        if !color.is_empty() && !is_valid_color(color) {
+            return Err(format!("Invalid color '{}': use #rgb, #rrggbb or a lowercase color name", color));
+        }
+        conn.color = non_empty(color);
+    }
+    if let Some(notes) = &update.notes {
+        if notes.chars().count() > MAX_NOTES_LEN {
+            return Err(format!("Notes are limited to {} characters", MAX_NOTES_LEN));
+        }
+        conn.notes = non_empty(notes);
+    }
+    if let Some(folder) = &update.folder {
+        let folder = folder.trim_matches('/');
+        let empty_segment = !folder.is_empty() && folder.split('/').any(|segment| segment.trim().is_empty());
+        if folder.len() > MAX_FOLDER_LEN || empty_segment {
+            return Err(format!("Invalid folder '{}': use non-empty names separated by '/'", folder));
+        }
+        conn.folder = non_empty(folder);
+    }
+    if let Some(favorite) = update.favorite {
+        conn.favorite = favorite;
+    }
+    Ok(())
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn is_valid_color(color: &str) -> bool {
+    match color.strip_prefix('#') {
+        Some(hex) => matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => color.len() <= 32 && color.chars().all(|c| c.is_ascii_lowercase()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn connection(path: &str, minutes_ago: i64) -> DatabaseConnection {
+        let at = Utc::now() - Duration::minutes(minutes_ago);
+        DatabaseConnection {
+            id: path.to_string(),
+            path: path.to_string(),
+            alias: None,
+            created_at: at,
+            last_accessed: at,
+            access_count: 1,
+            collections_count: 0,
+            total_documents: 0,
+            color: None,
+            favorite: false,
+            notes: None,
+            folder: None,
+        }
+    }
+
+    fn paths(connections: &[DatabaseConnection]) -> Vec<&str> {
+        connections.iter().map(|c| c.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_profile_update_validates_and_clears() {
+        let mut conn = connection("a.db", 0);
+        let update = ConnectionProfileUpdate {
+            color: Some("#1e90ff".to_string()),
+            favorite: Some(true),
+            notes: Some("Staging copy".to_string()),
+            folder: Some("/clients/acme/".to_string()),
+            ..Default::default()
+        };
+        apply_profile_update(&mut conn, &update).unwrap();
+        assert_eq!(conn.folder.as_deref(), Some("clients/acme"));
+        assert!(conn.favorite);
+
+        let clear = ConnectionProfileUpdate {
+            color: Some(String::new()),
+            folder: Some(String::new()),
+            ..Default::default()
+        };
+        apply_profile_update(&mut conn, &clear).unwrap();
+        assert_eq!((conn.color, conn.folder), (None, None));
+        assert_eq!(conn.notes.as_deref(), Some("Staging copy"));
+
+        for bad in [
+            ConnectionProfileUpdate { color: Some("#12345".to_string()), ..Default::default() },
+            ConnectionProfileUpdate { color: Some("Red".to_string()), ..Default::default() },
+            ConnectionProfileUpdate { folder: Some("a//b".to_string()), ..Default::default() },
+        ] {
+            assert!(apply_profile_update(&mut connection("b.db", 0), &bad).is_err());
+        }
+    }
+
+    #[test]
+    fn test_sort_and_filter() {
+        let mut a = connection("a.db", 30);
+        a.folder = Some("clients/acme".to_string());
+        let mut b = connection("b.db", 10);
+        b.folder = Some("clients".to_string());
+        b.notes = Some("Nightly IMPORT".to_string());
+        let mut c = connection("c.db", 20);
+        c.favorite = true;
+        c.folder = Some("clientsx".to_string());
+        let all = vec![a, b, c];
+
+        let mut list = all.clone();
+        ConnectionQuery::default().apply(&mut list);
+        assert_eq!(paths(&list), vec!["c.db", "b.db", "a.db"]);
+
+        let mut list = all.clone();
+        let by_path = ConnectionQuery {
+            sort: Some(ConnectionSort::Path),
+            favorites_first: Some(false),
+            ..Default::default()
+        };
+        by_path.apply(&mut list);
+        assert_eq!(paths(&list), vec!["a.db", "b.db", "c.db"]);
+
+        let mut list = all.clone();
+        ConnectionQuery { folder: Some("clients".to_string()), ..Default::default() }.apply(&mut list);
+        assert_eq!(paths(&list), vec!["b.db", "a.db"]);
+
+        let mut list = all;
+        ConnectionQuery { search: Some("import".to_string()), ..Default::default() }.apply(&mut list);
+        assert_eq!(paths(&list), vec!["b.db"]);
+    }
+}
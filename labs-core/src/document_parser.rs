@@ -0,0 +1,1698 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use mail_parser::mailbox::mbox::MessageIterator;
+use mail_parser::{Address, Message, MessageParser, MimeHeaders, PartType};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+#[derive(Debug, Default, Serialize)]
+pub struct ParsedDocument {
+    pub text: String,
+    pub pages: usize,
+    pub file_type: String,
+    /// Same as `metadata.title`, kept at the top level for existing clients
+    pub title: Option<String>,
+    /// Character encoding the source bytes were decoded from (text formats only)
+    pub encoding: Option<String>,
+    /// Confidence in `encoding`: 1.0 when it comes from a byte order mark, a
+    /// declared charset or the bytes being valid UTF-8, 0.5 when statistical
+    /// detection clearly preferred it; absent when it is only a guess
+    pub encoding_confidence: Option<f32>,
+    pub metadata: DocumentMetadata,
+    /// Nested units parsed separately, e.g. the messages of a mailbox or the
+    /// attachments of an email
+    pub parts: Vec<ParsedDocument>,
+}
+
+/// Descriptive metadata extracted alongside the text. Serialized flat so it can
+/// be stored as vector metadata as-is.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct DocumentMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<DateTime<Utc>>,
+    /// BCP 47 language tag, e.g. `en-US` when declared by the document or
+    /// `en` when detected from the text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    pub word_count: usize,
+    /// Hex-encoded SHA-256 of the source bytes
+    pub content_hash: String,
+    /// Format-specific fields, e.g. email headers or an attachment's filename
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, String>,
+}
+
+/// Parse a PDF file and extract text
+pub fn parse_pdf(data: &[u8]) -> Result<ParsedDocument, String> {
+    match pdf_extract::extract_text_from_mem(data) {
+        Ok(text) => {
+            let cleaned = clean_text(&text);
+            let pages = count_pages(&cleaned);
+            Ok(ParsedDocument {
+                text: cleaned,
+                pages,
+                file_type: "pdf".to_string(),
+                metadata: read_pdf_info(data),
+                ..Default::default()
+            })
+        }
+        Err(e) => Err(format!("Failed to parse PDF: {}", e)),
+    }
+}
+
+/// Parse a DOCX file and extract text
+pub fn parse_docx(data: &[u8], budget: &mut ArchiveBudget) -> Result<ParsedDocument, String> {
+    let cursor = Cursor::new(data);
+    let mut archive = ZipArchive::new(cursor)
+        .map_err(|e| format!("Failed to open DOCX as ZIP: {}", e))?;
+
+    let mut text_content = String::new();
+
+    // DOCX stores content in word/document.xml
+    if let Ok(file) = archive.by_name("word/document.xml") {
+        let xml_content = budget.read_entry("word/document.xml", file)?;
+
+        // Extract text from <w:t> tags
+        text_content = extract_text_from_xml(&String::from_utf8_lossy(&xml_content), "w:t");
+    }
+
+    let cleaned = clean_text(&text_content);
+    Ok(ParsedDocument {
+        text: cleaned,
+        pages: 1, // DOCX doesn't have clear page boundaries in the XML
+        file_type: "docx".to_string(),
+        metadata: read_office_core_properties(&mut archive, budget)?,
+        ..Default::default()
+    })
+}
+
+/// Parse an Excel file (XLSX) and extract text
+pub fn parse_xlsx(data: &[u8], budget: &mut ArchiveBudget) -> Result<ParsedDocument, String> {
+    use calamine::{Reader, Xlsx};
+
+    // calamine decompresses on its own, so run every entry past the budget first
+    let mut archive = ZipArchive::new(Cursor::new(data))
+        .map_err(|e| format!("Failed to open XLSX as ZIP: {}", e))?;
+    for i in 0..archive.len() {
+        let file = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read XLSX entry {}: {}", i, e))?;
+        if file.is_file() {
+            let name = file.name().to_string();
+            budget.skim_entry(&name, file)?;
+        }
+    }
+
+    let cursor = Cursor::new(data);
+    let mut workbook: Xlsx<_> = Xlsx::new(cursor)
+        .map_err(|e| format!("Failed to open XLSX: {}", e))?;
+
+    let mut all_text = Vec::new();
+
+    for sheet_name in workbook.sheet_names().to_vec() {
+        if let Ok(range) = workbook.worksheet_range(&sheet_name) {
+            let mut sheet_text = format!("[Sheet: {}]\n", sheet_name);
+            
+            for row in range.rows() {
+                let row_text: Vec<String> = row
+                    .iter()
+                    .map(|cell| cell.to_string())
+                    .collect();
+                sheet_text.push_str(&row_text.join("\t"));
+                sheet_text.push('\n');
+            }
+            
+            all_text.push(sheet_text);
+        }
+    }
+
+    let text = all_text.join("\n");
+    let cleaned = clean_text(&text);
+
+    // calamine doesn't expose document properties, so read them from the ZIP directly
+    let metadata = read_office_core_properties(&mut archive, budget)?;
+
+    Ok(ParsedDocument {
+        text: cleaned,
+        pages: all_text.len(),
+        file_type: "xlsx".to_string(),
+        metadata,
+        ..Default::default()
+    })
+}
+
+/// Parse a PowerPoint file (PPTX) and extract text
+pub fn parse_pptx(data: &[u8], budget: &mut ArchiveBudget) -> Result<ParsedDocument, String> {
+    let cursor = Cursor::new(data);
+    let mut archive = ZipArchive::new(cursor)
+        .map_err(|e| format!("Failed to open PPTX as ZIP: {}", e))?;
+
+    let mut slides: Vec<(usize, String)> = Vec::new();
+
+    // Find all slide XML files
+    let file_names: Vec<String> = (0..archive.len())
+        .filter_map(|i| archive.by_index(i).ok().map(|f| f.name().to_string()))
+        .filter(|name| name.starts_with("ppt/slides/slide") && name.ends_with(".xml"))
+        .collect();
+
+    for file_name in file_names {
+        // Extract slide number from filename
+        let slide_num = file_name
+            .trim_start_matches("ppt/slides/slide")
+            .trim_end_matches(".xml")
+            .parse::<usize>()
+            .unwrap_or(0);
+
+        if let Ok(file) = archive.by_name(&file_name) {
+            let xml_content = budget.read_entry(&file_name, file)?;
+            let text = extract_text_from_xml(&String::from_utf8_lossy(&xml_content), "a:t");
+            if !text.trim().is_empty() {
+                slides.push((slide_num, text));
+            }
+        }
+    }
+
+    // Sort by slide number
+    slides.sort_by_key(|(num, _)| *num);
+
+    let text = slides
+        .iter()
+        .map(|(num, text)| format!("[Slide {}]\n{}", num, text))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let cleaned = clean_text(&text);
+    let page_count = slides.len();
+
+    Ok(ParsedDocument {
+        text: cleaned,
+        pages: page_count,
+        file_type: "pptx".to_string(),
+        metadata: read_office_core_properties(&mut archive, budget)?,
+        ..Default::default()
+    })
+}
+
+/// Parse an HTML page, dropping markup, scripts and styles
+pub fn parse_html(data: &[u8]) -> Result<ParsedDocument, String> {
+    let decoded = decode_text_as(data, declared_html_charset(data));
+    let (metadata, text) = extract_text_from_html(&decoded.text);
+
+    Ok(ParsedDocument {
+        text: clean_text(&text),
+        pages: 1,
+        file_type: "html".to_string(),
+        metadata,
+        encoding: Some(decoded.encoding.to_string()),
+        encoding_confidence: decoded.confidence,
+        ..Default::default()
+    })
+}
+
+/// Parse an EPUB ebook, reading its chapters in spine order
+pub fn parse_epub(data: &[u8], budget: &mut ArchiveBudget) -> Result<ParsedDocument, String> {
+    let cursor = Cursor::new(data);
+    let mut archive = ZipArchive::new(cursor)
+        .map_err(|e| format!("Failed to open EPUB as ZIP: {}", e))?;
+
+    // META-INF/container.xml points at the OPF package document
+    let container = read_zip_entry(&mut archive, "META-INF/container.xml", budget)?;
+    let opf_path = find_xml_attribute(&container, b"rootfile", b"full-path")
+        .ok_or("EPUB container.xml has no rootfile")?;
+    let opf = read_zip_entry(&mut archive, &opf_path, budget)?;
+    let package = parse_opf(&opf)?;
+
+    // Manifest hrefs are relative to the directory holding the OPF
+    let base_dir = match opf_path.rfind('/') {
+        Some(idx) => &opf_path[..=idx],
+        None => "",
+    };
+
+    let mut chapters = Vec::new();
+    let mut metadata = parse_core_properties(&opf);
+
+    for idref in &package.spine {
+        let href = match package.manifest.get(idref) {
+            Some(href) => href,
+            None => {
+                log::warn!("EPUB spine references unknown item: {}", idref);
+                continue;
+            }
+        };
+
+        let entry_path = resolve_epub_href(base_dir, href);
+        let xhtml = match read_zip_entry(&mut archive, &entry_path, budget) {
+            Ok(xhtml) => xhtml,
+            Err(e) if budget.exceeded() => return Err(e),
+            Err(e) => {
+                log::warn!("Skipping EPUB chapter {}: {}", entry_path, e);
+                continue;
+            }
+        };
+
+        let (chapter_metadata, text) = extract_text_from_html(&xhtml);
+        if metadata.title.is_none() {
+            metadata.title = chapter_metadata.title;
+        }
+        if !text.trim().is_empty() {
+            chapters.push(text);
+        }
+    }
+
+    let text = chapters.join("\n\n");
+    let cleaned = clean_text(&text);
+
+    Ok(ParsedDocument {
+        text: cleaned,
+        pages: chapters.len(),
+        file_type: "epub".to_string(),
+        metadata,
+        ..Default::default()
+    })
+}
+
+/// Parse a single RFC 822 email message
+pub fn parse_eml(data: &[u8], budget: &mut ArchiveBudget) -> Result<ParsedDocument, String> {
+    let message = MessageParser::default()
+        .parse(data)
+        .ok_or("Failed to parse email message")?;
+
+    parse_email_message(&message, budget)
+}
+
+/// Parse an mbox mailbox, producing one part per message
+pub fn parse_mbox(data: &[u8], budget: &mut ArchiveBudget) -> Result<ParsedDocument, String> {
+    let parser = MessageParser::default();
+    let mut messages = Vec::new();
+
+    for (index, entry) in MessageIterator::new(Cursor::new(data)).enumerate() {
+        let entry = entry.map_err(|_| format!("Failed to read message {} from mbox", index + 1))?;
+        match parser.parse(entry.contents()) {
+            Some(message) => {
+                let mut parsed = parse_email_message(&message, budget)?;
+                fill_common_metadata(&mut parsed, entry.contents());
+                messages.push(parsed);
+            }
+            None => log::warn!("Skipping unparseable message {} in mbox", index + 1),
+        }
+    }
+
+    if messages.is_empty() {
+        return Err("No messages found in mbox".to_string());
+    }
+
+    let text = messages
+        .iter()
+        .map(|m| m.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let mut metadata = DocumentMetadata::default();
+    metadata
+        .extra
+        .insert("message_count".to_string(), messages.len().to_string());
+
+    Ok(ParsedDocument {
+        text,
+        pages: messages.len(),
+        file_type: "mbox".to_string(),
+        metadata,
+        parts: messages,
+        ..Default::default()
+    })
+}
+
+/// Deepest chain of emails attached to emails that is parsed
+const MAX_EMAIL_DEPTH: usize = 8;
+
+/// Guards applied while walking an archive and the containers nested in it,
+/// such as Office documents, EPUBs and email attachments, to reject zip bombs
+#[derive(Debug, Clone, Copy)]
+pub struct ArchiveLimits {
+    /// Maximum number of file entries read, nested ones included
+    pub max_entries: usize,
+    /// Maximum total size of all entries once decompressed, nested ones included
+    pub max_total_bytes: u64,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 1_000,
+            max_total_bytes: 512 * 1024 * 1024,
+        }
+    }
+}
+
+/// Result of parsing a single file inside an archive
+#[derive(Debug)]
+pub struct ArchiveEntry {
+    pub path: String,
+    pub result: Result<ParsedDocument, String>,
+}
+
+/// Check whether a filename is an archive handled by `parse_archive`
+pub fn is_archive(filename: &str) -> bool {
+    let lower = filename.to_lowercase();
+    lower.ends_with(".zip") || lower.ends_with(".tar.gz") || lower.ends_with(".tgz")
+}
+
+/// Walk a ZIP or tar.gz archive and parse every file it contains
+pub fn parse_archive(
+    data: &[u8],
+    filename: &str,
+    limits: ArchiveLimits,
+) -> Result<Vec<ArchiveEntry>, String> {
+    parse_archive_with_progress(data, filename, limits, |_, _| {})
+}
+
+/// Like `parse_archive`, calling `on_entry(parsed, total)` after each file
+pub fn parse_archive_with_progress(
+    data: &[u8],
+    filename: &str,
+    limits: ArchiveLimits,
+    mut on_entry: impl FnMut(usize, usize),
+) -> Result<Vec<ArchiveEntry>, String> {
+    let lower = filename.to_lowercase();
+    let mut budget = ArchiveBudget::new(limits);
+    let files = if lower.ends_with(".zip") {
+        read_zip_files(data, &mut budget)?
+    } else if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") {
+        read_tar_gz_files(data, &mut budget)?
+    } else {
+        return Err(format!("Unsupported archive type: {}", filename));
+    };
+
+    let total = files.len();
+    let mut entries = Vec::with_capacity(total);
+    for (index, (path, contents)) in files.into_iter().enumerate() {
+        // Entries that fail on their own are reported; a blown budget fails the archive
+        let result = match parse_with_budget(&contents, &path, &mut budget) {
+            Err(e) if budget.exceeded() => return Err(e),
+            result => result,
+        };
+        on_entry(index + 1, total);
+        entries.push(ArchiveEntry { path, result });
+    }
+    Ok(entries)
+}
+
+/// Parse any supported document type
+pub fn parse_document(data: &[u8], filename: &str) -> Result<ParsedDocument, String> {
+    parse_document_with_limits(data, filename, ArchiveLimits::default())
+}
+
+/// Like `parse_document`, with `limits` bounding what containers such as
+/// Office documents, EPUBs and emails may unpack
+pub fn parse_document_with_limits(
+    data: &[u8],
+    filename: &str,
+    limits: ArchiveLimits,
+) -> Result<ParsedDocument, String> {
+    parse_with_budget(data, filename, &mut ArchiveBudget::new(limits))
+}
+
+fn parse_with_budget(data: &[u8], filename: &str, budget: &mut ArchiveBudget) -> Result<ParsedDocument, String> {
+    let mut parsed = parse_by_extension(data, filename, budget)?;
+    fill_common_metadata(&mut parsed, data);
+    Ok(parsed)
+}
+
+fn parse_by_extension(data: &[u8], filename: &str, budget: &mut ArchiveBudget) -> Result<ParsedDocument, String> {
+    let ext = filename
+        .rsplit('.')
+        .next()
+        .unwrap_or("")
+        .to_lowercase();
+
+    match ext.as_str() {
+        "pdf" => parse_pdf(data),
+        "docx" | "doc" => parse_docx(data, budget),
+        "xlsx" | "xls" => parse_xlsx(data, budget),
+        "pptx" | "ppt" => parse_pptx(data, budget),
+        "html" | "htm" | "xhtml" => parse_html(data),
+        "epub" => parse_epub(data, budget),
+        "eml" => parse_eml(data, budget),
+        "mbox" => parse_mbox(data, budget),
+        // Text-based files - just convert to string
+        "txt" | "md" | "markdown" | "json" | "csv" | "xml" | "yaml" | "yml" 
+        | "css" | "js" | "ts" | "jsx" | "tsx" | "py" | "rs" 
+        | "go" | "java" | "c" | "cpp" | "h" | "hpp" | "cs" | "rb" | "php" 
+        | "swift" | "kt" | "scala" | "r" | "sql" | "sh" | "bash" | "ps1"
+        | "vue" | "svelte" | "toml" | "ini" | "env" | "log" => {
+            let decoded = decode_text(data);
+            Ok(ParsedDocument {
+                text: clean_text(&decoded.text),
+                pages: 1,
+                file_type: ext,
+                encoding: Some(decoded.encoding.to_string()),
+                encoding_confidence: decoded.confidence,
+                ..Default::default()
+            })
+        }
+        _ => Err(format!("Unsupported file type: {}", ext)),
+    }
+}
+
+/// Text decoded from raw bytes along with the encoding that was detected
+#[derive(Debug)]
+pub struct DecodedText {
+    pub text: String,
+    pub encoding: &'static str,
+    /// See `ParsedDocument::encoding_confidence`
+    pub confidence: Option<f32>,
+}
+
+/// Detect the character encoding of `data` and transcode it to UTF-8.
+///
+/// A byte order mark wins outright. Otherwise NUL-byte patterns identify
+/// BOM-less UTF-16 (which is also valid UTF-8 when mostly ASCII), valid UTF-8
+/// is taken as-is, and anything else goes through statistical detection.
+pub fn decode_text(data: &[u8]) -> DecodedText {
+    decode_text_as(data, None)
+}
+
+// Like `decode_text`, with a charset the document declares taking precedence
+// over everything but a byte order mark
+fn decode_text_as(data: &[u8], declared: Option<&'static Encoding>) -> DecodedText {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(data) {
+        let (text, _) = encoding.decode_without_bom_handling(&data[bom_len..]);
+        return DecodedText {
+            text: text.into_owned(),
+            encoding: encoding.name(),
+            confidence: Some(1.0),
+        };
+    }
+
+    if let Some(encoding) = declared {
+        let (text, had_errors) = encoding.decode_without_bom_handling(data);
+        return DecodedText {
+            text: text.into_owned(),
+            encoding: encoding.name(),
+            confidence: (!had_errors).then_some(1.0),
+        };
+    }
+
+    if let Some(encoding) = detect_utf16_without_bom(data) {
+        let (text, _) = encoding.decode_without_bom_handling(data);
+        return DecodedText {
+            text: text.into_owned(),
+            encoding: encoding.name(),
+            confidence: None,
+        };
+    }
+
+    if let Ok(text) = std::str::from_utf8(data) {
+        return DecodedText {
+            text: text.to_string(),
+            encoding: UTF_8.name(),
+            confidence: Some(1.0),
+        };
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(data, true);
+    // `assessed` is false when no other candidate scored lower, i.e. the
+    // guess is likely wrong
+    let (encoding, assessed) = detector.guess_assess(None, true);
+    let (text, had_errors) = encoding.decode_without_bom_handling(data);
+
+    DecodedText {
+        text: text.into_owned(),
+        encoding: encoding.name(),
+        confidence: (assessed && !had_errors).then_some(0.5),
+    }
+}
+
+/// Charset declared by a `<meta charset>` or `<meta http-equiv>` tag within
+/// the first 1024 bytes, the range browsers prescan. As in browsers, a
+/// declared UTF-16 is read as UTF-8 since the bytes would have had a BOM.
+fn declared_html_charset(data: &[u8]) -> Option<&'static Encoding> {
+    let head = String::from_utf8_lossy(&data[..data.len().min(1024)]).to_ascii_lowercase();
+    let mut rest = head.as_str();
+    while let Some(start) = rest.find("<meta") {
+        rest = &rest[start + "<meta".len()..];
+        let tag = &rest[..rest.find('>').unwrap_or(rest.len())];
+        let Some(at) = tag.find("charset") else {
+            continue;
+        };
+        let value = tag[at + "charset".len()..].trim_start();
+        let Some(value) = value.strip_prefix('=') else {
+            continue;
+        };
+        let label: String = value
+            .trim_start()
+            .trim_start_matches(['"', '\''])
+            .chars()
+            .take_while(|c| !matches!(c, '"' | '\'' | ';' | '/' | '>') && !c.is_whitespace())
+            .collect();
+        if let Some(encoding) = Encoding::for_label_no_replacement(label.as_bytes()) {
+            return Some(encoding.output_encoding());
+        }
+    }
+    None
+}
+
+/// UTF-16 text that is mostly ASCII has a NUL in every other byte
+fn detect_utf16_without_bom(data: &[u8]) -> Option<&'static Encoding> {
+    let sample = &data[..data.len().min(4096)];
+    let pairs = sample.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+
+    let even_nuls = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd_nuls = sample.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+
+    if odd_nuls * 10 >= pairs * 4 && even_nuls * 20 < pairs {
+        Some(UTF_16LE)
+    } else if even_nuls * 10 >= pairs * 4 && odd_nuls * 20 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Extract text content from XML tags
+fn extract_text_from_xml(xml: &str, tag: &str) -> String {
+    let open_tag = format!("<{}>", tag);
+    let close_tag = format!("</{}>", tag);
+    
+    let mut result = Vec::new();
+    let mut remaining = xml;
+    
+    while let Some(start) = remaining.find(&open_tag) {
+        remaining = &remaining[start + open_tag.len()..];
+        if let Some(end) = remaining.find(&close_tag) {
+            let text = &remaining[..end];
+            // Skip if it contains nested XML
+            if !text.contains('<') {
+                result.push(text.to_string());
+            }
+            remaining = &remaining[end + close_tag.len()..];
+        }
+    }
+    
+    result.join(" ")
+}
+
+/// Tracks entry count and decompressed size while extracting an archive.
+/// One budget covers a whole upload, containers nested in it included.
+pub struct ArchiveBudget {
+    limits: ArchiveLimits,
+    entries: usize,
+    total_bytes: u64,
+    /// Emails currently being parsed inside one another
+    email_depth: usize,
+}
+
+impl ArchiveBudget {
+    pub fn new(limits: ArchiveLimits) -> Self {
+        Self {
+            limits,
+            entries: 0,
+            total_bytes: 0,
+            email_depth: 0,
+        }
+    }
+
+    /// Read one entry, failing once the archive exceeds either limit. The size
+    /// is measured on the decompressed stream since headers can lie about it.
+    fn read_entry<R: Read>(&mut self, path: &str, reader: R) -> Result<Vec<u8>, String> {
+        let mut contents = Vec::new();
+        self.copy_entry(path, reader, &mut contents)?;
+        Ok(contents)
+    }
+
+    /// Count an entry against the limits without keeping it
+    fn skim_entry<R: Read>(&mut self, path: &str, reader: R) -> Result<(), String> {
+        self.copy_entry(path, reader, &mut std::io::sink())
+    }
+
+    fn copy_entry<R: Read>(&mut self, path: &str, reader: R, out: &mut impl std::io::Write) -> Result<(), String> {
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            return Err(format!(
+                "Archive contains more than {} files",
+                self.limits.max_entries
+            ));
+        }
+
+        let remaining = self.limits.max_total_bytes.saturating_sub(self.total_bytes);
+        let copied = std::io::copy(&mut reader.take(remaining + 1), out)
+            .map_err(|e| format!("Failed to read {} from archive: {}", path, e))?;
+
+        self.total_bytes += copied;
+        if self.total_bytes > self.limits.max_total_bytes {
+            return Err(format!(
+                "Archive exceeds the {} byte uncompressed size limit",
+                self.limits.max_total_bytes
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Whether a limit was hit; such errors fail the whole upload rather than
+    /// the entry that happened to cross it
+    fn exceeded(&self) -> bool {
+        self.entries > self.limits.max_entries || self.total_bytes > self.limits.max_total_bytes
+    }
+}
+
+/// Skip directories and OS metadata such as `__MACOSX/` or `.DS_Store`
+fn is_archive_junk(path: &str) -> bool {
+    path.ends_with('/')
+        || path.starts_with("__MACOSX/")
+        || path
+            .rsplit('/')
+            .next()
+            .map(|name| name.starts_with('.'))
+            .unwrap_or(false)
+}
+
+fn read_zip_files(data: &[u8], budget: &mut ArchiveBudget) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut archive = ZipArchive::new(Cursor::new(data))
+        .map_err(|e| format!("Failed to open ZIP archive: {}", e))?;
+    let mut files = Vec::new();
+
+    for i in 0..archive.len() {
+        let file = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read ZIP entry {}: {}", i, e))?;
+
+        if !file.is_file() {
+            continue;
+        }
+        // enclosed_name rejects absolute paths and `..` components
+        let path = match file.enclosed_name() {
+            Some(path) => path.to_string_lossy().replace('\\', "/"),
+            None => continue,
+        };
+        if is_archive_junk(&path) {
+            continue;
+        }
+
+        let contents = budget.read_entry(&path, file)?;
+        files.push((path, contents));
+    }
+
+    Ok(files)
+}
+
+fn read_tar_gz_files(data: &[u8], budget: &mut ArchiveBudget) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let decoder = flate2::read::GzDecoder::new(Cursor::new(data));
+    let mut archive = tar::Archive::new(decoder);
+    let mut files = Vec::new();
+
+    let entries = archive
+        .entries()
+        .map_err(|e| format!("Failed to open tar.gz archive: {}", e))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read tar entry: {}", e))?;
+
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry
+            .path()
+            .map_err(|e| format!("Invalid tar entry path: {}", e))?
+            .to_string_lossy()
+            .trim_start_matches("./")
+            .to_string();
+        if is_archive_junk(&path) {
+            continue;
+        }
+
+        let contents = budget.read_entry(&path, entry)?;
+        files.push((path, contents));
+    }
+
+    Ok(files)
+}
+
+/// Convert a decoded email into a parsed unit, with attachments as parts.
+/// Emails attached to emails, directly or as `.eml` files, are parsed up to
+/// `MAX_EMAIL_DEPTH` levels deep.
+fn parse_email_message(message: &Message, budget: &mut ArchiveBudget) -> Result<ParsedDocument, String> {
+    if budget.email_depth >= MAX_EMAIL_DEPTH {
+        return Err(format!("Email nested more than {} levels deep", MAX_EMAIL_DEPTH));
+    }
+    budget.email_depth += 1;
+    let parsed = read_email_message(message, budget);
+    budget.email_depth -= 1;
+    parsed
+}
+
+fn read_email_message(message: &Message, budget: &mut ArchiveBudget) -> Result<ParsedDocument, String> {
+    let mut metadata = DocumentMetadata {
+        title: message.subject().map(str::to_string),
+        author: message.from().map(format_address),
+        created: message
+            .date()
+            .and_then(|d| DateTime::parse_from_rfc3339(&d.to_rfc3339()).ok())
+            .map(|d| d.with_timezone(&Utc)),
+        ..Default::default()
+    };
+    let mut header_lines = Vec::new();
+
+    let headers = [
+        ("from", message.from().map(format_address)),
+        ("to", message.to().map(format_address)),
+        ("cc", message.cc().map(format_address)),
+        ("subject", message.subject().map(str::to_string)),
+        ("date", message.date().map(|d| d.to_rfc3339())),
+    ];
+
+    for (name, value) in headers {
+        if let Some(value) = value.filter(|v| !v.is_empty()) {
+            let mut label = name.to_string();
+            label[..1].make_ascii_uppercase();
+            header_lines.push(format!("{}: {}", label, value));
+            metadata.extra.insert(name.to_string(), value);
+        }
+    }
+
+    // text_body holds the text/plain parts when present, otherwise the HTML ones
+    let mut body = Vec::new();
+    for part_id in &message.text_body {
+        match message.part(*part_id).map(|part| &part.body) {
+            Some(PartType::Text(text)) => body.push(text.to_string()),
+            Some(PartType::Html(html)) => body.push(extract_text_from_html(html).1),
+            _ => {}
+        }
+    }
+
+    let mut parts = Vec::new();
+    for attachment in message.attachments() {
+        if let Some(nested) = attachment.message() {
+            match parse_email_message(nested, budget) {
+                Ok(mut parsed) => {
+                    fill_common_metadata(&mut parsed, nested.raw_message());
+                    parts.push(parsed);
+                }
+                Err(e) if budget.exceeded() => return Err(e),
+                Err(e) => log::warn!("Skipping attached email: {}", e),
+            }
+            continue;
+        }
+
+        let filename = match attachment.attachment_name() {
+            Some(name) => name.to_string(),
+            None if attachment.is_text_html() => "attachment.html".to_string(),
+            None if attachment.is_text() => "attachment.txt".to_string(),
+            None => continue,
+        };
+
+        match parse_with_budget(attachment.contents(), &filename, budget) {
+            Ok(mut parsed) => {
+                parsed.metadata.extra.insert("filename".to_string(), filename);
+                parts.push(parsed);
+            }
+            Err(e) if budget.exceeded() => return Err(e),
+            Err(e) => log::warn!("Skipping email attachment {}: {}", filename, e),
+        }
+    }
+
+    let text = format!("{}\n\n{}", header_lines.join("\n"), body.join("\n"));
+
+    Ok(ParsedDocument {
+        text: clean_text(&text),
+        pages: 1,
+        file_type: "eml".to_string(),
+        metadata,
+        parts,
+        ..Default::default()
+    })
+}
+
+/// Render an address header as a comma-separated `Name <address>` list
+fn format_address(address: &Address) -> String {
+    address
+        .iter()
+        .filter_map(|addr| match (&addr.name, &addr.address) {
+            (Some(name), Some(email)) => Some(format!("{} <{}>", name, email)),
+            (None, Some(email)) => Some(email.to_string()),
+            (Some(name), None) => Some(name.to_string()),
+            (None, None) => None,
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Elements whose contents are never part of the readable text
+const HTML_SKIPPED_TAGS: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "iframe", "object",
+];
+
+/// Elements that start a new line in the extracted text
+const HTML_BLOCK_TAGS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "dd", "div", "dl", "dt", "figcaption",
+    "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li",
+    "main", "nav", "ol", "p", "pre", "section", "table", "tr", "ul",
+];
+
+/// Extract the `<title>`, author and language along with the readable body
+/// text from an HTML document
+fn extract_text_from_html(html: &str) -> (DocumentMetadata, String) {
+    let document = Html::parse_document(html);
+
+    let select_first = |css: &str| {
+        Selector::parse(css)
+            .ok()
+            .and_then(|selector| document.select(&selector).next())
+    };
+
+    let metadata = DocumentMetadata {
+        title: select_first("title")
+            .map(|el| el.text().collect::<Vec<_>>().join(" "))
+            .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|t| !t.is_empty()),
+        author: select_first(r#"meta[name="author"]"#)
+            .and_then(|el| el.value().attr("content"))
+            .map(|a| a.trim().to_string())
+            .filter(|a| !a.is_empty()),
+        language: select_first("html[lang]")
+            .and_then(|el| el.value().attr("lang"))
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty()),
+        ..Default::default()
+    };
+
+    let mut text = String::new();
+    collect_html_text(document.root_element(), &mut text);
+
+    (metadata, text)
+}
+
+/// Walk an element tree, appending visible text and line breaks at block boundaries
+fn collect_html_text(element: ElementRef, out: &mut String) {
+    for child in element.children() {
+        if let Some(text) = child.value().as_text() {
+            push_collapsed_whitespace(out, text);
+        } else if let Some(child_el) = ElementRef::wrap(child) {
+            let tag = child_el.value().name();
+
+            if HTML_SKIPPED_TAGS.contains(&tag) {
+                continue;
+            }
+
+            if tag == "br" {
+                out.push('\n');
+                continue;
+            }
+
+            if tag == "pre" {
+                out.push('\n');
+                out.push_str(&child_el.text().collect::<String>());
+                out.push('\n');
+                continue;
+            }
+
+            let is_block = HTML_BLOCK_TAGS.contains(&tag);
+            if is_block {
+                out.push('\n');
+            } else if tag == "td" || tag == "th" {
+                out.push('\t');
+            }
+
+            collect_html_text(child_el, out);
+
+            if is_block {
+                out.push('\n');
+            }
+        }
+    }
+}
+
+/// Append text with runs of whitespace collapsed to a single space
+fn push_collapsed_whitespace(out: &mut String, text: &str) {
+    if text.starts_with(char::is_whitespace) && !out.ends_with(char::is_whitespace) {
+        out.push(' ');
+    }
+
+    out.push_str(&text.split_whitespace().collect::<Vec<_>>().join(" "));
+
+    if text.ends_with(char::is_whitespace) && !text.trim().is_empty() {
+        out.push(' ');
+    }
+}
+
+/// Contents of an EPUB OPF package document that matter for text extraction
+struct EpubPackage {
+    /// Manifest item id -> href
+    manifest: HashMap<String, String>,
+    /// Item ids in reading order
+    spine: Vec<String>,
+}
+
+fn parse_opf(xml: &str) -> Result<EpubPackage, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut package = EpubPackage {
+        manifest: HashMap::new(),
+        spine: Vec::new(),
+    };
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => match e.local_name().as_ref() {
+                b"item" => {
+                    let id = xml_attribute(&e, b"id");
+                    let href = xml_attribute(&e, b"href");
+                    if let (Some(id), Some(href)) = (id, href) {
+                        package.manifest.insert(id, href);
+                    }
+                }
+                b"itemref" => {
+                    if let Some(idref) = xml_attribute(&e, b"idref") {
+                        package.spine.push(idref);
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Failed to parse OPF: {}", e)),
+            _ => {}
+        }
+    }
+
+    Ok(package)
+}
+
+/// Read Dublin Core properties, as found in an Office `docProps/core.xml` or
+/// the `<metadata>` section of an EPUB package document. Malformed XML yields
+/// whatever was read before the error.
+fn parse_core_properties(xml: &str) -> DocumentMetadata {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut metadata = DocumentMetadata::default();
+    let mut current: Option<&'static str> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                current = match e.local_name().as_ref() {
+                    b"title" => Some("title"),
+                    b"creator" => Some("author"),
+                    b"language" => Some("language"),
+                    b"created" | b"date" => Some("created"),
+                    b"modified" => Some("modified"),
+                    // EPUB 3 records the modification date as <meta property="dcterms:modified">
+                    b"meta" if xml_attribute(&e, b"property").as_deref() == Some("dcterms:modified") => {
+                        Some("modified")
+                    }
+                    _ => None,
+                };
+            }
+            Ok(Event::Text(t)) => {
+                let value = match (current, t.unescape()) {
+                    (Some(_), Ok(value)) => value.trim().to_string(),
+                    _ => continue,
+                };
+                if value.is_empty() {
+                    continue;
+                }
+
+                match current {
+                    Some("title") if metadata.title.is_none() => metadata.title = Some(value),
+                    Some("author") if metadata.author.is_none() => metadata.author = Some(value),
+                    Some("language") if metadata.language.is_none() => metadata.language = Some(value),
+                    Some("created") if metadata.created.is_none() => metadata.created = parse_loose_date(&value),
+                    Some("modified") if metadata.modified.is_none() => metadata.modified = parse_loose_date(&value),
+                    _ => {}
+                }
+            }
+            Ok(Event::End(_)) => current = None,
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    metadata
+}
+
+fn read_office_core_properties<R: Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    budget: &mut ArchiveBudget,
+) -> Result<DocumentMetadata, String> {
+    match read_zip_entry(archive, "docProps/core.xml", budget) {
+        Ok(xml) => Ok(parse_core_properties(&xml)),
+        Err(e) if budget.exceeded() => Err(e),
+        Err(_) => Ok(DocumentMetadata::default()),
+    }
+}
+
+/// Accept full RFC 3339 timestamps as well as the bare `YYYY-MM-DD` and `YYYY`
+/// forms allowed by W3CDTF
+fn parse_loose_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(&format!("{}-01", value), "%Y-%m-%d"))
+        .or_else(|_| NaiveDate::parse_from_str(&format!("{}-01-01", value), "%Y-%m-%d"))
+        .ok()?;
+    Some(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0)?))
+}
+
+/// Read title, author and dates from a PDF's document information dictionary
+fn read_pdf_info(data: &[u8]) -> DocumentMetadata {
+    let mut metadata = DocumentMetadata::default();
+
+    let document = match lopdf::Document::load_mem(data) {
+        Ok(document) => document,
+        Err(e) => {
+            log::warn!("Failed to read PDF info dictionary: {}", e);
+            return metadata;
+        }
+    };
+    let info = match document
+        .trailer
+        .get_deref(b"Info", &document)
+        .and_then(|info| info.as_dict())
+    {
+        Ok(info) => info,
+        Err(_) => return metadata,
+    };
+
+    let field = |key: &[u8]| {
+        info.get_deref(key, &document)
+            .and_then(|value| value.as_str())
+            .ok()
+            .map(decode_pdf_string)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    metadata.title = field(b"Title");
+    metadata.author = field(b"Author");
+    metadata.created = field(b"CreationDate").and_then(|d| parse_pdf_date(&d));
+    metadata.modified = field(b"ModDate").and_then(|d| parse_pdf_date(&d));
+    metadata
+}
+
+/// PDF text strings are UTF-16BE with a BOM, or PDFDocEncoding (close enough to Latin-1)
+fn decode_pdf_string(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xfe, 0xff]) {
+        Some(utf16) => UTF_16BE.decode_without_bom_handling(utf16).0.into_owned(),
+        None => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// Parse a PDF date string such as `D:20240131094500+01'00'`
+fn parse_pdf_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.strip_prefix("D:").unwrap_or(value);
+    let digits: String = value.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.len() < 4 {
+        return None;
+    }
+
+    let part = |range: std::ops::Range<usize>, default: u32| {
+        digits.get(range).and_then(|p| p.parse().ok()).unwrap_or(default)
+    };
+    let year: i32 = digits[..4].parse().ok()?;
+    let local = NaiveDate::from_ymd_opt(year, part(4..6, 1), part(6..8, 1))?
+        .and_hms_opt(part(8..10, 0), part(10..12, 0), part(12..14, 0))?;
+
+    // Timezone: Z, or +HH'mm' / -HH'mm'; missing means UTC
+    let tz = &value[digits.len()..];
+    let offset_secs = match tz.chars().next() {
+        Some(sign @ ('+' | '-')) => {
+            let tz_digits: String = tz.chars().filter(|c| c.is_ascii_digit()).collect();
+            let hours: i32 = tz_digits.get(0..2).and_then(|h| h.parse().ok()).unwrap_or(0);
+            let minutes: i32 = tz_digits.get(2..4).and_then(|m| m.parse().ok()).unwrap_or(0);
+            let secs = hours * 3600 + minutes * 60;
+            if sign == '-' { -secs } else { secs }
+        }
+        _ => 0,
+    };
+
+    Some(Utc.from_utc_datetime(&(local - chrono::Duration::seconds(offset_secs as i64))))
+}
+
+/// Fill in the metadata every format shares: word count, content hash and,
+/// when the document doesn't declare one, a detected language
+fn fill_common_metadata(parsed: &mut ParsedDocument, source: &[u8]) {
+    parsed.metadata.word_count = parsed.text.split_whitespace().count();
+    parsed.metadata.content_hash = Sha256::digest(source)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    parsed.metadata.language = match parsed.metadata.language.take() {
+        Some(declared) => normalize_language_tag(&declared),
+        None => whatlang::detect(&parsed.text)
+            .filter(|info| info.is_reliable())
+            .map(|info| bcp47_language(info.lang().code()).to_string()),
+    };
+    parsed.title = parsed.metadata.title.clone();
+}
+
+/// Canonical casing of a declared BCP 47 tag (`en-us` → `en-US`, `zh_hant` →
+/// `zh-Hant`), with ISO 639-3 primary subtags shortened where ISO 639-1 has one
+fn normalize_language_tag(tag: &str) -> Option<String> {
+    let mut subtags = tag.trim().split(['-', '_']).filter(|s| !s.is_empty());
+    let primary = subtags.next()?.to_ascii_lowercase();
+    let mut normalized = bcp47_language(&primary).to_string();
+
+    for subtag in subtags {
+        normalized.push('-');
+        match subtag.len() {
+            // Script, e.g. `Latn`
+            4 if subtag.chars().all(|c| c.is_ascii_alphabetic()) => {
+                normalized.push_str(&subtag[..1].to_ascii_uppercase());
+                normalized.push_str(&subtag[1..].to_ascii_lowercase());
+            }
+            // Region, e.g. `US`
+            2 => normalized.push_str(&subtag.to_ascii_uppercase()),
+            _ => normalized.push_str(&subtag.to_ascii_lowercase()),
+        }
+    }
+    Some(normalized)
+}
+
+/// BCP 47 primary language subtag for an ISO 639-3 code: the ISO 639-1 code
+/// where there is one, the code itself otherwise. Covers the languages
+/// `whatlang` detects.
+fn bcp47_language(code: &str) -> &str {
+    match code {
+        "afr" => "af",
+        "aka" => "ak",
+        "amh" => "am",
+        "ara" => "ar",
+        "aze" => "az",
+        "bel" => "be",
+        "ben" => "bn",
+        "bul" => "bg",
+        "cat" => "ca",
+        "ces" => "cs",
+        "cmn" => "zh",
+        "dan" => "da",
+        "deu" => "de",
+        "ell" => "el",
+        "eng" => "en",
+        "epo" => "eo",
+        "est" => "et",
+        "fin" => "fi",
+        "fra" => "fr",
+        "guj" => "gu",
+        "heb" => "he",
+        "hin" => "hi",
+        "hrv" => "hr",
+        "hun" => "hu",
+        "hye" => "hy",
+        "ind" => "id",
+        "ita" => "it",
+        "jav" => "jv",
+        "jpn" => "ja",
+        "kan" => "kn",
+        "kat" => "ka",
+        "khm" => "km",
+        "kor" => "ko",
+        "lat" => "la",
+        "lav" => "lv",
+        "lit" => "lt",
+        "mal" => "ml",
+        "mar" => "mr",
+        "mkd" => "mk",
+        "mya" => "my",
+        "nep" => "ne",
+        "nld" => "nl",
+        "nob" => "nb",
+        "ori" => "or",
+        "pan" => "pa",
+        "pes" => "fa",
+        "pol" => "pl",
+        "por" => "pt",
+        "ron" => "ro",
+        "rus" => "ru",
+        "sin" => "si",
+        "slk" => "sk",
+        "slv" => "sl",
+        "sna" => "sn",
+        "spa" => "es",
+        "srp" => "sr",
+        "swe" => "sv",
+        "tam" => "ta",
+        "tel" => "te",
+        "tgl" => "tl",
+        "tha" => "th",
+        "tuk" => "tk",
+        "tur" => "tr",
+        "ukr" => "uk",
+        "urd" => "ur",
+        "uzb" => "uz",
+        "vie" => "vi",
+        "yid" => "yi",
+        "zul" => "zu",
+        other => other,
+    }
+}
+
+/// Find the first element with the given local name and return one of its attributes
+fn find_xml_attribute(xml: &str, element: &[u8], attribute: &[u8]) -> Option<String> {
+    let mut reader = Reader::from_str(xml);
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if e.local_name().as_ref() == element => {
+                return xml_attribute(&e, attribute);
+            }
+            Ok(Event::Eof) | Err(_) => return None,
+            _ => {}
+        }
+    }
+}
+
+fn xml_attribute(element: &quick_xml::events::BytesStart, name: &[u8]) -> Option<String> {
+    element
+        .try_get_attribute(name)
+        .ok()
+        .flatten()
+        .and_then(|attr| attr.unescape_value().ok().map(|v| v.into_owned()))
+}
+
+fn read_zip_entry<R: Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+    budget: &mut ArchiveBudget,
+) -> Result<String, String> {
+    let file = archive
+        .by_name(name)
+        .map_err(|e| format!("Missing {}: {}", name, e))?;
+    let bytes = budget.read_entry(name, file)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Resolve a manifest href against the OPF directory, dropping fragments and
+/// decoding percent-escapes so it matches the ZIP entry name
+fn resolve_epub_href(base_dir: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or(href);
+    let joined = format!("{}{}", base_dir, percent_decode(href));
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Clean extracted text
+fn clean_text(text: &str) -> String {
+    text
+        // Normalize line endings
+        .replace("\r\n", "\n")
+        .replace("\r", "\n")
+        // Remove excessive whitespace
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Count approximate pages based on content
+fn count_pages(text: &str) -> usize {
+    // Rough estimate: ~3000 chars per page
+    (text.len() / 3000).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn build_zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, content) in files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_clean_text() {
+        let input = "  Hello   World  \n\n\n  Test  ";
+        let result = clean_text(input);
+        assert_eq!(result, "Hello   World\nTest");
+    }
+
+    #[test]
+    fn test_extract_text_from_xml() {
+        let xml = "<root><a:t>Hello</a:t> <a:t>World</a:t></root>";
+        let result = extract_text_from_xml(xml, "a:t");
+        assert_eq!(result, "Hello World");
+    }
+
+    #[test]
+    fn test_parse_html_strips_markup() {
+        let html = r#"<html lang="en_us"><head><title> Release  Notes </title>
+            <style>body { color: red; }</style><script>alert("hi")</script></head>
+            <body><h1>Version 2</h1><p>See the <a href="/docs">migration guide</a> first.</p>
+            <noscript>Enable JS</noscript></body></html>"#;
+
+        let parsed = parse_document(html.as_bytes(), "notes.html").unwrap();
+        assert_eq!(parsed.metadata.title.as_deref(), Some("Release Notes"));
+        assert_eq!(parsed.title.as_deref(), Some("Release Notes"));
+        assert_eq!(parsed.metadata.language.as_deref(), Some("en-US"));
+        assert_eq!(parsed.text, "Version 2\nSee the migration guide first.");
+    }
+
+    #[test]
+    fn test_parse_epub_follows_spine_order() {
+        let epub = build_zip(&[
+            ("mimetype", "application/epub+zip"),
+            (
+                "META-INF/container.xml",
+                r#"<container><rootfiles><rootfile full-path="OEBPS/content.opf"/></rootfiles></container>"#,
+            ),
+            (
+                "OEBPS/content.opf",
+                r#"<package xmlns:dc="http://purl.org/dc/elements/1.1/">
+                    <metadata><dc:title>Sample Book</dc:title></metadata>
+                    <manifest>
+                        <item id="c1" href="text/chapter%201.xhtml"/>
+                        <item id="c2" href="text/chapter2.xhtml"/>
+                    </manifest>
+                    <spine><itemref idref="c2"/><itemref idref="c1"/></spine>
+                </package>"#,
+            ),
+            ("OEBPS/text/chapter 1.xhtml", "<html><body><p>First file</p></body></html>"),
+            ("OEBPS/text/chapter2.xhtml", "<html><body><p>Read first</p></body></html>"),
+        ]);
+
+        let parsed = parse_document(&epub, "book.epub").unwrap();
+        assert_eq!(parsed.metadata.title.as_deref(), Some("Sample Book"));
+        assert_eq!(parsed.pages, 2);
+        assert_eq!(parsed.text, "Read first\nFirst file");
+    }
+
+    #[test]
+    fn test_parse_eml_prefers_plain_text_and_parses_attachments() {
+        let eml = concat!(
+            "From: Ada Lovelace <ada@example.com>\r\n",
+            "To: support@example.com\r\n",
+            "Subject: Engine =?utf-8?q?not_starting?=\r\n",
+            "Date: Tue, 1 Jul 2025 10:52:37 +0200\r\n",
+            "MIME-Version: 1.0\r\n",
+            "Content-Type: multipart/mixed; boundary=\"outer\"\r\n",
+            "\r\n",
+            "--outer\r\n",
+            "Content-Type: multipart/alternative; boundary=\"inner\"\r\n",
+            "\r\n",
+            "--inner\r\n",
+            "Content-Type: text/plain; charset=utf-8\r\n",
+            "Content-Transfer-Encoding: quoted-printable\r\n",
+            "\r\n",
+            "The engine stopped at step 3=2E\r\n",
+            "--inner\r\n",
+            "Content-Type: text/html; charset=utf-8\r\n",
+            "\r\n",
+            "<p>HTML version</p>\r\n",
+            "--inner--\r\n",
+            "--outer\r\n",
+            "Content-Type: text/plain; name=\"trace.log\"\r\n",
+            "Content-Disposition: attachment; filename=\"trace.log\"\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "c3RlcCAzIGZhaWxlZA==\r\n",
+            "--outer--\r\n",
+        );
+
+        let parsed = parse_document(eml.as_bytes(), "message.eml").unwrap();
+        assert_eq!(parsed.metadata.title.as_deref(), Some("Engine not starting"));
+        assert_eq!(parsed.metadata.extra["from"], "Ada Lovelace <ada@example.com>");
+        assert_eq!(parsed.metadata.extra["to"], "support@example.com");
+        assert!(parsed.metadata.extra["date"].starts_with("2025-07-01T10:52:37"));
+        assert!(parsed.text.ends_with("The engine stopped at step 3."));
+        assert!(!parsed.text.contains("HTML version"));
+
+        assert_eq!(parsed.parts.len(), 1);
+        assert_eq!(parsed.parts[0].metadata.extra["filename"], "trace.log");
+        assert_eq!(parsed.parts[0].text, "step 3 failed");
+    }
+
+    #[test]
+    fn test_parse_mbox_splits_messages() {
+        let mbox = concat!(
+            "From alice@example.com Mon Jan  1 00:00:00 2024\n",
+            "From: alice@example.com\n",
+            "Subject: First\n",
+            "\n",
+            "Hello\n",
+            ">From the archive\n",
+            "\n",
+            "From bob@example.com Tue Jan  2 00:00:00 2024\n",
+            "From: bob@example.com\n",
+            "Subject: Second\n",
+            "\n",
+            "Bye\n",
+        );
+
+        let parsed = parse_document(mbox.as_bytes(), "support.mbox").unwrap();
+        assert_eq!(parsed.pages, 2);
+        assert_eq!(parsed.parts.len(), 2);
+        assert_eq!(parsed.parts[0].metadata.title.as_deref(), Some("First"));
+        assert!(parsed.parts[0].text.contains("From the archive"));
+        assert_eq!(parsed.parts[1].metadata.extra["from"], "bob@example.com");
+    }
+
+    #[test]
+    fn test_parse_zip_archive_reports_each_file() {
+        let archive = build_zip(&[
+            ("notes/readme.md", "# Notes"),
+            ("notes/data.bin", "\u{0}\u{1}"),
+            ("__MACOSX/notes/._readme.md", "junk"),
+            ("notes/.DS_Store", "junk"),
+        ]);
+
+        let entries = parse_archive(&archive, "upload.zip", ArchiveLimits::default()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, "notes/readme.md");
+        assert_eq!(entries[0].result.as_ref().unwrap().text, "# Notes");
+        assert_eq!(entries[1].result.as_ref().unwrap_err(), "Unsupported file type: bin");
+    }
+
+    #[test]
+    fn test_parse_tar_gz_archive() {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let content = b"hello from tar";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "./docs/hello.txt", &content[..]).unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        let entries = parse_archive(&archive, "docs.tar.gz", ArchiveLimits::default()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, "docs/hello.txt");
+        assert_eq!(entries[0].result.as_ref().unwrap().text, "hello from tar");
+    }
+
+    #[test]
+    fn test_parse_archive_enforces_limits() {
+        let archive = build_zip(&[("a.txt", "aaaa"), ("b.txt", "bbbb"), ("c.txt", "cccc")]);
+
+        let too_many = ArchiveLimits { max_entries: 2, max_total_bytes: 1024 };
+        assert!(parse_archive(&archive, "a.zip", too_many).is_err());
+
+        let too_large = ArchiveLimits { max_entries: 10, max_total_bytes: 10 };
+        assert!(parse_archive(&archive, "a.zip", too_large).is_err());
+    }
+
+    #[test]
+    fn test_parse_eml_limits_nesting_depth() {
+        let mut eml = "From: a@example.com\r\nSubject: Level 0\r\n\r\nInnermost\r\n".to_string();
+        for level in 1..=MAX_EMAIL_DEPTH + 2 {
+            eml = format!(
+                "From: a@example.com\r\nSubject: Level {level}\r\n\
+                 Content-Type: multipart/mixed; boundary=\"b{level}\"\r\n\r\n\
+                 --b{level}\r\nContent-Type: text/plain\r\n\r\nBody {level}\r\n\
+                 --b{level}\r\nContent-Type: message/rfc822\r\n\r\n{eml}\r\n--b{level}--\r\n"
+            );
+        }
+
+        let parsed = parse_document(eml.as_bytes(), "deep.eml").unwrap();
+        let mut depth = 1;
+        let mut message = &parsed;
+        while let Some(nested) = message.parts.first() {
+            depth += 1;
+            message = nested;
+        }
+        assert_eq!(depth, MAX_EMAIL_DEPTH);
+    }
+
+    #[test]
+    fn test_nested_containers_share_the_archive_budget() {
+        let body = format!("<w:t>{}</w:t>", "x".repeat(4096));
+        let docx = build_zip(&[("word/document.xml", body.as_str())]);
+        let limits = ArchiveLimits { max_entries: 10, max_total_bytes: 2048 };
+
+        // The DOCX is small compressed but unpacks past the limit
+        assert!(docx.len() < 2048);
+        assert!(parse_document_with_limits(&docx, "big.docx", limits).is_err());
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("report.docx", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(&docx).unwrap();
+        let archive = zip.finish().unwrap().into_inner();
+        assert!(parse_archive(&archive, "upload.zip", limits).is_err());
+        assert!(parse_archive(&archive, "upload.zip", ArchiveLimits::default()).is_ok());
+    }
+
+    #[test]
+    fn test_decode_text_detects_legacy_encodings() {
+        let utf8 = decode_text("naïve café".as_bytes());
+        assert_eq!(utf8.encoding, "UTF-8");
+        assert_eq!(utf8.confidence, Some(1.0));
+
+        // "Grüße aus Köln" in Windows-1252
+        let latin = decode_text(b"Gr\xfc\xdfe aus K\xf6ln");
+        assert_eq!(latin.encoding, "windows-1252");
+        assert_eq!(latin.text, "Grüße aus Köln");
+
+        let mut utf16_bom = vec![0xff, 0xfe];
+        utf16_bom.extend("hi ✓".encode_utf16().flat_map(|u| u.to_le_bytes()));
+        let bom = decode_text(&utf16_bom);
+        assert_eq!(bom.encoding, "UTF-16LE");
+        assert_eq!(bom.text, "hi ✓");
+
+        let utf16_be: Vec<u8> = "plain ascii".encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+        let be = decode_text(&utf16_be);
+        assert_eq!(be.encoding, "UTF-16BE");
+        assert_eq!(be.text, "plain ascii");
+        assert_eq!(be.confidence, None);
+    }
+
+    #[test]
+    fn test_parse_text_file_reports_encoding() {
+        // "日本語のテキスト" in Shift_JIS
+        let sjis = b"\x93\xfa\x96\x7b\x8c\xea\x82\xcc\x83\x65\x83\x4c\x83\x58\x83\x67";
+        let parsed = parse_document(sjis, "notes.txt").unwrap();
+        assert_eq!(parsed.encoding.as_deref(), Some("Shift_JIS"));
+        assert_eq!(parsed.text, "日本語のテキスト");
+        assert_eq!(parsed.encoding_confidence, Some(0.5));
+    }
+
+    #[test]
+    fn test_parse_html_honors_meta_charset() {
+        // "Grüße" in Windows-1252, which statistical detection alone may not pick
+        let mut html = b"<html><head><meta charset=\"windows-1252\"><title>Gr".to_vec();
+        html.extend_from_slice(b"\xfc\xdfe</title></head><body>Hallo</body></html>");
+        let parsed = parse_document(&html, "page.html").unwrap();
+        assert_eq!(parsed.encoding.as_deref(), Some("windows-1252"));
+        assert_eq!(parsed.encoding_confidence, Some(1.0));
+        assert_eq!(parsed.metadata.title.as_deref(), Some("Grüße"));
+
+        let http_equiv = br#"<meta http-equiv="Content-Type" content="text/html; charset=Shift_JIS">"#;
+        assert_eq!(declared_html_charset(http_equiv), Some(encoding_rs::SHIFT_JIS));
+        assert_eq!(declared_html_charset(b"<meta charset='utf-16le'>"), Some(UTF_8));
+        assert_eq!(declared_html_charset(b"<meta name=\"charset\">"), None);
+    }
+
+    #[test]
+    fn test_parse_docx_reads_core_properties() {
+        let docx = build_zip(&[
+            ("word/document.xml", "<w:document><w:body><w:p><w:r><w:t>Quarterly report</w:t></w:r></w:p></w:body></w:document>"),
+            (
+                "docProps/core.xml",
+                r#"<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties"
+                    xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/">
+                    <dc:title>Q3 Report</dc:title>
+                    <dc:creator>Grace Hopper</dc:creator>
+                    <dcterms:created>2024-10-01T08:30:00Z</dcterms:created>
+                    <dcterms:modified>2024-10-02T17:00:00Z</dcterms:modified>
+                </cp:coreProperties>"#,
+            ),
+        ]);
+
+        let parsed = parse_document(&docx, "report.docx").unwrap();
+        assert_eq!(parsed.metadata.title.as_deref(), Some("Q3 Report"));
+        assert_eq!(parsed.metadata.author.as_deref(), Some("Grace Hopper"));
+        assert_eq!(parsed.metadata.created.unwrap().to_rfc3339(), "2024-10-01T08:30:00+00:00");
+        assert_eq!(parsed.metadata.modified.unwrap().to_rfc3339(), "2024-10-02T17:00:00+00:00");
+    }
+
+    #[test]
+    fn test_parse_pdf_date() {
+        assert_eq!(
+            parse_pdf_date("D:20240131094500+01'00'").unwrap().to_rfc3339(),
+            "2024-01-31T08:45:00+00:00"
+        );
+        assert_eq!(parse_pdf_date("D:2024").unwrap().to_rfc3339(), "2024-01-01T00:00:00+00:00");
+        assert!(parse_pdf_date("garbage").is_none());
+    }
+
+    #[test]
+    fn test_parse_document_fills_common_metadata() {
+        let text = "The support team reviewed every ticket that arrived over the weekend. \
+                    Most of them were about the new billing page, which customers found \
+                    confusing, so we are going to rewrite the help article and add a short \
+                    video that explains how invoices are generated each month.";
+        let parsed = parse_document(text.as_bytes(), "fox.txt").unwrap();
+
+        assert_eq!(parsed.metadata.word_count, 46);
+        assert_eq!(parsed.metadata.language.as_deref(), Some("en"));
+        assert_eq!(normalize_language_tag("ZH-hant-tw").as_deref(), Some("zh-Hant-TW"));
+        assert_eq!(normalize_language_tag("deu").as_deref(), Some("de"));
+        assert_eq!(normalize_language_tag("  ").as_deref(), None);
+        assert_eq!(parsed.metadata.content_hash.len(), 64);
+
+        let hello = parse_document(b"hello", "hello.txt").unwrap();
+        assert_eq!(
+            hello.metadata.content_hash,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+}
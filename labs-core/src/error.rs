@@ -0,0 +1,177 @@
+use crate::sandbox::SandboxError;
+use serde::Serialize;
+use std::fmt;
+
+/// Machine-readable error codes shared by the REST API and the Tauri commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    DbNotOpen,
+    CollectionNotFound,
+    DocumentNotFound,
+    VectorNotFound,
+    NotFound,
+    DimensionMismatch,
+    InvalidRequest,
+    InvalidPath,
+    PathOutsideRoots,
+    ParseFailed,
+    PayloadTooLarge,
+    Unauthorized,
+    Forbidden,
+    StorageError,
+    Internal,
+}
+
+#[cfg(feature = "actix")]
+impl ErrorCode {
+    pub fn status(self) -> actix_web::http::StatusCode {
+        use actix_web::http::StatusCode;
+
+        match self {
+            ErrorCode::DbNotOpen
+            | ErrorCode::CollectionNotFound
+            | ErrorCode::DocumentNotFound
+            | ErrorCode::VectorNotFound
+            | ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::DimensionMismatch | ErrorCode::InvalidRequest | ErrorCode::InvalidPath => {
+                StatusCode::BAD_REQUEST
+            }
+            ErrorCode::ParseFailed => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden | ErrorCode::PathOutsideRoots => StatusCode::FORBIDDEN,
+            ErrorCode::StorageError | ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Error returned by every operation. Tauri commands reject with it as-is
+/// (`{"code": "...", "message": "..."}`); REST responses wrap it in
+/// [`ErrorBody`].
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+/// Body of every REST error response
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErrorBody {
+    pub error: ApiError,
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    pub fn db_not_open(db: &str) -> Self {
+        Self::new(ErrorCode::DbNotOpen, format!("Database is not open: {}", db))
+    }
+
+    pub fn collection_not_found(collection: &str) -> Self {
+        Self::new(ErrorCode::CollectionNotFound, format!("Collection not found: {}", collection))
+    }
+
+    pub fn dimension_mismatch(expected: usize, actual: usize) -> Self {
+        Self::new(
+            ErrorCode::DimensionMismatch,
+            format!("Expected a vector with {} dimensions, got {}", expected, actual),
+        )
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidRequest, message)
+    }
+
+    pub fn internal(message: impl fmt::Display) -> Self {
+        Self::new(ErrorCode::Internal, message.to_string())
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[cfg(feature = "actix")]
+impl actix_web::ResponseError for ApiError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        self.code.status()
+    }
+
+    fn error_response(&self) -> actix_web::HttpResponse {
+        actix_web::HttpResponse::build(self.status_code()).json(ErrorBody { error: self.clone() })
+    }
+}
+
+/// Missing documents, collections and files keep their own codes; anything
+/// else keradb reports is a storage failure
+impl From<keradb::Error> for ApiError {
+    fn from(e: keradb::Error) -> Self {
+        let code = match &e {
+            keradb::Error::DocumentNotFound(_) => ErrorCode::DocumentNotFound,
+            keradb::Error::CollectionNotFound(_) => ErrorCode::CollectionNotFound,
+            keradb::Error::InvalidDocument(_) => ErrorCode::InvalidRequest,
+            keradb::Error::Io(io) if io.kind() == std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+            _ => ErrorCode::StorageError,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+/// System database failures
+impl From<Box<dyn std::error::Error>> for ApiError {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        Self::internal(e)
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::internal(e)
+    }
+}
+
+impl From<SandboxError> for ApiError {
+    fn from(e: SandboxError) -> Self {
+        let code = match e {
+            SandboxError::OutsideRoots(_) => ErrorCode::PathOutsideRoots,
+            _ => ErrorCode::InvalidPath,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_serialization() {
+        let error = ApiError::db_not_open("main/users.db");
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({ "code": "DB_NOT_OPEN", "message": "Database is not open: main/users.db" })
+        );
+
+        let error = ApiError::from(SandboxError::OutsideRoots("/etc".to_string()));
+        assert_eq!(error.code, ErrorCode::PathOutsideRoots);
+        let error = ApiError::from(SandboxError::ParentTraversal("a/../b".to_string()));
+        assert_eq!(error.code, ErrorCode::InvalidPath);
+
+        let error = ApiError::from(keradb::Error::DocumentNotFound("42".to_string()));
+        assert_eq!(error.code, ErrorCode::DocumentNotFound);
+        let error = ApiError::from(keradb::Error::Io(std::io::ErrorKind::PermissionDenied.into()));
+        assert_eq!(error.code, ErrorCode::StorageError);
+    }
+}
@@ -0,0 +1,337 @@
+//! Background jobs for operations too slow to run inside a request. Each job
+//! runs on its own thread, reports progress to listeners and is recorded in
+//! the system database so its history survives restarts.
+
+use crate::error::{ApiError, ApiResult, ErrorCode};
+use crate::service::LabsService;
+use crate::slow_log;
+use crate::system_db::{Job, JobStatus};
+use chrono::Utc;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two progress notifications of one job
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Job history entries returned when no limit is given
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VectorInput {
+    pub vector: Vec<f32>,
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// Work a job performs. `db` accepts any database handle.
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobRequest {
+    /// Delete every document in a collection
+    DropCollection { db: String, collection: String },
+    /// Insert documents one by one
+    ImportDocuments {
+        db: String,
+        collection: String,
+        #[cfg_attr(feature = "openapi", schema(value_type = Vec<Object>))]
+        documents: Vec<serde_json::Value>,
+    },
+    /// Insert vectors into an existing vector collection
+    ImportVectors {
+        db: String,
+        collection: String,
+        vectors: Vec<VectorInput>,
+    },
+}
+
+impl JobRequest {
+    fn kind(&self) -> &'static str {
+        match self {
+            JobRequest::DropCollection { .. } => "drop_collection",
+            JobRequest::ImportDocuments { .. } => "import_documents",
+            JobRequest::ImportVectors { .. } => "import_vectors",
+        }
+    }
+
+    /// Database handle the job works on
+    pub fn db(&self) -> &str {
+        self.target().0
+    }
+
+    fn target(&self) -> (&str, &str) {
+        match self {
+            JobRequest::DropCollection { db, collection }
+            | JobRequest::ImportDocuments { db, collection, .. }
+            | JobRequest::ImportVectors { db, collection, .. } => (db, collection),
+        }
+    }
+
+    fn total(&self) -> usize {
+        match self {
+            JobRequest::DropCollection { .. } => 0,
+            JobRequest::ImportDocuments { documents, .. } => documents.len(),
+            JobRequest::ImportVectors { vectors, .. } => vectors.len(),
+        }
+    }
+}
+
+struct RunningJob {
+    job: Mutex<Job>,
+    cancelled: AtomicBool,
+}
+
+/// Receives every job update; returning false unsubscribes. Must not block.
+type Listener = Box<dyn Fn(&Job) -> bool + Send + Sync>;
+
+pub struct JobManager {
+    service: Arc<LabsService>,
+    running: Mutex<HashMap<String, Arc<RunningJob>>>,
+    listeners: Mutex<HashMap<u64, Listener>>,
+    next_listener: AtomicU64,
+}
+
+impl JobManager {
+    /// Jobs left queued or running by a previous process are marked failed
+    pub fn new(service: Arc<LabsService>) -> Arc<Self> {
+        match service.system_db().list_jobs(None) {
+            Ok(jobs) => {
+                for mut job in jobs.into_iter().filter(|job| !job.status.is_finished()) {
+                    job.status = JobStatus::Failed;
+                    job.error = Some("Interrupted by a restart".to_string());
+                    job.finished_at = Some(Utc::now());
+                    if let Err(e) = service.system_db().save_job(&job) {
+                        log::warn!("Failed to mark job {} as interrupted: {}", job.id, e);
+                    }
+                }
+            }
+            Err(e) => log::warn!("Failed to read job history: {}", e),
+        }
+
+        Arc::new(Self {
+            service,
+            running: Mutex::new(HashMap::new()),
+            listeners: Mutex::new(HashMap::new()),
+            next_listener: AtomicU64::new(1),
+        })
+    }
+
+    /// Record the job and start it on a background thread
+    pub fn submit(self: &Arc<Self>, request: JobRequest) -> ApiResult<Job> {
+        let (db, collection) = request.target();
+        let database = self.service.database_name(db)?;
+        // Fail now rather than in the background if the database is not open
+        self.service.collections(&database)?;
+
+        let mut job = Job {
+            id: String::new(),
+            kind: request.kind().to_string(),
+            database,
+            collection: collection.to_string(),
+            status: JobStatus::Queued,
+            done: 0,
+            total: request.total(),
+            result: None,
+            error: None,
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+        };
+        job.id = self
+            .service
+            .system_db()
+            .create_job(&job)
+            .map_err(|e| ApiError::internal(format!("Failed to record job: {}", e)))?;
+
+        let running = Arc::new(RunningJob {
+            job: Mutex::new(job.clone()),
+            cancelled: AtomicBool::new(false),
+        });
+        self.running.lock().insert(job.id.clone(), running.clone());
+
+        log::info!("Submitted {} job {} for {}/{}", job.kind, job.id, job.database, job.collection);
+
+        // Slow operations and audit entries of the job are attributed to
+        // whoever submitted it; local jobs stay without a caller
+        let caller = slow_log::current_caller();
+        let manager = self.clone();
+        std::thread::Builder::new()
+            .name(format!("job-{}", job.id))
+            .spawn(move || match caller {
+                Some(caller) => slow_log::with_caller(&caller, || manager.run(running, request)),
+                None => manager.run(running, request),
+            })
+            .map_err(ApiError::internal)?;
+
+        Ok(job)
+    }
+
+    pub fn get(&self, id: &str) -> ApiResult<Job> {
+        if let Some(running) = self.running.lock().get(id) {
+            return Ok(running.job.lock().clone());
+        }
+        self.service
+            .system_db()
+            .get_job(id)?
+            .ok_or_else(|| ApiError::new(ErrorCode::NotFound, format!("Job not found: {}", id)))
+    }
+
+    /// Job history, most recent first, with live progress for running jobs
+    pub fn list(&self, limit: Option<usize>) -> ApiResult<Vec<Job>> {
+        let mut jobs = self
+            .service
+            .system_db()
+            .list_jobs(Some(limit.unwrap_or(DEFAULT_HISTORY_LIMIT)))?;
+
+        let running = self.running.lock();
+        for job in &mut jobs {
+            if let Some(live) = running.get(&job.id) {
+                *job = live.job.lock().clone();
+            }
+        }
+        Ok(jobs)
+    }
+
+    /// Ask a job to stop; it finishes as cancelled after its current item.
+    /// Finished jobs are returned unchanged.
+    pub fn cancel(&self, id: &str) -> ApiResult<Job> {
+        if let Some(running) = self.running.lock().get(id) {
+            running.cancelled.store(true, Ordering::Relaxed);
+            log::info!("Cancelling job {}", id);
+        }
+        self.get(id)
+    }
+
+    /// Register a listener for every job update
+    pub fn subscribe(&self, listener: impl Fn(&Job) -> bool + Send + Sync + 'static) -> u64 {
+        let id = self.next_listener.fetch_add(1, Ordering::Relaxed);
+        self.listeners.lock().insert(id, Box::new(listener));
+        id
+    }
+
+    /// Returns whether the listener existed
+    pub fn unsubscribe(&self, id: u64) -> bool {
+        self.listeners.lock().remove(&id).is_some()
+    }
+
+    fn notify(&self, job: &Job) {
+        self.listeners.lock().retain(|_, listener| listener(job));
+    }
+
+    // Update the in-memory job and notify listeners
+    fn update(&self, running: &RunningJob, change: impl FnOnce(&mut Job)) -> Job {
+        let mut job = running.job.lock();
+        change(&mut job);
+        let snapshot = job.clone();
+        drop(job);
+
+        self.notify(&snapshot);
+        snapshot
+    }
+
+    fn persist(&self, job: &Job) {
+        if let Err(e) = self.service.system_db().save_job(job) {
+            log::warn!("Failed to save job {}: {}", job.id, e);
+        }
+    }
+
+    fn run(&self, running: Arc<RunningJob>, request: JobRequest) {
+        let job = self.update(&running, |job| {
+            job.status = JobStatus::Running;
+            job.started_at = Some(Utc::now());
+        });
+        self.persist(&job);
+
+        let mut last_notified = Instant::now();
+        let mut progress = |done: usize, total: usize| {
+            let finished = done >= total;
+            if finished || last_notified.elapsed() >= PROGRESS_INTERVAL {
+                last_notified = Instant::now();
+                self.update(&running, |job| {
+                    job.done = done;
+                    job.total = total;
+                });
+            } else {
+                let mut job = running.job.lock();
+                job.done = done;
+                job.total = total;
+            }
+            !running.cancelled.load(Ordering::Relaxed)
+        };
+
+        let outcome = self.execute(&job.database, request, &mut progress);
+        let cancelled = running.cancelled.load(Ordering::Relaxed);
+
+        let job = self.update(&running, |job| {
+            job.finished_at = Some(Utc::now());
+            match outcome {
+                Ok(result) => {
+                    job.status = if cancelled { JobStatus::Cancelled } else { JobStatus::Completed };
+                    job.result = Some(result);
+                }
+                Err((result, e)) => {
+                    job.status = JobStatus::Failed;
+                    job.result = Some(result);
+                    job.error = Some(e.to_string());
+                }
+            }
+        });
+        self.persist(&job);
+        self.running.lock().remove(&job.id);
+
+        log::info!("Job {} finished: {:?}", job.id, job.status);
+    }
+
+    // Run the work, returning the result so far alongside any error
+    fn execute(
+        &self,
+        db: &str,
+        request: JobRequest,
+        progress: &mut impl FnMut(usize, usize) -> bool,
+    ) -> Result<serde_json::Value, (serde_json::Value, ApiError)> {
+        match request {
+            JobRequest::DropCollection { collection, .. } => self
+                .service
+                .drop_collection_with_progress(db, &collection, &mut *progress)
+                .map(|deleted| serde_json::json!({ "documents_deleted": deleted }))
+                .map_err(|(deleted, e)| (serde_json::json!({ "documents_deleted": deleted }), e)),
+            JobRequest::ImportDocuments { collection, documents, .. } => {
+                let total = documents.len();
+                let mut inserted = 0;
+                for document in documents {
+                    if let Err(e) = self.service.insert_document(db, &collection, document) {
+                        return Err((serde_json::json!({ "inserted": inserted }), e));
+                    }
+                    inserted += 1;
+                    if !progress(inserted, total) {
+                        break;
+                    }
+                }
+                Ok(serde_json::json!({ "inserted": inserted }))
+            }
+            JobRequest::ImportVectors { collection, vectors, .. } => {
+                let total = vectors.len();
+                let mut ids = Vec::new();
+                for input in vectors {
+                    if let Err(e) = self
+                        .service
+                        .insert_vector(db, &collection, input.vector, input.metadata)
+                        .map(|id| ids.push(id))
+                    {
+                        return Err((serde_json::json!({ "inserted": ids.len(), "ids": ids }), e));
+                    }
+                    if !progress(ids.len(), total) {
+                        break;
+                    }
+                }
+                Ok(serde_json::json!({ "inserted": ids.len(), "ids": ids }))
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Append-only journal of system database writes that have not been synced
+//! yet. Entries are appended without an fsync, so a write never waits on the
+//! disk; the flusher syncs them as a batch when it rotates the journal. If
+//! the process dies between two syncs of the database nothing is lost, as
+//! the operating system still writes the appended entries out and the next
+//! start replays them. A power loss or OS crash can lose the entries of the
+//! current batch, at most about `FLUSH_INTERVAL` of deferred writes.
+
+use crate::audit::AuditEntry;
+use crate::system_db::{DatabaseConnection, PerformanceMetric, QueryHistoryEntry};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// A deferred write. Replaying one that already reached the database is
+/// harmless: metrics, query runs, audit entries and new connections are
+/// skipped when an identical one exists, connection updates overwrite with
+/// the same state and removals of a missing connection do nothing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum JournalEntry {
+    Metric(PerformanceMetric),
+    /// A connection registered for the first time, with the id it got
+    NewConnection(DatabaseConnection),
+    /// Full state of an existing connection after an update
+    Connection(DatabaseConnection),
+    RemovedConnection { id: String },
+    QueryHistory(QueryHistoryEntry),
+    Audit(AuditEntry),
+}
+
+pub(crate) struct Journal {
+    path: PathBuf,
+    file: File,
+}
+
+impl Journal {
+    /// `<db_path>.journal`
+    pub fn path_for(db_path: &Path) -> PathBuf {
+        let mut name = db_path.as_os_str().to_owned();
+        name.push(".journal");
+        PathBuf::from(name)
+    }
+
+    /// Journal being retired by a flush, kept until the sync completes
+    pub fn retired_path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".old");
+        PathBuf::from(name)
+    }
+
+    pub fn open(path: PathBuf) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, file })
+    }
+
+    /// Write one entry as a single line so a crash can only tear the last
+    /// one. It is not synced here; see `rotate`.
+    pub fn append(&mut self, entry: &JournalEntry) -> Result<(), Box<dyn std::error::Error>> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        Ok(())
+    }
+
+    /// Sync the current entries, move them aside and start an empty journal;
+    /// returns where the old entries went
+    pub fn rotate(&mut self) -> std::io::Result<PathBuf> {
+        self.file.sync_data()?;
+        let retired = Self::retired_path(&self.path);
+        if retired.exists() {
+            // The previous flush failed to sync; its entries are still needed
+            let mut old = OpenOptions::new().append(true).open(&retired)?;
+            std::io::copy(&mut File::open(&self.path)?, &mut old)?;
+            old.sync_data()?;
+            self.file.set_len(0)?;
+            self.file.sync_all()?;
+        } else {
+            std::fs::rename(&self.path, &retired)?;
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+            // Make the rename and the new file durable
+            sync_dir(&self.path)?;
+        }
+        Ok(retired)
+    }
+
+    /// Entries of a journal file, oldest first. A missing file has none; a
+    /// torn or unreadable line is skipped.
+    pub fn read(path: &Path) -> std::io::Result<Vec<JournalEntry>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => log::warn!("Skipping unreadable journal entry in {}: {}", path.display(), e),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Sync the directory holding `path`, so entries added or renamed in it
+/// survive a crash. Windows cannot open a directory as a file, so there this
+/// is left to the filesystem.
+fn sync_dir(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
@@ -0,0 +1,23 @@
+//! Database operations shared by the keradb Labs REST backend and the
+//! desktop app. Both front ends are thin adapters over [`LabsService`].
+
+pub mod audit;
+pub mod changes;
+pub mod connections;
+pub mod document_parser;
+pub mod error;
+pub mod jobs;
+mod journal;
+pub mod metrics;
+pub mod migrations;
+pub mod parsing;
+pub mod queries;
+pub mod sandbox;
+pub mod service;
+pub mod slow_log;
+pub mod system_db;
+pub mod types;
+pub mod workspace;
+
+pub use error::{ApiError, ApiResult, ErrorBody, ErrorCode};
+pub use service::LabsService;
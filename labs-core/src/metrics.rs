@@ -0,0 +1,260 @@
+//! Operation timing rollups. Raw samples are recorded by `LabsService`;
+//! compaction turns closed time buckets into per-minute, per-hour and
+//! per-day rollups and prunes samples and rollups past their retention.
+
+use crate::service::LabsService;
+use crate::system_db::PerformanceMetric;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// How often the background compaction runs
+const COMPACTION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Granularity {
+    pub const ALL: [Granularity; 3] = [Granularity::Minute, Granularity::Hour, Granularity::Day];
+
+    pub fn duration(self) -> Duration {
+        match self {
+            Granularity::Minute => Duration::minutes(1),
+            Granularity::Hour => Duration::hours(1),
+            Granularity::Day => Duration::days(1),
+        }
+    }
+
+    /// Start of the bucket `timestamp` falls in (UTC)
+    pub fn bucket_start(self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        timestamp.duration_trunc(self.duration()).unwrap_or(timestamp)
+    }
+}
+
+/// Timing statistics of one operation on one database over one bucket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MetricRollup {
+    pub database_path: String,
+    pub operation: String,
+    pub granularity: Granularity,
+    pub bucket_start: DateTime<Utc>,
+    pub count: u64,
+    pub mean_ms: f64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+}
+
+/// How long samples and rollups are kept before compaction prunes them
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsRetention {
+    /// Raw samples are also kept until the day they belong to is rolled up
+    pub raw_hours: i64,
+    pub minute_days: i64,
+    pub hour_days: i64,
+    pub day_days: i64,
+    /// Slow operation log entries
+    pub slow_op_days: i64,
+    /// Query history entries
+    pub query_history_days: i64,
+}
+
+impl Default for MetricsRetention {
+    fn default() -> Self {
+        Self {
+            raw_hours: 24,
+            minute_days: 7,
+            hour_days: 90,
+            day_days: 730,
+            slow_op_days: 30,
+            query_history_days: 30,
+        }
+    }
+}
+
+impl MetricsRetention {
+    /// Rollups of `granularity` starting before this are pruned
+    pub fn rollup_cutoff(&self, granularity: Granularity, now: DateTime<Utc>) -> DateTime<Utc> {
+        let days = match granularity {
+            Granularity::Minute => self.minute_days,
+            Granularity::Hour => self.hour_days,
+            Granularity::Day => self.day_days,
+        };
+        now - Duration::days(days)
+    }
+
+    /// Slow operation log entries before this are pruned
+    pub fn slow_op_cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - Duration::days(self.slow_op_days)
+    }
+
+    /// Query history entries before this are pruned
+    pub fn query_history_cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - Duration::days(self.query_history_days)
+    }
+
+    /// Raw samples before this are pruned
+    pub fn raw_cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        (now - Duration::hours(self.raw_hours)).min(Granularity::Day.bucket_start(now))
+    }
+}
+
+/// Selects samples or rollups; unset fields match everything. `db` accepts
+/// any database handle.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct MetricsQuery {
+    /// Database id, alias, logical name or path
+    pub db: Option<String>,
+    pub operation: Option<String>,
+    /// Inclusive start (RFC 3339)
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive end (RFC 3339)
+    pub to: Option<DateTime<Utc>>,
+    /// Rollup bucket size; defaults to `minute`
+    pub granularity: Option<Granularity>,
+    /// Maximum number of raw samples, most recent first
+    pub limit: Option<usize>,
+}
+
+/// `MetricsQuery` with the database resolved to the name metrics are recorded under
+#[derive(Debug, Clone, Default)]
+pub struct MetricsFilter {
+    pub database_path: Option<String>,
+    pub operation: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl MetricsFilter {
+    pub fn matches(&self, database_path: &str, operation: &str, timestamp: DateTime<Utc>) -> bool {
+        self.database_path.as_ref().is_none_or(|db| db == database_path)
+            && self.operation.as_ref().is_none_or(|op| op == operation)
+            && self.from.is_none_or(|from| timestamp >= from)
+            && self.to.is_none_or(|to| timestamp < to)
+    }
+}
+
+/// Totals from one compaction run
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CompactionStats {
+    pub rollups_created: usize,
+    pub samples_pruned: usize,
+    pub rollups_pruned: usize,
+    pub slow_operations_pruned: usize,
+    pub query_history_pruned: usize,
+}
+
+/// Group samples by database, operation and bucket and summarise each group,
+/// oldest bucket first
+pub fn rollup<'a>(
+    samples: impl IntoIterator<Item = &'a PerformanceMetric>,
+    granularity: Granularity,
+) -> Vec<MetricRollup> {
+    let mut groups: BTreeMap<(DateTime<Utc>, &str, &str), Vec<u64>> = BTreeMap::new();
+    for sample in samples {
+        groups
+            .entry((granularity.bucket_start(sample.timestamp), &sample.database_path, &sample.operation))
+            .or_default()
+            .push(sample.duration_ms);
+    }
+
+    groups
+        .into_iter()
+        .map(|((bucket_start, database_path, operation), mut durations)| {
+            durations.sort_unstable();
+            let count = durations.len() as u64;
+            MetricRollup {
+                database_path: database_path.to_string(),
+                operation: operation.to_string(),
+                granularity,
+                bucket_start,
+                count,
+                mean_ms: durations.iter().sum::<u64>() as f64 / count as f64,
+                p50_ms: percentile(&durations, 50.0),
+                p95_ms: percentile(&durations, 95.0),
+                p99_ms: percentile(&durations, 99.0),
+                max_ms: durations[durations.len() - 1],
+            }
+        })
+        .collect()
+}
+
+// Nearest-rank percentile of a sorted, non-empty slice
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Compact once now and then every few minutes on a background thread
+pub fn spawn_compaction(service: Arc<LabsService>, retention: MetricsRetention) -> std::thread::JoinHandle<()> {
+    std::thread::Builder::new()
+        .name("metrics-compaction".to_string())
+        .spawn(move || loop {
+            match service.compact_metrics(&retention) {
+                Ok(stats) if stats != CompactionStats::default() => log::info!("Compacted metrics: {:?}", stats),
+                Ok(_) => {}
+                Err(e) => log::warn!("Failed to compact metrics: {}", e),
+            }
+            std::thread::sleep(COMPACTION_INTERVAL);
+        })
+        .expect("Failed to spawn metrics compaction thread")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(operation: &str, duration_ms: u64, timestamp: &str) -> PerformanceMetric {
+        PerformanceMetric {
+            id: String::new(),
+            database_path: "a.db".to_string(),
+            operation: operation.to_string(),
+            duration_ms,
+            timestamp: timestamp.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_rollup_percentiles_per_bucket() {
+        let mut samples: Vec<PerformanceMetric> = (1..=100)
+            .map(|ms| sample("insert_document", ms, "2025-03-01T10:15:30Z"))
+            .collect();
+        samples.push(sample("insert_document", 7, "2025-03-01T10:16:00Z"));
+        samples.push(sample("vector_search", 3, "2025-03-01T10:15:59Z"));
+
+        let minutes = rollup(&samples, Granularity::Minute);
+        assert_eq!(minutes.len(), 3);
+        let first = &minutes[0];
+        assert_eq!(first.bucket_start, "2025-03-01T10:15:00Z".parse::<DateTime<Utc>>().unwrap());
+        assert_eq!(first.operation, "insert_document");
+        assert_eq!((first.count, first.p50_ms, first.p95_ms, first.p99_ms, first.max_ms), (100, 50, 95, 99, 100));
+        assert_eq!(first.mean_ms, 50.5);
+
+        let hours = rollup(&samples, Granularity::Hour);
+        assert_eq!(hours.len(), 2);
+        assert_eq!(hours[0].count, 101);
+        assert_eq!(hours[1].p99_ms, 3);
+    }
+
+    #[test]
+    fn test_retention_keeps_raw_samples_of_the_current_day() {
+        let retention = MetricsRetention { raw_hours: 1, ..Default::default() };
+        let now: DateTime<Utc> = "2025-03-01T10:15:30Z".parse().unwrap();
+        assert_eq!(retention.raw_cutoff(now), "2025-03-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap());
+
+        let retention = MetricsRetention { raw_hours: 48, ..Default::default() };
+        assert_eq!(retention.raw_cutoff(now), "2025-02-27T10:15:30Z".parse::<DateTime<Utc>>().unwrap());
+    }
+}
@@ -0,0 +1,162 @@
+//! Schema version of the system database and the ordered migrations that
+//! bring older files up to it. `SystemDatabase::open_at` runs the pending
+//! migrations before replaying the journal; each one is recorded as soon as
+//! it completes, so an interrupted upgrade resumes where it stopped.
+//!
+//! Add a migration whenever stored records would no longer deserialize into
+//! the current types, e.g. a new field without a `serde(default)`, and bump
+//! `SCHEMA_VERSION` to its version.
+
+use crate::system_db::COLLECTIONS;
+use chrono::Utc;
+use keradb::Database;
+use serde_json::{Map, Value};
+
+/// Version written by this build
+pub const SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA_COLLECTION: &str = "schema_version";
+
+struct Migration {
+    version: u32,
+    description: &'static str,
+    /// Returns how many records were changed
+    run: fn(&Database) -> Result<usize, Box<dyn std::error::Error>>,
+}
+
+/// In version order; never reorder or edit a released migration
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "store document ids in the `id` field",
+        run: store_document_ids,
+    },
+];
+
+/// Version recorded in `db`; 0 for files that predate versioning
+pub(crate) fn stored_version(db: &Database) -> Result<u32, Box<dyn std::error::Error>> {
+    let version = db
+        .find_all(SCHEMA_COLLECTION, None, None)?
+        .into_iter()
+        .filter_map(|doc| doc.to_value().get("version").and_then(Value::as_u64))
+        .max()
+        .unwrap_or(0);
+    Ok(u32::try_from(version)?)
+}
+
+/// Run every migration newer than the stored version and return the version
+/// the database was at before
+pub(crate) fn migrate(db: &Database) -> Result<u32, Box<dyn std::error::Error>> {
+    let from = stored_version(db)?;
+    if from > SCHEMA_VERSION {
+        return Err(format!(
+            "System database schema version {} is newer than this build supports ({})",
+            from, SCHEMA_VERSION
+        )
+        .into());
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > from) {
+        let changed = (migration.run)(db)
+            .map_err(|e| format!("System database migration {} failed: {}", migration.version, e))?;
+        set_version(db, migration.version)?;
+        db.sync()?;
+        log::info!(
+            "Migrated system database to version {} ({}): {} records changed",
+            migration.version, migration.description, changed
+        );
+    }
+    Ok(from)
+}
+
+fn set_version(db: &Database, version: u32) -> Result<(), Box<dyn std::error::Error>> {
+    let doc = serde_json::json!({ "version": version, "migrated_at": Utc::now() });
+    let existing = db.find_all(SCHEMA_COLLECTION, None, None)?;
+    match existing.first().and_then(|doc| doc.to_value().get("_id").and_then(Value::as_str).map(str::to_string)) {
+        Some(id) => db.update(SCHEMA_COLLECTION, &id, doc).map(|_| ())?,
+        None => db.insert(SCHEMA_COLLECTION, doc).map(|_| ())?,
+    }
+    Ok(())
+}
+
+// Apply `change` to every record of `collection` and store those it reports
+// as changed; returns how many were stored
+fn rewrite(
+    db: &Database,
+    collection: &str,
+    change: impl Fn(&str, &mut Map<String, Value>) -> bool,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut changed = 0;
+    for doc in db.find_all(collection, None, None)? {
+        let Value::Object(mut record) = doc.to_value() else {
+            continue;
+        };
+        let Some(id) = record.remove("_id").and_then(|id| id.as_str().map(str::to_string)) else {
+            continue;
+        };
+        if change(&id, &mut record) {
+            db.update(collection, &id, Value::Object(record))?;
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+// Records used to be inserted with an empty `id` and only the generated `_id`
+// identified them
+fn store_document_ids(db: &Database) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut changed = 0;
+    for collection in COLLECTIONS {
+        changed += rewrite(db, collection, |id, record| match record.get("id") {
+            Some(Value::String(stored)) if stored != id => {
+                record.insert("id".to_string(), Value::from(id));
+                true
+            }
+            _ => false,
+        })?;
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system_db::CONNECTIONS_COLLECTION;
+
+    #[test]
+    fn test_migrations_run_once_in_order() {
+        let dir = std::env::temp_dir().join(format!("keradb-labs-migrations-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::create(dir.join("system.db")).unwrap();
+
+        // A connection as the first release stored it
+        let id = db
+            .insert(
+                CONNECTIONS_COLLECTION,
+                serde_json::json!({
+                    "id": "",
+                    "path": "a.db",
+                    "created_at": Utc::now(),
+                    "last_accessed": Utc::now(),
+                    "access_count": 1,
+                    "collections_count": 0,
+                    "total_documents": 0,
+                }),
+            )
+            .unwrap();
+
+        assert_eq!(migrate(&db).unwrap(), 0);
+        assert_eq!(stored_version(&db).unwrap(), SCHEMA_VERSION);
+        let record = db.find_by_id(CONNECTIONS_COLLECTION, &id).unwrap().to_value();
+        assert_eq!(record["id"], id.as_str());
+
+        assert_eq!(migrate(&db).unwrap(), SCHEMA_VERSION);
+        assert_eq!(db.find_all(SCHEMA_COLLECTION, None, None).unwrap().len(), 1);
+
+        set_version(&db, SCHEMA_VERSION + 1).unwrap();
+        assert!(migrate(&db).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
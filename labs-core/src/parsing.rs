@@ -0,0 +1,203 @@
+//! Document parsing results shaped the same way for REST uploads and for
+//! files the desktop app reads straight from disk.
+
+use crate::document_parser::{self, ArchiveLimits, ParsedDocument};
+use crate::error::{ApiError, ApiResult, ErrorCode};
+use serde::Serialize;
+use std::io::Read;
+use std::path::Path;
+
+/// Bytes read between two `Reading` progress reports
+const READ_CHUNK_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseStage {
+    Reading,
+    Parsing,
+    Done,
+}
+
+/// Progress of a single parse, reported between stages and while reading
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseProgress {
+    /// Path or file name as given by the caller
+    pub source: String,
+    pub stage: ParseStage,
+    pub bytes_read: u64,
+    pub total_bytes: u64,
+    /// Archive entries parsed so far and in total; both 0 for single documents
+    pub files_done: usize,
+    pub files_total: usize,
+}
+
+/// Parse a document held in memory. Archives are unpacked and every entry is
+/// reported separately; `limits` bounds what they and any nested containers
+/// may unpack.
+pub fn parse_bytes(
+    data: &[u8],
+    filename: &str,
+    limits: ArchiveLimits,
+    mut progress: impl FnMut(ParseProgress),
+) -> ApiResult<serde_json::Value> {
+    let total_bytes = data.len() as u64;
+    let report = |stage, files_done, files_total| ParseProgress {
+        source: filename.to_string(),
+        stage,
+        bytes_read: total_bytes,
+        total_bytes,
+        files_done,
+        files_total,
+    };
+
+    log::info!("Parsing document: {} ({} bytes)", filename, data.len());
+    progress(report(ParseStage::Parsing, 0, 0));
+
+    let response = if document_parser::is_archive(filename) {
+        let mut files_total = 0;
+        let entries = document_parser::parse_archive_with_progress(
+            data,
+            filename,
+            limits,
+            |done, total| {
+                files_total = total;
+                progress(report(ParseStage::Parsing, done, total));
+            },
+        )
+        .map_err(|e| {
+            log::error!("Failed to read archive {}: {}", filename, e);
+            ApiError::new(ErrorCode::ParseFailed, format!("Failed to read archive {}: {}", filename, e))
+        })?;
+
+        let files: Vec<serde_json::Value> = entries
+            .iter()
+            .map(|entry| match &entry.result {
+                Ok(parsed) => parsed_document_json(&entry.path, parsed),
+                Err(e) => serde_json::json!({
+                    "success": false,
+                    "filename": entry.path,
+                    "error": ApiError::new(ErrorCode::ParseFailed, e.clone())
+                }),
+            })
+            .collect();
+
+        let parsed_count = entries.iter().filter(|entry| entry.result.is_ok()).count();
+        log::info!("Parsed {} of {} files from archive {}", parsed_count, entries.len(), filename);
+        progress(report(ParseStage::Done, files_total, files_total));
+
+        serde_json::json!({
+            "success": true,
+            "filename": filename,
+            "archive": true,
+            "files": files
+        })
+    } else {
+        let parsed = document_parser::parse_document_with_limits(data, filename, limits).map_err(|e| {
+            log::error!("Failed to parse {}: {}", filename, e);
+            ApiError::new(ErrorCode::ParseFailed, format!("Failed to parse {}: {}", filename, e))
+        })?;
+
+        log::info!("Successfully parsed {}: {} chars, {} pages", filename, parsed.text.len(), parsed.pages);
+        progress(report(ParseStage::Done, 0, 0));
+
+        parsed_document_json(filename, &parsed)
+    };
+
+    Ok(response)
+}
+
+/// Read and parse a local file, reporting progress while it is read. The
+/// `filename` in the result is the file's name, not its full path.
+pub fn parse_file(
+    path: &Path,
+    limits: ArchiveLimits,
+    mut progress: impl FnMut(ParseProgress),
+) -> ApiResult<serde_json::Value> {
+    let source = path.to_string_lossy().into_owned();
+    let read_error = |e: std::io::Error| {
+        let code = match e.kind() {
+            std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+            _ => ErrorCode::InvalidPath,
+        };
+        ApiError::new(code, format!("Failed to read {}: {}", source, e))
+    };
+
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| ApiError::new(ErrorCode::InvalidPath, format!("Not a file: {}", source)))?;
+
+    let mut file = std::fs::File::open(path).map_err(read_error)?;
+    let total_bytes = file.metadata().map_err(read_error)?.len();
+
+    let mut data = Vec::with_capacity(total_bytes as usize);
+    let mut chunk = vec![0; READ_CHUNK_BYTES];
+    loop {
+        let read = file.read(&mut chunk).map_err(read_error)?;
+        if read == 0 {
+            break;
+        }
+        data.extend_from_slice(&chunk[..read]);
+        progress(ParseProgress {
+            source: source.clone(),
+            stage: ParseStage::Reading,
+            bytes_read: data.len() as u64,
+            total_bytes,
+            files_done: 0,
+            files_total: 0,
+        });
+    }
+
+    parse_bytes(&data, &filename, limits, |update| {
+        progress(ParseProgress {
+            source: source.clone(),
+            ..update
+        })
+    })
+}
+
+fn parsed_document_json(filename: &str, parsed: &ParsedDocument) -> serde_json::Value {
+    serde_json::json!({
+        "success": true,
+        "filename": filename,
+        "text": parsed.text,
+        "pages": parsed.pages,
+        "file_type": parsed.file_type,
+        "title": parsed.title,
+        "encoding": parsed.encoding,
+        "encoding_confidence": parsed.encoding_confidence,
+        "metadata": parsed.metadata,
+        "parts": parsed.parts,
+        "char_count": parsed.text.len()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_file_reports_progress() {
+        let dir = std::env::temp_dir().join(format!("keradb-labs-parsing-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.md");
+        std::fs::write(&path, "# Notes\n\nHello from disk").unwrap();
+
+        let mut stages = Vec::new();
+        let result = parse_file(&path, ArchiveLimits::default(), |update| {
+            assert_eq!(update.source, path.to_string_lossy());
+            stages.push(update.stage);
+        })
+        .unwrap();
+
+        assert_eq!(stages, vec![ParseStage::Reading, ParseStage::Parsing, ParseStage::Done]);
+        assert_eq!(result["success"], true);
+        assert_eq!(result["filename"], "notes.md");
+        assert_eq!(result["file_type"], "md");
+
+        let missing = parse_file(&dir.join("missing.pdf"), ArchiveLimits::default(), |_| {}).unwrap_err();
+        assert_eq!(missing.code, ErrorCode::NotFound);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
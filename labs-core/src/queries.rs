@@ -0,0 +1,395 @@
+//! Saved queries and query history. A query is either a document query
+//! (filter, sort, projection and paging over one collection) or a vector
+//! search; both can be stored by name per database and every run is
+//! recorded in the history so it can be repeated by id.
+
+use crate::types::VectorSearchResultResponse;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+
+/// History entries returned when no limit is given
+pub const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+const MAX_NAME_LEN: usize = 128;
+
+/// Operators accepted in a document filter
+const OPERATORS: [&str; 9] = ["$eq", "$ne", "$gt", "$gte", "$lt", "$lte", "$in", "$nin", "$exists"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SortField {
+    /// Dotted path, e.g. `address.city`
+    pub field: String,
+    #[serde(default)]
+    pub desc: bool,
+}
+
+/// A query that can be run, saved and re-run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QuerySpec {
+    /// Documents of one collection matching `filter`. Filter keys are dotted
+    /// field paths; values match exactly or are an object of operators
+    /// (`$eq`, `$ne`, `$gt`, `$gte`, `$lt`, `$lte`, `$in`, `$nin`,
+    /// `$exists`) that must all hold.
+    Documents {
+        collection: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+        filter: Option<Value>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        sort: Vec<SortField>,
+        /// Fields to return besides `_id`; all fields when unset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        projection: Option<Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        skip: Option<usize>,
+    },
+    /// Nearest neighbours of `vector`
+    VectorSearch { collection: String, vector: Vec<f32>, k: usize },
+}
+
+impl QuerySpec {
+    pub fn collection(&self) -> &str {
+        match self {
+            QuerySpec::Documents { collection, .. } | QuerySpec::VectorSearch { collection, .. } => collection,
+        }
+    }
+
+    /// Check the query is well formed before it is run or saved
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            QuerySpec::Documents { filter, sort, projection, .. } => {
+                if let Some(filter) = filter {
+                    validate_filter(filter)?;
+                }
+                let paths = sort.iter().map(|s| &s.field).chain(projection.iter().flatten());
+                if paths.into_iter().any(|path| path.split('.').any(str::is_empty)) {
+                    return Err("Sort and projection fields must be non-empty dotted paths".to_string());
+                }
+                Ok(())
+            }
+            QuerySpec::VectorSearch { vector, k, .. } => {
+                if vector.is_empty() {
+                    return Err("The search vector is empty".to_string());
+                }
+                if *k == 0 {
+                    return Err("`k` must be at least 1".to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// What a query returned
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QueryResults {
+    Documents {
+        #[cfg_attr(feature = "openapi", schema(value_type = Vec<Object>))]
+        documents: Vec<Value>,
+        /// Documents matching the filter before `skip` and `limit`
+        matched: usize,
+    },
+    VectorSearch { results: Vec<VectorSearchResultResponse> },
+}
+
+impl QueryResults {
+    /// Documents or search results returned
+    pub fn len(&self) -> usize {
+        match self {
+            QueryResults::Documents { documents, .. } => documents.len(),
+            QueryResults::VectorSearch { results } => results.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Body for creating or replacing a saved query
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SavedQueryRequest {
+    /// Unique per database
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub query: QuerySpec,
+}
+
+impl SavedQueryRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+            return Err(format!("Query names must be 1 to {} characters", MAX_NAME_LEN));
+        }
+        self.query.validate()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct QueryHistoryQuery {
+    /// Maximum number of entries, most recent first (default 50)
+    pub limit: Option<usize>,
+}
+
+/// Apply the document part of a query to every document of a collection;
+/// returns the requested page and how many documents matched
+pub fn select_documents(
+    documents: Vec<Value>,
+    filter: Option<&Value>,
+    sort: &[SortField],
+    projection: Option<&[String]>,
+    limit: Option<usize>,
+    skip: Option<usize>,
+) -> (Vec<Value>, usize) {
+    let mut matched: Vec<Value> = documents
+        .into_iter()
+        .filter(|doc| filter.is_none_or(|filter| matches_filter(doc, filter)))
+        .collect();
+    let total = matched.len();
+
+    if !sort.is_empty() {
+        matched.sort_by(|a, b| {
+            sort.iter()
+                .map(|s| {
+                    let order = compare(lookup(a, &s.field), lookup(b, &s.field));
+                    if s.desc {
+                        order.reverse()
+                    } else {
+                        order
+                    }
+                })
+                .find(|order| order.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+
+    let page = matched
+        .into_iter()
+        .skip(skip.unwrap_or(0))
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|doc| match projection {
+            Some(fields) => project(&doc, fields),
+            None => doc,
+        })
+        .collect();
+    (page, total)
+}
+
+fn validate_filter(filter: &Value) -> Result<(), String> {
+    let Value::Object(fields) = filter else {
+        return Err("The filter must be an object of field paths".to_string());
+    };
+    for (path, condition) in fields {
+        if path.is_empty() || path.starts_with('$') {
+            return Err(format!("Invalid filter field '{}'", path));
+        }
+        let Some(operators) = operators(condition) else {
+            continue;
+        };
+        for (op, operand) in operators {
+            if !OPERATORS.contains(&op.as_str()) {
+                return Err(format!("Unknown filter operator '{}'", op));
+            }
+            if matches!(op.as_str(), "$in" | "$nin") && !operand.is_array() {
+                return Err(format!("'{}' takes an array", op));
+            }
+            if op == "$exists" && !operand.is_boolean() {
+                return Err("'$exists' takes true or false".to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// An object whose keys all start with `$` is a set of operators; anything
+/// else is matched exactly
+fn operators(condition: &Value) -> Option<&Map<String, Value>> {
+    condition
+        .as_object()
+        .filter(|map| !map.is_empty() && map.keys().all(|key| key.starts_with('$')))
+}
+
+fn matches_filter(doc: &Value, filter: &Value) -> bool {
+    let Some(fields) = filter.as_object() else {
+        return false;
+    };
+    fields.iter().all(|(path, condition)| {
+        let value = lookup(doc, path);
+        match operators(condition) {
+            Some(operators) => operators.iter().all(|(op, operand)| apply_operator(value, op, operand)),
+            None => value.is_some_and(|value| equal(value, condition)),
+        }
+    })
+}
+
+fn apply_operator(value: Option<&Value>, op: &str, operand: &Value) -> bool {
+    let ordered = |accept: fn(Ordering) -> bool| {
+        value.is_some_and(|value| same_kind(value, operand) && accept(compare(Some(value), Some(operand))))
+    };
+    let contained = || {
+        operand
+            .as_array()
+            .is_some_and(|options| value.is_some_and(|value| options.iter().any(|option| equal(value, option))))
+    };
+
+    match op {
+        "$eq" => value.is_some_and(|value| equal(value, operand)),
+        "$ne" => !value.is_some_and(|value| equal(value, operand)),
+        "$gt" => ordered(Ordering::is_gt),
+        "$gte" => ordered(Ordering::is_ge),
+        "$lt" => ordered(Ordering::is_lt),
+        "$lte" => ordered(Ordering::is_le),
+        "$in" => contained(),
+        "$nin" => !contained(),
+        "$exists" => value.is_some() == operand.as_bool().unwrap_or(true),
+        _ => false,
+    }
+}
+
+/// Value at a dotted path; numeric segments index into arrays
+fn lookup<'a>(doc: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(doc, |value, segment| match value {
+        Value::Object(map) => map.get(segment),
+        Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+fn project(doc: &Value, fields: &[String]) -> Value {
+    let mut out = Map::new();
+    if let Some(id) = doc.get("_id") {
+        out.insert("_id".to_string(), id.clone());
+    }
+    for field in fields {
+        let Some(value) = lookup(doc, field) else {
+            continue;
+        };
+        let mut segments: Vec<&str> = field.split('.').collect();
+        let last = segments.pop().unwrap_or_default();
+        let mut target = &mut out;
+        for segment in segments {
+            let entry = target
+                .entry(segment.to_string())
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !entry.is_object() {
+                *entry = Value::Object(Map::new());
+            }
+            target = entry.as_object_mut().expect("just made an object");
+        }
+        target.insert(last.to_string(), value.clone());
+    }
+    Value::Object(out)
+}
+
+/// Numbers compare by value, so `1` equals `1.0`
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(x), Some(y)) => x == y,
+        _ => a == b,
+    }
+}
+
+fn same_kind(a: &Value, b: &Value) -> bool {
+    rank(Some(a)) == rank(Some(b))
+}
+
+// Missing and null sort first, then booleans, numbers, strings, arrays and
+// objects
+fn rank(value: Option<&Value>) -> u8 {
+    match value {
+        None | Some(Value::Null) => 0,
+        Some(Value::Bool(_)) => 1,
+        Some(Value::Number(_)) => 2,
+        Some(Value::String(_)) => 3,
+        Some(Value::Array(_)) => 4,
+        Some(Value::Object(_)) => 5,
+    }
+}
+
+fn compare(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    match (a, b) {
+        (Some(Value::Bool(x)), Some(Value::Bool(y))) => x.cmp(y),
+        (Some(Value::Number(x)), Some(Value::Number(y))) => {
+            let (x, y) = (x.as_f64().unwrap_or(0.0), y.as_f64().unwrap_or(0.0));
+            x.partial_cmp(&y).unwrap_or(Ordering::Equal)
+        }
+        (Some(Value::String(x)), Some(Value::String(y))) => x.cmp(y),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn people() -> Vec<Value> {
+        vec![
+            json!({ "_id": "1", "name": "ada", "age": 36, "address": { "city": "London" } }),
+            json!({ "_id": "2", "name": "grace", "age": 85, "address": { "city": "New York" } }),
+            json!({ "_id": "3", "name": "linus", "age": 28.0 }),
+        ]
+    }
+
+    fn names(docs: &[Value]) -> Vec<&str> {
+        docs.iter().filter_map(|doc| doc["name"].as_str()).collect()
+    }
+
+    #[test]
+    fn test_filter_sort_and_page() {
+        let filter = json!({ "age": { "$gte": 28, "$lt": 80 } });
+        let sort = [SortField { field: "age".to_string(), desc: true }];
+        let (docs, matched) = select_documents(people(), Some(&filter), &sort, None, None, None);
+        assert_eq!(matched, 2);
+        assert_eq!(names(&docs), vec!["ada", "linus"]);
+
+        let filter = json!({ "address.city": "London" });
+        let (docs, _) = select_documents(people(), Some(&filter), &[], None, None, None);
+        assert_eq!(names(&docs), vec!["ada"]);
+
+        let filter = json!({ "address": { "$exists": false }, "age": 28 });
+        let (docs, _) = select_documents(people(), Some(&filter), &[], None, None, None);
+        assert_eq!(names(&docs), vec!["linus"]);
+
+        let sort = [SortField { field: "name".to_string(), desc: false }];
+        let (docs, matched) = select_documents(people(), None, &sort, None, Some(1), Some(1));
+        assert_eq!((names(&docs), matched), (vec!["grace"], 3));
+    }
+
+    #[test]
+    fn test_projection_keeps_id_and_nesting() {
+        let fields = ["address.city".to_string(), "missing".to_string()];
+        let (docs, _) = select_documents(people(), None, &[], Some(&fields), Some(1), None);
+        assert_eq!(docs, vec![json!({ "_id": "1", "address": { "city": "London" } })]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let documents = |filter: Value| QuerySpec::Documents {
+            collection: "people".to_string(),
+            filter: Some(filter),
+            sort: Vec::new(),
+            projection: None,
+            limit: None,
+            skip: None,
+        };
+        assert!(documents(json!({ "age": { "$gt": 3 }, "name": { "first": "ada" } })).validate().is_ok());
+        assert!(documents(json!({ "age": { "$regex": "a" } })).validate().is_err());
+        assert!(documents(json!({ "age": { "$in": 3 } })).validate().is_err());
+        assert!(documents(json!([1, 2])).validate().is_err());
+
+        let search = QuerySpec::VectorSearch { collection: "v".to_string(), vector: vec![1.0], k: 0 };
+        assert!(search.validate().is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// Convert a Windows path (`D:\path` or `D:/path`) to its WSL form (`/mnt/d/path`).
+/// On Windows hosts such paths are native and are returned unchanged.
+pub fn normalize_path(path: &str) -> String {
+    if cfg!(windows) {
+        return path.to_string();
+    }
+    if let Some(drive_and_rest) = path.strip_prefix(|c: char| c.is_ascii_alphabetic()) {
+        if let Some(rest) = drive_and_rest.strip_prefix(":\\") {
+            let drive = path.chars().next().unwrap().to_lowercase();
+            return format!("/mnt/{}/{}", drive, rest.replace('\\', "/"));
+        } else if let Some(rest) = drive_and_rest.strip_prefix(":/") {
+            let drive = path.chars().next().unwrap().to_lowercase();
+            return format!("/mnt/{}/{}", drive, rest);
+        }
+    }
+    path.to_string()
+}
+
+/// A database resolved against the configured roots
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedDatabase {
+    /// Name used in the API, e.g. `main/users.db`
+    pub name: String,
+    /// Location on disk; never sent to clients when roots are configured
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+struct DatabaseRoot {
+    name: String,
+    path: PathBuf,
+}
+
+/// Directories the server may open or create databases in. With no roots
+/// configured any path is accepted and named by its canonical absolute path,
+/// so every spelling of a file maps to one name.
+#[derive(Debug, Clone, Default)]
+pub struct DatabaseRoots {
+    roots: Vec<DatabaseRoot>,
+}
+
+#[derive(Debug)]
+pub enum SandboxError {
+    EmptyPath,
+    ParentTraversal(String),
+    UnknownRoot(String),
+    OutsideRoots(String),
+    Io(String),
+}
+
+impl fmt::Display for SandboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SandboxError::EmptyPath => write!(f, "Database path is empty"),
+            SandboxError::ParentTraversal(path) => {
+                write!(f, "Database path may not contain '..': {}", path)
+            }
+            SandboxError::UnknownRoot(root) => write!(f, "Unknown database root: {}", root),
+            SandboxError::OutsideRoots(path) => {
+                write!(f, "Database path is outside the allowed roots: {}", path)
+            }
+            SandboxError::Io(message) => write!(f, "Failed to resolve database path: {}", message),
+        }
+    }
+}
+
+impl DatabaseRoots {
+    /// Canonicalize the configured `name -> directory` roots, creating missing
+    /// directories
+    pub fn new(roots: &BTreeMap<String, PathBuf>) -> Result<Self, String> {
+        let mut resolved = Vec::with_capacity(roots.len());
+        for (name, dir) in roots {
+            if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+                return Err(format!("Invalid database root name: {:?}", name));
+            }
+            let dir = PathBuf::from(normalize_path(&dir.to_string_lossy()));
+            std::fs::create_dir_all(&dir)
+                .and_then(|_| dir.canonicalize())
+                .map(|path| resolved.push(DatabaseRoot { name: name.clone(), path }))
+                .map_err(|e| format!("Invalid database root {} ({}): {}", name, dir.display(), e))?;
+        }
+        Ok(Self { roots: resolved })
+    }
+
+    pub fn is_sandboxed(&self) -> bool {
+        !self.roots.is_empty()
+    }
+
+    /// Resolve a logical name (`root/relative/path`) or a filesystem path
+    /// inside one of the roots
+    pub fn resolve(&self, input: &str) -> Result<ResolvedDatabase, SandboxError> {
+        let normalized = normalize_path(input.trim());
+        if normalized.is_empty() {
+            return Err(SandboxError::EmptyPath);
+        }
+
+        let requested = Path::new(&normalized);
+        if !self.is_sandboxed() {
+            let absolute = std::path::absolute(requested).map_err(|e| SandboxError::Io(e.to_string()))?;
+            let canonical = canonicalize_existing(&absolute)?;
+            return Ok(ResolvedDatabase {
+                name: canonical.to_string_lossy().into_owned(),
+                path: canonical,
+            });
+        }
+
+        if requested.components().any(|c| c == Component::ParentDir) {
+            return Err(SandboxError::ParentTraversal(input.to_string()));
+        }
+
+        let candidate = if requested.is_absolute() {
+            requested.to_path_buf()
+        } else {
+            let mut components = requested.components();
+            let root_name = components.next().map(|c| c.as_os_str().to_string_lossy().into_owned());
+            let root_name = root_name.unwrap_or_default();
+            let root = self
+                .roots
+                .iter()
+                .find(|root| root.name == root_name)
+                .ok_or(SandboxError::UnknownRoot(root_name))?;
+            root.path.join(components.as_path())
+        };
+
+        let canonical = canonicalize_existing(&candidate)?;
+        for root in &self.roots {
+            if let Ok(relative) = canonical.strip_prefix(&root.path) {
+                if relative.as_os_str().is_empty() {
+                    break;
+                }
+                let relative: Vec<String> = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect();
+                return Ok(ResolvedDatabase {
+                    name: format!("{}/{}", root.name, relative.join("/")),
+                    path: canonical,
+                });
+            }
+        }
+
+        Err(SandboxError::OutsideRoots(input.to_string()))
+    }
+}
+
+/// Canonicalize the longest existing ancestor of `path` (resolving symlinks)
+/// and append the components that do not exist yet, which may not include
+/// `..`. A dangling symlink
+/// counts as existing, since creating the database would follow it, and is
+/// rejected because its target cannot be checked against the roots.
+fn canonicalize_existing(path: &Path) -> Result<PathBuf, SandboxError> {
+    let mut existing = path;
+    let mut missing = Vec::new();
+    loop {
+        match existing.symlink_metadata() {
+            Ok(_) => break,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(SandboxError::Io(e.to_string())),
+        }
+        if existing.ends_with("..") {
+            return Err(SandboxError::ParentTraversal(path.display().to_string()));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => break,
+        }
+    }
+
+    let mut canonical = existing.canonicalize().map_err(|e| {
+        let dangling = existing.symlink_metadata().is_ok_and(|meta| meta.file_type().is_symlink());
+        if dangling {
+            SandboxError::OutsideRoots(existing.display().to_string())
+        } else {
+            SandboxError::Io(e.to_string())
+        }
+    })?;
+    for name in missing.into_iter().rev() {
+        canonical.push(name);
+    }
+    Ok(canonical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(label: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("keradb-labs-sandbox-{}-{}", label, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("D:\\data\\a.db"), "/mnt/d/data/a.db");
+        assert_eq!(normalize_path("C:/data/a.db"), "/mnt/c/data/a.db");
+        assert_eq!(normalize_path("/data/a.db"), "/data/a.db");
+    }
+
+    #[test]
+    fn test_resolves_names_and_paths_inside_roots() {
+        let dir = temp_root("resolve");
+        let roots = DatabaseRoots::new(&[("main".to_string(), dir.clone())].into_iter().collect()).unwrap();
+
+        let by_name = roots.resolve("main/sub/users.db").unwrap();
+        assert_eq!(by_name.name, "main/sub/users.db");
+        assert!(by_name.path.ends_with("sub/users.db"));
+
+        let by_path = roots.resolve(&dir.join("users.db").to_string_lossy()).unwrap();
+        assert_eq!(by_path.name, "main/users.db");
+
+        assert!(matches!(roots.resolve("main/../escape.db"), Err(SandboxError::ParentTraversal(_))));
+        assert!(matches!(roots.resolve("other/users.db"), Err(SandboxError::UnknownRoot(_))));
+        assert!(matches!(roots.resolve("/etc/passwd"), Err(SandboxError::OutsideRoots(_))));
+        assert!(matches!(roots.resolve("main"), Err(SandboxError::OutsideRoots(_))));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rejects_symlink_escape() {
+        let dir = temp_root("symlink");
+        let outside = temp_root("outside");
+        std::os::unix::fs::symlink(&outside, dir.join("link")).unwrap();
+        let roots = DatabaseRoots::new(&[("main".to_string(), dir.clone())].into_iter().collect()).unwrap();
+
+        assert!(matches!(roots.resolve("main/link/users.db"), Err(SandboxError::OutsideRoots(_))));
+
+        // A link to a file that does not exist yet would be created outside
+        std::os::unix::fs::symlink(outside.join("evil.db"), dir.join("evil.db")).unwrap();
+        assert!(matches!(roots.resolve("main/evil.db"), Err(SandboxError::OutsideRoots(_))));
+        std::os::unix::fs::symlink(outside.join("missing"), dir.join("dangling")).unwrap();
+        assert!(matches!(roots.resolve("main/dangling/users.db"), Err(SandboxError::OutsideRoots(_))));
+
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::remove_dir_all(&outside).ok();
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_unsandboxed_uses_canonical_paths() {
+        let dir = temp_root("unsandboxed").canonicalize().unwrap();
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let roots = DatabaseRoots::default();
+        let resolved = roots.resolve(&dir.join("sub/../a.db").to_string_lossy()).unwrap();
+        assert_eq!(resolved.path, dir.join("a.db"));
+        assert_eq!(resolved.name, dir.join("a.db").to_string_lossy());
+        assert!(matches!(
+            roots.resolve(&dir.join("missing/../a.db").to_string_lossy()),
+            Err(SandboxError::ParentTraversal(_))
+        ));
+
+        let relative = roots.resolve("a.db").unwrap();
+        assert!(relative.path.is_absolute());
+        assert_eq!(relative.name, relative.path.to_string_lossy());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,1242 @@
+use crate::audit::{self, AuditAction, AuditEntry, AuditFilter, AuditQuery};
+use crate::changes::{ChangeEvent, ChangeFeed, ChangeFilter, ChangeOp, ChangeTarget, ResumePoint, Subscription};
+use crate::connections::{ConnectionProfileUpdate, ConnectionQuery};
+use crate::error::{ApiError, ApiResult, ErrorCode};
+use crate::metrics::{CompactionStats, Granularity, MetricRollup, MetricsFilter, MetricsQuery, MetricsRetention};
+use crate::queries::{self, QueryHistoryQuery, QueryResults, QuerySpec, SavedQueryRequest};
+use crate::sandbox::{DatabaseRoots, ResolvedDatabase, SandboxError};
+use crate::slow_log::{self, OperationDetails, SlowOperationFilter, SlowOperationQuery};
+use crate::system_db::{
+    DatabaseConnection, PerformanceMetric, QueryHistoryEntry, SavedQuery, SlowOperation, SystemDatabase,
+};
+use crate::types::{
+    CollectionInfo, DatabaseInfo, DatabaseStats, VectorCollectionInfoResponse, VectorCollectionSize,
+    VectorDocumentResponse, VectorPage, VectorSearchResultResponse,
+};
+use crate::workspace::{self, ImportReport, WorkspaceBundle, WorkspaceConnection, WorkspaceImport, WorkspaceQuery};
+use keradb::{Database, Distance, VectorConfig};
+use parking_lot::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Owns the open databases and the system database. Every operation takes a
+/// database handle (connection id, alias, logical name or path) and records
+/// its timing in the system database where the front ends used to; slow
+/// ones are also added to the slow operation log. Mutations are published to
+/// the change feed once they succeed.
+pub struct LabsService {
+    databases: RwLock<HashMap<String, Arc<Database>>>,
+    system_db: SystemDatabase,
+    roots: DatabaseRoots,
+    changes: ChangeFeed,
+    slow_op_threshold: Option<Duration>,
+}
+
+impl LabsService {
+    pub fn new(system_db: SystemDatabase, roots: DatabaseRoots) -> Self {
+        Self {
+            databases: RwLock::new(HashMap::new()),
+            system_db,
+            roots,
+            changes: ChangeFeed::default(),
+            slow_op_threshold: Some(slow_log::DEFAULT_SLOW_OP_THRESHOLD),
+        }
+    }
+
+    /// Log operations taking at least `threshold`; None turns the log off
+    pub fn with_slow_op_threshold(mut self, threshold: Option<Duration>) -> Self {
+        self.slow_op_threshold = threshold;
+        self
+    }
+
+    pub fn system_db(&self) -> &SystemDatabase {
+        &self.system_db
+    }
+
+    pub fn roots(&self) -> &DatabaseRoots {
+        &self.roots
+    }
+
+    pub fn changes(&self) -> &ChangeFeed {
+        &self.changes
+    }
+
+    /// Subscribe to changes, optionally limited to one database (any handle)
+    /// and collection. See `ChangeFeed::subscribe` for resuming.
+    pub fn subscribe_changes(
+        &self,
+        db: Option<&str>,
+        collection: Option<&str>,
+        resume_after: Option<ResumePoint>,
+        sink: impl Fn(&ChangeEvent) -> bool + Send + Sync + 'static,
+    ) -> ApiResult<Subscription> {
+        let filter = ChangeFilter {
+            db: db.map(|db| self.database_name(db)).transpose()?,
+            collection: collection.map(str::to_string),
+        };
+        Ok(self.changes.subscribe(filter, resume_after, sink))
+    }
+
+    /// Resolve a path or logical name against the configured roots
+    pub fn resolve(&self, path: &str) -> Result<ResolvedDatabase, SandboxError> {
+        self.roots.resolve(path)
+    }
+
+    /// Map a database handle (connection id, alias, logical name or path) to
+    /// the name databases are tracked under
+    pub fn database_name(&self, db: &str) -> Result<String, SandboxError> {
+        match self.system_db.find_connection(db) {
+            Ok(Some(conn)) => Ok(conn.path),
+            Ok(None) => self.roots.resolve(db).map(|resolved| resolved.name),
+            Err(e) => {
+                log::warn!("Failed to look up connection handle {}: {}", db, e);
+                self.roots.resolve(db).map(|resolved| resolved.name)
+            }
+        }
+    }
+
+    // Look up an open database by handle
+    fn database(&self, db: &str) -> ApiResult<(String, Arc<Database>)> {
+        let name = self.database_name(db)?;
+        let database = self
+            .databases
+            .read()
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| ApiError::db_not_open(&name))?;
+        Ok((name, database))
+    }
+
+    // Record the timing and, for slow operations, what the operation did and
+    // for whom; `details` is only evaluated for slow operations
+    fn record_metric(
+        &self,
+        db_name: &str,
+        operation: &str,
+        start: Instant,
+        details: impl FnOnce() -> OperationDetails,
+    ) {
+        let elapsed = start.elapsed();
+        let duration = elapsed.as_millis() as u64;
+        if let Err(e) = self.system_db.record_metric(db_name, operation, duration) {
+            log::warn!("Failed to record metric: {}", e);
+        }
+
+        if self.slow_op_threshold.is_none_or(|threshold| elapsed < threshold) {
+            return;
+        }
+        let details = details();
+        log::warn!("Slow operation: {} on {} took {} ms", operation, db_name, duration);
+
+        let op = SlowOperation {
+            id: String::new(),
+            database_path: db_name.to_string(),
+            operation: operation.to_string(),
+            collection: details.collection,
+            params: details.params,
+            result_size: details.result_size,
+            duration_ms: duration,
+            timestamp: chrono::Utc::now(),
+            caller: slow_log::current_caller(),
+        };
+        if let Err(e) = self.system_db.record_slow_operation(&op) {
+            log::warn!("Failed to record slow operation: {}", e);
+        }
+    }
+
+    // Append a completed write to the audit log
+    fn audit(&self, entry: AuditEntry) {
+        if let Err(e) = self.system_db.record_audit(&entry) {
+            log::error!("Failed to record audit entry {:?} on {}: {}", entry.action, entry.database_path, e);
+        }
+    }
+
+    // ============================================================
+    // Databases
+    // ============================================================
+
+    /// Open a database, creating it if missing, and register the connection
+    pub fn open_database(&self, db: &ResolvedDatabase, alias: Option<&str>) -> ApiResult<DatabaseInfo> {
+        let start = Instant::now();
+        log::info!("Opening database: {}", db.name);
+
+        let database = match Database::open(&db.path) {
+            Ok(database) => database,
+            Err(_) => Database::create(&db.path)?,
+        };
+
+        let collections = collection_infos(&database);
+        let total_docs: usize = collections.iter().map(|c| c.count).sum();
+
+        self.databases.write().insert(db.name.clone(), Arc::new(database));
+
+        // Register in system database; the connection id is the database handle
+        let id = self.system_db.register_connection(&db.name).map_err(|e| {
+            ApiError::internal(format!("Failed to register connection in system db: {}", e))
+        })?;
+        let alias = self.apply_alias(&id, &db.name, alias)?;
+
+        if let Err(e) = self.system_db.update_connection_stats(&db.name, collections.len(), total_docs) {
+            log::warn!("Failed to update connection stats: {}", e);
+        }
+
+        self.record_metric(&db.name, "open_database", start, || OperationDetails {
+            params: alias.as_ref().map(|alias| serde_json::json!({ "alias": alias })),
+            result_size: Some(total_docs),
+            ..Default::default()
+        });
+
+        Ok(DatabaseInfo {
+            id,
+            alias,
+            path: db.name.clone(),
+            collections,
+        })
+    }
+
+    /// Create a new, empty database and register the connection
+    pub fn create_database(&self, db: &ResolvedDatabase, alias: Option<&str>) -> ApiResult<DatabaseInfo> {
+        let start = Instant::now();
+        log::info!("Creating database: {}", db.name);
+
+        let database = Database::create(&db.path)?;
+        self.databases.write().insert(db.name.clone(), Arc::new(database));
+
+        let id = self.system_db.register_connection(&db.name).map_err(|e| {
+            ApiError::internal(format!("Failed to register connection in system db: {}", e))
+        })?;
+        let alias = self.apply_alias(&id, &db.name, alias)?;
+
+        self.record_metric(&db.name, "create_database", start, OperationDetails::default);
+
+        Ok(DatabaseInfo {
+            id,
+            alias,
+            path: db.name.clone(),
+            collections: Vec::new(),
+        })
+    }
+
+    // Register the requested alias, or return the one already stored for the connection
+    fn apply_alias(&self, id: &str, db_name: &str, requested: Option<&str>) -> ApiResult<Option<String>> {
+        if let Some(alias) = requested {
+            self.system_db
+                .set_alias(db_name, Some(alias))
+                .map_err(|e| ApiError::invalid_request(e.to_string()))?;
+            return Ok(Some(alias.to_string()));
+        }
+
+        let existing = self.system_db.find_connection(id)?;
+        Ok(existing.and_then(|conn| conn.alias))
+    }
+
+    /// Names of the open databases
+    pub fn list_databases(&self) -> Vec<String> {
+        self.databases.read().keys().cloned().collect()
+    }
+
+    /// Close a database; returns its name
+    pub fn close_database(&self, db: &str) -> ApiResult<String> {
+        let db_name = self.database_name(db)?;
+        self.databases.write().remove(&db_name);
+
+        log::info!("Database closed: {}", db_name);
+        Ok(db_name)
+    }
+
+    /// Close a database and delete its file; returns its name
+    pub fn delete_database(&self, db: &str) -> ApiResult<String> {
+        let resolved = self.roots.resolve(&self.database_name(db)?)?;
+        let db_name = resolved.name;
+
+        // What the file held, for the audit log; see the `audit` module
+        let open = self.databases.write().remove(&db_name);
+        let collections = open
+            .or_else(|| Database::open(&resolved.path).ok().map(Arc::new))
+            .map(|database| serde_json::json!({ "collections": collection_infos(&database) }));
+
+        std::fs::remove_file(&resolved.path).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                return ApiError::new(ErrorCode::NotFound, format!("Database not found: {}", db_name));
+            }
+            log::error!("Failed to delete database file {}: {}", db_name, e);
+            ApiError::new(ErrorCode::StorageError, format!("Failed to delete database file: {}", e))
+        })?;
+
+        if let Err(e) = self.system_db.remove_connection(&db_name) {
+            log::warn!("Failed to remove connection from system db: {}", e);
+        }
+
+        self.audit(AuditEntry::new(AuditAction::DeleteDatabase, &db_name).before(collections));
+        log::info!("Database deleted: {}", db_name);
+        Ok(db_name)
+    }
+
+    pub fn collections(&self, db: &str) -> ApiResult<Vec<CollectionInfo>> {
+        let (_, database) = self.database(db)?;
+        Ok(collection_infos(&database))
+    }
+
+    pub fn database_stats(&self, db: &str) -> ApiResult<DatabaseStats> {
+        let (db_name, database) = self.database(db)?;
+        let collections = database.list_collections();
+        let total_documents = collections.iter().map(|(_, count)| count).sum();
+
+        Ok(DatabaseStats {
+            path: db_name,
+            collections: collections.len(),
+            total_documents,
+            collections_detail: collections,
+        })
+    }
+
+    // ============================================================
+    // Documents
+    // ============================================================
+
+    /// Insert a document and sync to disk; returns its id
+    pub fn insert_document(&self, db: &str, collection: &str, document: serde_json::Value) -> ApiResult<String> {
+        let start = Instant::now();
+        let (db_name, database) = self.database(db)?;
+
+        let id = database.insert(collection, document.clone())?;
+        database.sync()?;
+
+        self.record_metric(&db_name, "insert_document", start, || {
+            OperationDetails::collection(collection).params(serde_json::json!({ "id": id }))
+        });
+        self.changes.publish(
+            &db_name,
+            collection,
+            ChangeTarget::Document,
+            ChangeOp::Insert,
+            Some(id.clone()),
+            Some(document),
+        );
+        self.audit(AuditEntry::new(AuditAction::InsertDocument, &db_name).collection(collection).target(&id));
+        Ok(id)
+    }
+
+    pub fn find_documents(
+        &self,
+        db: &str,
+        collection: &str,
+        limit: Option<usize>,
+        skip: Option<usize>,
+    ) -> ApiResult<Vec<serde_json::Value>> {
+        let start = Instant::now();
+        let (db_name, database) = self.database(db)?;
+        let docs: Vec<serde_json::Value> = database
+            .find_all(collection, limit, skip)?
+            .into_iter()
+            .map(|doc| doc.to_value())
+            .collect();
+
+        self.record_metric(&db_name, "find_documents", start, || {
+            OperationDetails::collection(collection)
+                .params(serde_json::json!({ "limit": limit, "skip": skip }))
+                .result_size(docs.len())
+        });
+        Ok(docs)
+    }
+
+    pub fn find_by_id(&self, db: &str, collection: &str, id: &str) -> ApiResult<serde_json::Value> {
+        let (_, database) = self.database(db)?;
+        let doc = database.find_by_id(collection, id)?;
+        Ok(doc.to_value())
+    }
+
+    /// Replace a document; returns the stored document
+    pub fn update_document(
+        &self,
+        db: &str,
+        collection: &str,
+        id: &str,
+        document: serde_json::Value,
+    ) -> ApiResult<serde_json::Value> {
+        let start = Instant::now();
+        let (db_name, database) = self.database(db)?;
+        let before = database.find_by_id(collection, id).ok().map(|doc| doc.to_value());
+        let doc = database.update(collection, id, document)?.to_value();
+
+        self.record_metric(&db_name, "update_document", start, || {
+            OperationDetails::collection(collection).params(serde_json::json!({ "id": id }))
+        });
+        self.changes.publish(
+            &db_name,
+            collection,
+            ChangeTarget::Document,
+            ChangeOp::Update,
+            Some(id.to_string()),
+            Some(doc.clone()),
+        );
+        self.audit(
+            AuditEntry::new(AuditAction::UpdateDocument, &db_name)
+                .collection(collection)
+                .target(id)
+                .before(before),
+        );
+        Ok(doc)
+    }
+
+    /// Delete a document; returns the deleted document
+    pub fn delete_document(&self, db: &str, collection: &str, id: &str) -> ApiResult<serde_json::Value> {
+        let start = Instant::now();
+        let (db_name, database) = self.database(db)?;
+        let doc = database.delete(collection, id)?.to_value();
+
+        self.record_metric(&db_name, "delete_document", start, || {
+            OperationDetails::collection(collection).params(serde_json::json!({ "id": id }))
+        });
+        self.changes.publish(
+            &db_name,
+            collection,
+            ChangeTarget::Document,
+            ChangeOp::Delete,
+            Some(id.to_string()),
+            None,
+        );
+        self.audit(
+            AuditEntry::new(AuditAction::DeleteDocument, &db_name)
+                .collection(collection)
+                .target(id)
+                .before(Some(doc.clone())),
+        );
+        Ok(doc)
+    }
+
+    /// Delete every document in a collection; returns how many were deleted
+    pub fn drop_collection(&self, db: &str, collection: &str) -> ApiResult<usize> {
+        self.drop_collection_with_progress(db, collection, |_, _| true)
+            .map_err(|(_, e)| e)
+    }
+
+    /// Like `drop_collection`, calling `progress(done, total)` after each
+    /// document. Stops early, keeping what was already deleted, once
+    /// `progress` returns false. Errors come with the number of documents
+    /// deleted before them.
+    ///
+    /// Only a drop that deleted every document is published and audited as
+    /// one; otherwise each deleted document is, as if deleted on its own.
+    pub fn drop_collection_with_progress(
+        &self,
+        db: &str,
+        collection: &str,
+        mut progress: impl FnMut(usize, usize) -> bool,
+    ) -> Result<usize, (usize, ApiError)> {
+        let start = Instant::now();
+        let (db_name, database) = self.database(db).map_err(|e| (0, e))?;
+
+        let docs = database.find_all(collection, None, None).map_err(|e| (0, e.into()))?;
+        let total = docs.len();
+        let mut deleted = Vec::new();
+        for (index, doc) in docs.into_iter().enumerate() {
+            match database.delete(collection, &doc.id) {
+                Ok(_) => deleted.push((doc.id.clone(), doc.to_value())),
+                Err(e) => log::warn!("Failed to delete document {}: {}", doc.id, e),
+            }
+            if !progress(index + 1, total) {
+                break;
+            }
+        }
+
+        let synced = database.sync();
+        let deleted_count = deleted.len();
+
+        self.record_metric(&db_name, "drop_collection", start, || {
+            OperationDetails::collection(collection).result_size(deleted_count)
+        });
+        if deleted_count == total {
+            self.changes.publish(&db_name, collection, ChangeTarget::Document, ChangeOp::Drop, None, None);
+            let before = deleted.into_iter().map(|(_, doc)| doc).collect();
+            self.audit(
+                AuditEntry::new(AuditAction::DropCollection, &db_name)
+                    .collection(collection)
+                    .before(Some(serde_json::Value::Array(before)))
+                    .details(serde_json::json!({ "documents_deleted": deleted_count, "documents_total": total })),
+            );
+            log::info!(
+                "Collection '{}' dropped from database: {} ({} documents deleted)",
+                collection, db_name, deleted_count
+            );
+        } else {
+            for (id, doc) in deleted {
+                self.changes.publish(
+                    &db_name,
+                    collection,
+                    ChangeTarget::Document,
+                    ChangeOp::Delete,
+                    Some(id.clone()),
+                    None,
+                );
+                self.audit(
+                    AuditEntry::new(AuditAction::DeleteDocument, &db_name)
+                        .collection(collection)
+                        .target(id)
+                        .before(Some(doc)),
+                );
+            }
+            log::info!(
+                "Collection '{}' partially dropped from database: {} ({} of {} documents deleted)",
+                collection, db_name, deleted_count, total
+            );
+        }
+
+        synced.map_err(|e| (deleted_count, e.into()))?;
+        Ok(deleted_count)
+    }
+
+    // ============================================================
+    // System database
+    // ============================================================
+
+    /// Totals over the databases `include` accepts; see
+    /// `SystemDatabase::get_system_stats`
+    pub fn system_stats(&self, include: impl Fn(&str) -> bool, integrity: bool) -> ApiResult<serde_json::Value> {
+        Ok(self.system_db.get_system_stats(include, integrity)?)
+    }
+
+    /// Registered databases matching the query; by default favorites first,
+    /// then the most recently used
+    pub fn connection_history(&self, query: &ConnectionQuery) -> ApiResult<Vec<DatabaseConnection>> {
+        let mut connections = self.system_db.list_connections()?;
+        query.apply(&mut connections);
+        Ok(connections)
+    }
+
+    /// Edit the alias, color, favorite flag, notes or folder of a registered
+    /// database; it does not need to be open
+    pub fn update_connection_profile(
+        &self,
+        db: &str,
+        update: &ConnectionProfileUpdate,
+    ) -> ApiResult<DatabaseConnection> {
+        let db_name = self.database_name(db)?;
+        let known = self
+            .system_db
+            .list_connections()?
+            .iter()
+            .any(|conn| conn.path == db_name);
+        if !known {
+            return Err(ApiError::new(ErrorCode::NotFound, format!("Connection not found: {}", db)));
+        }
+
+        self.system_db
+            .update_connection_profile(&db_name, update)
+            .map_err(|e| ApiError::invalid_request(e.to_string()))
+    }
+
+    pub fn metrics(&self, db: &str, limit: Option<usize>) -> ApiResult<Vec<PerformanceMetric>> {
+        let db_name = self.database_name(db)?;
+        Ok(self.system_db.get_metrics(&db_name, limit)?)
+    }
+
+    /// Raw samples matching the query, most recent first
+    pub fn query_metrics(
+        &self,
+        query: &MetricsQuery,
+        include: impl Fn(&str) -> bool,
+    ) -> ApiResult<Vec<PerformanceMetric>> {
+        let filter = self.metrics_filter(query)?;
+        Ok(self.system_db.query_metrics(&filter, include, query.limit)?)
+    }
+
+    /// Rollups matching the query, oldest bucket first
+    pub fn metric_rollups(&self, query: &MetricsQuery) -> ApiResult<Vec<MetricRollup>> {
+        let filter = self.metrics_filter(query)?;
+        let granularity = query.granularity.unwrap_or(Granularity::Minute);
+        Ok(self.system_db.metric_rollups(&filter, granularity)?)
+    }
+
+    pub fn compact_metrics(&self, retention: &MetricsRetention) -> ApiResult<CompactionStats> {
+        Ok(self.system_db.compact_metrics(retention, chrono::Utc::now())?)
+    }
+
+    /// Logged slow operations of the databases `include` accepts, most
+    /// recent first
+    pub fn slow_operations(
+        &self,
+        query: &SlowOperationQuery,
+        include: impl Fn(&str) -> bool,
+    ) -> ApiResult<Vec<SlowOperation>> {
+        if let (Some(from), Some(to)) = (query.from, query.to) {
+            if from >= to {
+                return Err(ApiError::invalid_request("`from` must be before `to`"));
+            }
+        }
+
+        let filter = SlowOperationFilter {
+            database_path: query.db.as_deref().map(|db| self.database_name(db)).transpose()?,
+            operation: query.operation.clone(),
+            collection: query.collection.clone(),
+            min_ms: query.min_ms,
+            from: query.from,
+            to: query.to,
+        };
+        Ok(self.system_db.slow_operations(&filter, include, query.limit)?)
+    }
+
+    fn metrics_filter(&self, query: &MetricsQuery) -> ApiResult<MetricsFilter> {
+        if let (Some(from), Some(to)) = (query.from, query.to) {
+            if from >= to {
+                return Err(ApiError::invalid_request("`from` must be before `to`"));
+            }
+        }
+
+        Ok(MetricsFilter {
+            database_path: query.db.as_deref().map(|db| self.database_name(db)).transpose()?,
+            operation: query.operation.clone(),
+            from: query.from,
+            to: query.to,
+        })
+    }
+
+    /// Audit log entries of the databases `include` accepts, most recent
+    /// first
+    pub fn audit_log(&self, query: &AuditQuery, include: impl Fn(&str) -> bool) -> ApiResult<Vec<AuditEntry>> {
+        let filter = self.audit_filter(query)?;
+        let limit = query.limit.unwrap_or(audit::DEFAULT_AUDIT_LIMIT);
+        Ok(self.system_db.audit_log(&filter, include, Some(limit))?)
+    }
+
+    /// Write matching audit entries of the databases `include` accepts to
+    /// `out` as NDJSON, oldest first; returns how many were written
+    pub fn export_audit_log(
+        &self,
+        query: &AuditQuery,
+        include: impl Fn(&str) -> bool,
+        out: &mut impl std::io::Write,
+    ) -> ApiResult<usize> {
+        let filter = self.audit_filter(query)?;
+        let mut entries = self.system_db.audit_log(&filter, include, query.limit)?;
+        entries.reverse();
+
+        for entry in &entries {
+            serde_json::to_writer(&mut *out, entry).map_err(ApiError::internal)?;
+            out.write_all(b"\n")
+                .map_err(|e| ApiError::new(ErrorCode::StorageError, format!("Failed to write audit export: {}", e)))?;
+        }
+        Ok(entries.len())
+    }
+
+    fn audit_filter(&self, query: &AuditQuery) -> ApiResult<AuditFilter> {
+        if let (Some(from), Some(to)) = (query.from, query.to) {
+            if from >= to {
+                return Err(ApiError::invalid_request("`from` must be before `to`"));
+            }
+        }
+
+        Ok(AuditFilter {
+            database_path: query.db.as_deref().map(|db| self.database_name(db)).transpose()?,
+            action: query.action,
+            collection: query.collection.clone(),
+            actor: query.actor.clone(),
+            from: query.from,
+            to: query.to,
+        })
+    }
+
+    /// Close a database and forget it; returns its name
+    pub fn remove_connection(&self, db: &str) -> ApiResult<String> {
+        let db_name = self.database_name(db)?;
+
+        self.databases.write().remove(&db_name);
+        self.system_db.remove_connection(&db_name)?;
+
+        Ok(db_name)
+    }
+
+    // ============================================================
+    // Vectors
+    // ============================================================
+
+    pub fn create_vector_collection(
+        &self,
+        db: &str,
+        name: &str,
+        dimensions: usize,
+        distance: &str,
+        m: Option<usize>,
+    ) -> ApiResult<()> {
+        let (db_name, database) = self.database(db)?;
+
+        if dimensions == 0 {
+            return Err(ApiError::invalid_request("Vector dimensions must be greater than 0"));
+        }
+
+        let mut config = VectorConfig::new(dimensions).with_distance(parse_distance(distance));
+        if let Some(m) = m {
+            config = config.with_m(m);
+        }
+
+        database.create_vector_collection(name, config)?;
+        self.audit(
+            AuditEntry::new(AuditAction::CreateVectorCollection, &db_name)
+                .collection(name)
+                .details(serde_json::json!({ "dimensions": dimensions, "distance": distance, "m": m })),
+        );
+
+        log::info!("Vector collection '{}' created in database: {}", name, db_name);
+        Ok(())
+    }
+
+    pub fn vector_collections(&self, db: &str) -> ApiResult<Vec<VectorCollectionInfoResponse>> {
+        let (_, database) = self.database(db)?;
+
+        let collections = database
+            .list_vector_collections()
+            .into_iter()
+            .filter_map(|(name, count)| {
+                database.vector_stats(&name).ok().map(|stats| VectorCollectionInfoResponse {
+                    name,
+                    count,
+                    dimensions: stats.dimensions,
+                    distance: stats.distance.name().to_string(),
+                })
+            })
+            .collect();
+
+        Ok(collections)
+    }
+
+    /// Vector count and memory use of every vector collection
+    pub fn vector_collection_sizes(&self, db: &str) -> ApiResult<Vec<VectorCollectionSize>> {
+        let (_, database) = self.database(db)?;
+
+        let sizes = database
+            .list_vector_collections()
+            .into_iter()
+            .filter_map(|(name, _)| {
+                database.vector_stats(&name).ok().map(|stats| VectorCollectionSize {
+                    name,
+                    vector_count: stats.vector_count,
+                    memory_bytes: stats.memory_bytes,
+                })
+            })
+            .collect();
+
+        Ok(sizes)
+    }
+
+    pub fn vector_collection_stats(&self, db: &str, collection: &str) -> ApiResult<serde_json::Value> {
+        let (_, database) = self.database(db)?;
+        let stats = database.vector_stats(collection)?;
+
+        Ok(serde_json::json!({
+            "name": stats.name,
+            "vector_count": stats.vector_count,
+            "dimensions": stats.dimensions,
+            "distance": stats.distance.name(),
+            "memory_bytes": stats.memory_bytes,
+            "hnsw_m": stats.hnsw_layers,
+            "lazy_embedding": stats.lazy_embedding,
+            "compression_mode": format!("{:?}", stats.compression_mode)
+        }))
+    }
+
+    /// Drop a vector collection; returns whether it existed
+    pub fn drop_vector_collection(&self, db: &str, collection: &str) -> ApiResult<bool> {
+        let (db_name, database) = self.database(db)?;
+        // The configuration, not the vectors; see the `audit` module
+        let stats = database.vector_stats(collection).ok();
+        let vector_count = stats.as_ref().map(|stats| stats.vector_count);
+        let before = stats.map(|stats| {
+            serde_json::json!({
+                "dimensions": stats.dimensions,
+                "distance": stats.distance.name(),
+                "vector_count": stats.vector_count,
+            })
+        });
+        let dropped = database.drop_vector_collection(collection)?;
+        if dropped {
+            self.changes.publish(&db_name, collection, ChangeTarget::Vector, ChangeOp::Drop, None, None);
+            self.audit(
+                AuditEntry::new(AuditAction::DropVectorCollection, &db_name)
+                    .collection(collection)
+                    .before(before)
+                    .details(serde_json::json!({ "vectors_deleted": vector_count })),
+            );
+        }
+
+        log::info!("Vector collection '{}' dropped from database: {}", collection, db_name);
+        Ok(dropped)
+    }
+
+    /// Insert a vector after checking its dimensions; returns its id
+    pub fn insert_vector(
+        &self,
+        db: &str,
+        collection: &str,
+        vector: Vec<f32>,
+        metadata: Option<serde_json::Value>,
+    ) -> ApiResult<u64> {
+        let start = Instant::now();
+        let (db_name, database) = self.database(db)?;
+
+        check_dimensions(&database, collection, &vector)?;
+        let dimensions = vector.len();
+        let id = database.insert_vector(collection, vector, metadata.clone())?;
+
+        self.record_metric(&db_name, "insert_vector", start, || {
+            OperationDetails::collection(collection).params(serde_json::json!({ "id": id, "dimensions": dimensions }))
+        });
+        self.changes.publish(
+            &db_name,
+            collection,
+            ChangeTarget::Vector,
+            ChangeOp::Insert,
+            Some(id.to_string()),
+            metadata,
+        );
+        self.audit(AuditEntry::new(AuditAction::InsertVector, &db_name).collection(collection).target(id));
+        Ok(id)
+    }
+
+    /// Page through a collection; defaults to the first 100 vectors
+    pub fn vectors(
+        &self,
+        db: &str,
+        collection: &str,
+        limit: Option<usize>,
+        skip: Option<usize>,
+    ) -> ApiResult<VectorPage> {
+        let start = Instant::now();
+        let (db_name, database) = self.database(db)?;
+        let stats = database.vector_stats(collection)?;
+
+        let limit = limit.unwrap_or(100);
+        let skip = skip.unwrap_or(0);
+
+        // keradb has no iterator over vectors, so probe ids; ids are assigned
+        // sequentially and may have gaps left by deletes
+        let vectors = (0..stats.vector_count as u64 + skip as u64 + 100)
+            .filter_map(|id| database.get_vector(collection, id).ok().flatten())
+            .skip(skip)
+            .take(limit)
+            .map(|doc| vector_document(doc.id, doc.embedding, doc.metadata))
+            .collect::<Vec<_>>();
+
+        self.record_metric(&db_name, "get_vectors", start, || {
+            OperationDetails::collection(collection)
+                .params(serde_json::json!({ "limit": limit, "skip": skip }))
+                .result_size(vectors.len())
+        });
+        Ok(VectorPage {
+            vectors,
+            total: stats.vector_count,
+            limit,
+            skip,
+        })
+    }
+
+    /// Nearest-neighbour search, best match first
+    pub fn vector_search(
+        &self,
+        db: &str,
+        collection: &str,
+        vector: &[f32],
+        k: usize,
+    ) -> ApiResult<Vec<VectorSearchResultResponse>> {
+        let start = Instant::now();
+        let (db_name, database) = self.database(db)?;
+
+        check_dimensions(&database, collection, vector)?;
+        let results = database.vector_search(collection, vector, k)?;
+
+        let response = results
+            .into_iter()
+            .map(|r| {
+                let doc = vector_document(r.document.id, r.document.embedding, r.document.metadata);
+                VectorSearchResultResponse {
+                    id: doc.id,
+                    score: r.score,
+                    vector: doc.vector,
+                    metadata: doc.metadata,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.record_metric(&db_name, "vector_search", start, || {
+            OperationDetails::collection(collection)
+                .params(serde_json::json!({ "k": k, "dimensions": vector.len() }))
+                .result_size(response.len())
+        });
+        Ok(response)
+    }
+
+    pub fn get_vector(&self, db: &str, collection: &str, id: u64) -> ApiResult<VectorDocumentResponse> {
+        let (_, database) = self.database(db)?;
+        let doc = database
+            .get_vector(collection, id)?
+            .ok_or_else(|| ApiError::new(ErrorCode::VectorNotFound, format!("Vector not found: {}", id)))?;
+        Ok(vector_document(doc.id, doc.embedding, doc.metadata))
+    }
+
+    /// Delete a vector; returns whether it existed
+    pub fn delete_vector(&self, db: &str, collection: &str, id: u64) -> ApiResult<bool> {
+        let (db_name, database) = self.database(db)?;
+        let before = database
+            .get_vector(collection, id)
+            .ok()
+            .flatten()
+            .and_then(|doc| serde_json::to_value(vector_document(doc.id, doc.embedding, doc.metadata)).ok());
+        let deleted = database.delete_vector(collection, id)?;
+        if deleted {
+            self.changes.publish(
+                &db_name,
+                collection,
+                ChangeTarget::Vector,
+                ChangeOp::Delete,
+                Some(id.to_string()),
+                None,
+            );
+            self.audit(
+                AuditEntry::new(AuditAction::DeleteVector, &db_name)
+                    .collection(collection)
+                    .target(id)
+                    .before(before),
+            );
+        }
+        Ok(deleted)
+    }
+
+    // ============================================================
+    // Saved queries and query history
+    // ============================================================
+
+    /// Run a query and record it in the query history
+    pub fn run_query(&self, db: &str, query: &QuerySpec) -> ApiResult<QueryResults> {
+        self.execute_query(db, query, None)
+    }
+
+    /// Saved queries of a database, by name
+    pub fn saved_queries(&self, db: &str) -> ApiResult<Vec<SavedQuery>> {
+        let db_name = self.database_name(db)?;
+        Ok(self.system_db.saved_queries(Some(&db_name))?)
+    }
+
+    pub fn create_saved_query(&self, db: &str, request: &SavedQueryRequest) -> ApiResult<SavedQuery> {
+        request.validate().map_err(ApiError::invalid_request)?;
+        let now = chrono::Utc::now();
+        let mut saved = SavedQuery {
+            id: String::new(), // Will be generated by insert
+            database_path: self.database_name(db)?,
+            name: request.name.trim().to_string(),
+            description: request.description.clone().filter(|d| !d.is_empty()),
+            query: request.query.clone(),
+            created_at: now,
+            updated_at: now,
+        };
+        saved.id = self
+            .system_db
+            .create_saved_query(&saved)
+            .map_err(|e| ApiError::invalid_request(e.to_string()))?;
+        Ok(saved)
+    }
+
+    /// Replace the name, description and query of a saved query
+    pub fn update_saved_query(&self, db: &str, id: &str, request: &SavedQueryRequest) -> ApiResult<SavedQuery> {
+        request.validate().map_err(ApiError::invalid_request)?;
+        let saved = SavedQuery {
+            name: request.name.trim().to_string(),
+            description: request.description.clone().filter(|d| !d.is_empty()),
+            query: request.query.clone(),
+            updated_at: chrono::Utc::now(),
+            ..self.saved_query(db, id)?
+        };
+        self.system_db
+            .save_saved_query(&saved)
+            .map_err(|e| ApiError::invalid_request(e.to_string()))?;
+        Ok(saved)
+    }
+
+    pub fn delete_saved_query(&self, db: &str, id: &str) -> ApiResult<SavedQuery> {
+        let saved = self.saved_query(db, id)?;
+        self.system_db.delete_saved_query(&saved.id)?;
+        Ok(saved)
+    }
+
+    /// Run a saved query; the history entry links back to it
+    pub fn run_saved_query(&self, db: &str, id: &str) -> ApiResult<QueryResults> {
+        let saved = self.saved_query(db, id)?;
+        self.execute_query(&saved.database_path, &saved.query, Some(&saved.id))
+    }
+
+    /// Queries run against a database, most recent first
+    pub fn query_history(&self, db: &str, query: &QueryHistoryQuery) -> ApiResult<Vec<QueryHistoryEntry>> {
+        let db_name = self.database_name(db)?;
+        let limit = query.limit.unwrap_or(queries::DEFAULT_HISTORY_LIMIT);
+        Ok(self.system_db.query_history(Some(&db_name), Some(limit))?)
+    }
+
+    /// Run the query of a history entry again
+    pub fn rerun_query(&self, db: &str, id: &str) -> ApiResult<QueryResults> {
+        let db_name = self.database_name(db)?;
+        let entry = self
+            .system_db
+            .query_history(Some(&db_name), None)?
+            .into_iter()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| ApiError::new(ErrorCode::NotFound, format!("Query history entry not found: {}", id)))?;
+        self.execute_query(&db_name, &entry.query, entry.saved_query_id.as_deref())
+    }
+
+    /// Delete the query history of a database; returns how many entries
+    /// were removed
+    pub fn clear_query_history(&self, db: &str) -> ApiResult<usize> {
+        let db_name = self.database_name(db)?;
+        Ok(self.system_db.clear_query_history(&db_name)?)
+    }
+
+    // Look up a saved query of the database `db`
+    fn saved_query(&self, db: &str, id: &str) -> ApiResult<SavedQuery> {
+        let db_name = self.database_name(db)?;
+        self.system_db
+            .saved_queries(Some(&db_name))?
+            .into_iter()
+            .find(|saved| saved.id == id)
+            .ok_or_else(|| ApiError::new(ErrorCode::NotFound, format!("Saved query not found: {}", id)))
+    }
+
+    fn execute_query(&self, db: &str, query: &QuerySpec, saved_query_id: Option<&str>) -> ApiResult<QueryResults> {
+        query.validate().map_err(ApiError::invalid_request)?;
+        let executed_at = chrono::Utc::now();
+        let start = Instant::now();
+        let (db_name, database) = self.database(db)?;
+
+        let results = match query {
+            QuerySpec::Documents {
+                collection,
+                filter,
+                sort,
+                projection,
+                limit,
+                skip,
+            } => {
+                let all = database
+                    .find_all(collection, None, None)?
+                    .into_iter()
+                    .map(|doc| doc.to_value())
+                    .collect();
+                let (documents, matched) =
+                    queries::select_documents(all, filter.as_ref(), sort, projection.as_deref(), *limit, *skip);
+
+                self.record_metric(&db_name, "query_documents", start, || {
+                    OperationDetails::collection(collection)
+                        .params(serde_json::json!({
+                            "filter": filter,
+                            "sort": sort,
+                            "limit": limit,
+                            "skip": skip,
+                        }))
+                        .result_size(documents.len())
+                });
+                QueryResults::Documents { documents, matched }
+            }
+            QuerySpec::VectorSearch { collection, vector, k } => QueryResults::VectorSearch {
+                results: self.vector_search(&db_name, collection, vector, *k)?,
+            },
+        };
+
+        let entry = QueryHistoryEntry {
+            id: String::new(),
+            database_path: db_name,
+            query: query.clone(),
+            saved_query_id: saved_query_id.map(str::to_string),
+            executed_at,
+            duration_ms: start.elapsed().as_millis() as u64,
+            result_count: results.len(),
+        };
+        if let Err(e) = self.system_db.record_query(&entry) {
+            log::warn!("Failed to record query history: {}", e);
+        }
+        Ok(results)
+    }
+
+    // ============================================================
+    // Workspace bundles
+    // ============================================================
+
+    /// Connections, profile settings and saved queries of the databases
+    /// `include` accepts, as a portable bundle
+    pub fn export_workspace(&self, include: impl Fn(&str) -> bool) -> ApiResult<WorkspaceBundle> {
+        let connections = self
+            .system_db
+            .list_connections()?
+            .into_iter()
+            .filter(|conn| include(&conn.path))
+            .map(|conn| WorkspaceConnection {
+                path: conn.path,
+                alias: conn.alias,
+                color: conn.color,
+                favorite: conn.favorite,
+                notes: conn.notes,
+                folder: conn.folder,
+            })
+            .collect();
+        let saved_queries = self
+            .system_db
+            .saved_queries(None)?
+            .into_iter()
+            .filter(|query| include(&query.database_path))
+            .map(|query| WorkspaceQuery {
+                database_path: query.database_path,
+                name: query.name,
+                description: query.description,
+                query: query.query,
+            })
+            .collect();
+        Ok(WorkspaceBundle::new(connections, saved_queries))
+    }
+
+    /// Register the connections of a bundle under their remapped paths,
+    /// apply their profile settings and add their saved queries. Databases
+    /// are not opened. `include` is checked against the remapped names;
+    /// items it rejects or that fail are reported and skipped.
+    pub fn import_workspace(
+        &self,
+        import: &WorkspaceImport,
+        include: impl Fn(&str) -> bool,
+    ) -> ApiResult<ImportReport> {
+        import.bundle.validate().map_err(ApiError::invalid_request)?;
+
+        let resolve = |path: &str| -> Result<ResolvedDatabase, String> {
+            let resolved = self
+                .resolve(&workspace::remap_path(path, &import.remap))
+                .map_err(|e| e.to_string())?;
+            if !include(&resolved.name) {
+                return Err(format!("Access to database {} is not allowed", resolved.name));
+            }
+            Ok(resolved)
+        };
+
+        let mut report = ImportReport::default();
+        let mut known: HashSet<String> = self.system_db.list_connections()?.into_iter().map(|c| c.path).collect();
+        // Bundle path -> name the database is tracked under here
+        let mut names = HashMap::new();
+
+        for conn in &import.bundle.connections {
+            let resolved = match resolve(&conn.path) {
+                Ok(resolved) => resolved,
+                Err(e) => {
+                    report.skip(conn.path.as_str(), e);
+                    continue;
+                }
+            };
+            if known.insert(resolved.name.clone()) {
+                self.system_db.register_connection(&resolved.name)?;
+                report.connections_created += 1;
+            } else {
+                report.connections_updated += 1;
+            }
+            if !resolved.path.exists() {
+                report.missing.push(resolved.name.clone());
+            }
+
+            // A favorite is never unset by an import
+            let mut update = ConnectionProfileUpdate {
+                alias: conn.alias.clone(),
+                color: conn.color.clone(),
+                favorite: conn.favorite.then_some(true),
+                notes: conn.notes.clone(),
+                folder: conn.folder.clone(),
+            };
+            if let Some(alias) = &conn.alias {
+                if let Some(other) = self.system_db.find_connection(alias)?.filter(|c| c.path != resolved.name) {
+                    report.skip(
+                        conn.path.as_str(),
+                        format!("Alias '{}' is already used by {}", alias, other.path),
+                    );
+                    update.alias = None;
+                }
+            }
+            if let Err(e) = self.system_db.update_connection_profile(&resolved.name, &update) {
+                report.skip(conn.path.as_str(), e);
+            }
+            names.insert(conn.path.as_str(), resolved.name);
+        }
+
+        for query in &import.bundle.saved_queries {
+            let item = format!("{}: {}", query.database_path, query.name);
+            let database_path = match names.get(query.database_path.as_str()) {
+                Some(name) => name.clone(),
+                None => match resolve(&query.database_path) {
+                    Ok(resolved) => resolved.name,
+                    Err(e) => {
+                        report.skip(item, e);
+                        continue;
+                    }
+                },
+            };
+
+            let request = SavedQueryRequest {
+                name: query.name.clone(),
+                description: query.description.clone(),
+                query: query.query.clone(),
+            };
+            if let Err(e) = request.validate() {
+                report.skip(item, e);
+                continue;
+            }
+            let now = chrono::Utc::now();
+            let saved = SavedQuery {
+                id: String::new(), // Will be generated by insert
+                database_path,
+                name: request.name.trim().to_string(),
+                description: request.description.filter(|d| !d.is_empty()),
+                query: request.query,
+                created_at: now,
+                updated_at: now,
+            };
+            match self.system_db.create_saved_query(&saved) {
+                Ok(_) => report.saved_queries_created += 1,
+                Err(e) => report.skip(item, e),
+            }
+        }
+
+        log::info!(
+            "Imported workspace: {} connections created, {} updated, {} saved queries, {} skipped",
+            report.connections_created,
+            report.connections_updated,
+            report.saved_queries_created,
+            report.skipped.len()
+        );
+        Ok(report)
+    }
+}
+
+fn collection_infos(database: &Database) -> Vec<CollectionInfo> {
+    database
+        .list_collections()
+        .into_iter()
+        .map(|(name, count)| CollectionInfo { name, count })
+        .collect()
+}
+
+fn parse_distance(s: &str) -> Distance {
+    match s.to_lowercase().as_str() {
+        "euclidean" | "l2" => Distance::Euclidean,
+        "dot" | "dot_product" | "dotproduct" => Distance::DotProduct,
+        "manhattan" | "l1" => Distance::Manhattan,
+        _ => Distance::Cosine, // Default
+    }
+}
+
+// Reject vectors whose length does not match the collection before they reach keradb
+fn check_dimensions(database: &Database, collection: &str, vector: &[f32]) -> ApiResult<()> {
+    let stats = database.vector_stats(collection)?;
+    if stats.dimensions != vector.len() {
+        return Err(ApiError::dimension_mismatch(stats.dimensions, vector.len()));
+    }
+    Ok(())
+}
+
+fn vector_document(id: u64, embedding: Option<Vec<f32>>, metadata: serde_json::Value) -> VectorDocumentResponse {
+    VectorDocumentResponse {
+        id,
+        vector: embedding.unwrap_or_default(),
+        metadata: if metadata.is_null() { None } else { Some(metadata) },
+        created_at: 0,
+    }
+}
@@ -0,0 +1,144 @@
+//! Slow operation log. Operations slower than the threshold set on
+//! `LabsService` are stored in the system database along with their
+//! parameters, result size and the caller that was current on the thread
+//! running them.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::time::Duration;
+
+/// Threshold used until a front end configures its own
+pub const DEFAULT_SLOW_OP_THRESHOLD: Duration = Duration::from_secs(1);
+
+thread_local! {
+    static CALLER: RefCell<Option<Caller>> = const { RefCell::new(None) };
+}
+
+/// Who asked for an operation, as far as the front end knows
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Caller {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+}
+
+/// Run `f` with `caller` as the current caller of this thread; the previous
+/// caller is restored afterwards
+pub fn with_caller<R>(caller: &Caller, f: impl FnOnce() -> R) -> R {
+    // Restores the previous caller even if `f` panics
+    struct Restore(Option<Caller>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CALLER.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let _restore = Restore(CALLER.with(|current| current.replace(Some(caller.clone()))));
+    f()
+}
+
+/// The caller set by the innermost enclosing `with_caller`
+pub fn current_caller() -> Option<Caller> {
+    CALLER.with(|current| current.borrow().clone())
+}
+
+/// What an operation worked on, recorded only when it turns out slow
+#[derive(Debug, Clone, Default)]
+pub struct OperationDetails {
+    pub collection: Option<String>,
+    /// Paging, ids and search sizes; never documents or vectors
+    pub params: Option<serde_json::Value>,
+    /// Documents or vectors returned or affected
+    pub result_size: Option<usize>,
+}
+
+impl OperationDetails {
+    pub fn collection(collection: &str) -> Self {
+        Self {
+            collection: Some(collection.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn params(mut self, params: serde_json::Value) -> Self {
+        self.params = Some(params);
+        self
+    }
+
+    pub fn result_size(mut self, size: usize) -> Self {
+        self.result_size = Some(size);
+        self
+    }
+}
+
+/// Selects logged operations; unset fields match everything. `db` accepts
+/// any database handle.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct SlowOperationQuery {
+    /// Database id, alias, logical name or path
+    pub db: Option<String>,
+    pub operation: Option<String>,
+    pub collection: Option<String>,
+    /// Only operations that took at least this long
+    pub min_ms: Option<u64>,
+    /// Inclusive start (RFC 3339)
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive end (RFC 3339)
+    pub to: Option<DateTime<Utc>>,
+    /// Maximum number of entries, most recent first
+    pub limit: Option<usize>,
+}
+
+/// `SlowOperationQuery` with the database resolved to the name operations
+/// are recorded under
+#[derive(Debug, Clone, Default)]
+pub struct SlowOperationFilter {
+    pub database_path: Option<String>,
+    pub operation: Option<String>,
+    pub collection: Option<String>,
+    pub min_ms: Option<u64>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl SlowOperationFilter {
+    pub fn matches(&self, op: &crate::system_db::SlowOperation) -> bool {
+        self.database_path.as_ref().is_none_or(|db| *db == op.database_path)
+            && self.operation.as_ref().is_none_or(|name| *name == op.operation)
+            && self.collection.as_ref().is_none_or(|c| op.collection.as_ref() == Some(c))
+            && self.min_ms.is_none_or(|min| op.duration_ms >= min)
+            && self.from.is_none_or(|from| op.timestamp >= from)
+            && self.to.is_none_or(|to| op.timestamp < to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caller_is_scoped() {
+        let outer = Caller {
+            ip: Some("10.0.0.1".to_string()),
+            ..Default::default()
+        };
+        let inner = Caller {
+            token_name: Some("ci".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(current_caller(), None);
+        with_caller(&outer, || {
+            with_caller(&inner, || assert_eq!(current_caller().as_ref(), Some(&inner)));
+            assert_eq!(current_caller().as_ref(), Some(&outer));
+        });
+        assert_eq!(current_caller(), None);
+    }
+}
@@ -0,0 +1,1664 @@
+use crate::audit::{AuditEntry, AuditFilter};
+use crate::connections::{self, ConnectionProfileUpdate};
+use crate::journal::{Journal, JournalEntry};
+use crate::metrics::{self, CompactionStats, Granularity, MetricRollup, MetricsFilter, MetricsRetention};
+use crate::migrations;
+use crate::queries::QuerySpec;
+use crate::slow_log::{Caller, SlowOperationFilter};
+use chrono::{DateTime, Utc};
+use keradb::Database;
+use parking_lot::{Condvar, Mutex};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const SYSTEM_DB_DIR: &str = ".keradb";
+/// File name of the REST backend's system database
+pub const SYSTEM_DB_NAME: &str = ".keradb_system.db";
+/// Subdirectory of the home directory holding named profiles
+const PROFILES_DIR: &str = "profiles";
+
+/// Directory system databases are kept in, instead of `~/.keradb`
+pub const HOME_ENV: &str = "KERADB_HOME";
+/// Named profile to use when none is given
+pub const PROFILE_ENV: &str = "KERADB_PROFILE";
+
+pub(crate) const CONNECTIONS_COLLECTION: &str = "connections";
+const METRICS_COLLECTION: &str = "metrics";
+const ROLLUPS_COLLECTION: &str = "metric_rollups";
+const TOKENS_COLLECTION: &str = "api_tokens";
+const JOBS_COLLECTION: &str = "jobs";
+const SLOW_OPS_COLLECTION: &str = "slow_operations";
+const SAVED_QUERIES_COLLECTION: &str = "saved_queries";
+const QUERY_HISTORY_COLLECTION: &str = "query_history";
+const AUDIT_COLLECTION: &str = "audit_log";
+
+/// Every collection of typed records, for migrations and integrity checks
+pub(crate) const COLLECTIONS: [&str; 9] = [
+    CONNECTIONS_COLLECTION,
+    METRICS_COLLECTION,
+    ROLLUPS_COLLECTION,
+    TOKENS_COLLECTION,
+    JOBS_COLLECTION,
+    SLOW_OPS_COLLECTION,
+    SAVED_QUERIES_COLLECTION,
+    QUERY_HISTORY_COLLECTION,
+    AUDIT_COLLECTION,
+];
+
+/// Deferred writes are synced once this many are pending...
+const FLUSH_PENDING: usize = 100;
+/// ...or at the latest this long after the previous check
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Records read per page when scanning a collection
+const SCAN_PAGE: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DatabaseConnection {
+    pub id: String,
+    pub path: String,
+    /// URL-safe name that can be used in place of the id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_accessed: DateTime<Utc>,
+    pub access_count: u64,
+    pub collections_count: usize,
+    pub total_documents: usize,
+    /// `#rgb`, `#rrggbb` or a color name, used to tag the connection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub favorite: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Grouping folder, `/`-separated for nesting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PerformanceMetric {
+    pub id: String,
+    pub database_path: String,
+    pub operation: String,
+    pub duration_ms: u64,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// An operation that took longer than the slow operation threshold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SlowOperation {
+    pub id: String,
+    pub database_path: String,
+    pub operation: String,
+    pub collection: Option<String>,
+    /// Paging, ids and search sizes the operation was called with
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub params: Option<serde_json::Value>,
+    /// Documents or vectors returned or affected
+    pub result_size: Option<usize>,
+    pub duration_ms: u64,
+    pub timestamp: DateTime<Utc>,
+    /// Token and address of the request; unset for the desktop app
+    pub caller: Option<Caller>,
+}
+
+/// A query stored by name for one database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SavedQuery {
+    pub id: String,
+    pub database_path: String,
+    /// Unique per database
+    pub name: String,
+    pub description: Option<String>,
+    pub query: QuerySpec,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// One run of a query, recorded automatically
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct QueryHistoryEntry {
+    pub id: String,
+    pub database_path: String,
+    pub query: QuerySpec,
+    /// Set when a saved query was run
+    pub saved_query_id: Option<String>,
+    pub executed_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    /// Documents or search results returned
+    pub result_count: usize,
+}
+
+/// What an API token is allowed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenScope {
+    ReadOnly,
+    ReadWrite,
+    Admin,
+}
+
+/// Access level a route requires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    Read,
+    Write,
+    Admin,
+}
+
+impl TokenScope {
+    /// Whether a token with this scope may perform an operation needing `permission`
+    pub fn allows(self, permission: Permission) -> bool {
+        match permission {
+            Permission::Read => true,
+            Permission::Write => self >= TokenScope::ReadWrite,
+            Permission::Admin => self == TokenScope::Admin,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    /// Hex-encoded SHA-256 of the token; the token itself is never stored
+    pub token_hash: String,
+    pub scope: TokenScope,
+    /// Database paths this token may access; empty means all databases
+    pub databases: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+/// A background job as recorded in the job history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Job {
+    pub id: String,
+    /// What the job does, e.g. `drop_collection`
+    pub kind: String,
+    pub database: String,
+    pub collection: String,
+    pub status: JobStatus,
+    /// Items processed so far and in total
+    pub done: usize,
+    pub total: usize,
+    /// Outcome of the job, also set for partially completed jobs
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// A stored record that does not deserialize into its type, typically one
+/// written by a newer or much older version without a migration
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MalformedRecord {
+    pub collection: String,
+    /// Document id, unset when the record has none
+    pub id: Option<String>,
+    pub error: String,
+}
+
+/// Where a system database is stored: a home directory and, for named
+/// profiles, a subdirectory of it. Every profile has its own connection
+/// history, metrics, tokens and audit log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemDbLocation {
+    pub home: PathBuf,
+    /// Unset for the default profile, stored directly in `home`
+    pub profile: Option<String>,
+    file_name: String,
+}
+
+impl SystemDbLocation {
+    /// `$KERADB_HOME`, or `~/<dir_name>` when it is unset, with the profile
+    /// named by `$KERADB_PROFILE`
+    pub fn from_env(dir_name: &str, file_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let home = match std::env::var_os(HOME_ENV).filter(|home| !home.is_empty()) {
+            Some(home) => PathBuf::from(home),
+            None => user_home()?.join(dir_name),
+        };
+        let profile = std::env::var(PROFILE_ENV).ok().filter(|profile| !profile.is_empty());
+        Self::new(home, file_name).with_profile(profile.as_deref())
+    }
+
+    /// The default profile in `home`
+    pub fn new(home: impl Into<PathBuf>, file_name: &str) -> Self {
+        Self {
+            home: home.into(),
+            profile: None,
+            file_name: file_name.to_string(),
+        }
+    }
+
+    /// Switch to a named profile, or back to the default one
+    pub fn with_profile(mut self, profile: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(profile) = profile {
+            if !is_valid_alias(profile) || profile.starts_with('.') {
+                return Err(format!(
+                    "Invalid profile '{}': use up to 64 letters, digits, '-', '_' or '.', not starting with '.'",
+                    profile
+                )
+                .into());
+            }
+        }
+        self.profile = profile.map(str::to_string);
+        Ok(self)
+    }
+
+    /// Path of the system database file
+    pub fn path(&self) -> PathBuf {
+        match &self.profile {
+            Some(profile) => self.home.join(PROFILES_DIR).join(profile).join(&self.file_name),
+            None => self.home.join(&self.file_name),
+        }
+    }
+
+    /// Named profiles that have a system database in this home, sorted
+    pub fn profiles(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let dir = self.home.join(PROFILES_DIR);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut profiles = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.path().join(&self.file_name).exists() {
+                if let Some(name) = entry.file_name().to_str() {
+                    profiles.push(name.to_string());
+                }
+            }
+        }
+        profiles.sort();
+        Ok(profiles)
+    }
+}
+
+/// Removes an ephemeral system database's directory once everything using
+/// it has been dropped
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.0) {
+            log::warn!("Failed to remove {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// Metrics, query history, audit entries and connection records are
+/// deferred writes: applied in memory and journaled at once, synced to disk
+/// by a background flusher. Everything else is synced before the call
+/// returns.
+pub struct SystemDatabase {
+    db: Arc<Database>,
+    writer: Arc<Writer>,
+    flusher: Option<std::thread::JoinHandle<()>>,
+    /// Malformed records already logged, by collection and document id
+    reported: Mutex<HashSet<(String, Option<String>)>>,
+    /// Active tokens by hash, loaded on first use and kept up to date by
+    /// `create_token` and `revoke_token`
+    active_tokens: Mutex<Option<HashMap<String, ApiToken>>>,
+    /// Held from reading a connection until its new state is journaled, so
+    /// concurrent updates cannot overwrite each other
+    connections: Mutex<()>,
+    /// Set for ephemeral databases; last so it is dropped after the database
+    temp_dir: Option<TempDir>,
+}
+
+struct Writer {
+    db: Arc<Database>,
+    journal: Mutex<Journal>,
+    /// Deferred writes since the last flush
+    pending: Mutex<usize>,
+    wake: Condvar,
+    /// Held for a whole flush so two flushes never retire the journal at once
+    flushing: Mutex<()>,
+    stopped: AtomicBool,
+}
+
+impl Writer {
+    /// Apply and journal `entry`; returns the id of a new connection
+    fn write(&self, mut entry: JournalEntry) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let mut journal = self.journal.lock();
+        let id = apply(&self.db, &entry)?;
+        // Later entries refer to the new connection by this id
+        if let (JournalEntry::NewConnection(conn), Some(id)) = (&mut entry, &id) {
+            conn.id = id.clone();
+        }
+        journal.append(&entry)?;
+
+        let mut pending = self.pending.lock();
+        *pending += 1;
+        if *pending >= FLUSH_PENDING {
+            self.wake.notify_one();
+        }
+        Ok(id)
+    }
+
+    fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let _flushing = self.flushing.lock();
+        let retired = {
+            let mut journal = self.journal.lock();
+            let mut pending = self.pending.lock();
+            if *pending == 0 {
+                return Ok(());
+            }
+            *pending = 0;
+            journal.rotate()?
+        };
+
+        if let Err(e) = self.db.sync() {
+            // The retired entries stay on disk and are merged into the next flush
+            *self.pending.lock() += 1;
+            return Err(e.into());
+        }
+        std::fs::remove_file(&retired)?;
+        Ok(())
+    }
+
+    fn run(&self) {
+        loop {
+            let stopping = {
+                let mut pending = self.pending.lock();
+                if *pending < FLUSH_PENDING && !self.stopped.load(Ordering::Relaxed) {
+                    self.wake.wait_for(&mut pending, FLUSH_INTERVAL);
+                }
+                self.stopped.load(Ordering::Relaxed)
+            };
+
+            if let Err(e) = self.flush() {
+                log::warn!("Failed to flush the system database: {}", e);
+            }
+            if stopping {
+                break;
+            }
+        }
+    }
+}
+
+impl Drop for SystemDatabase {
+    /// Stop the flusher after a final flush
+    fn drop(&mut self) {
+        {
+            let _pending = self.writer.pending.lock();
+            self.writer.stopped.store(true, Ordering::Relaxed);
+            self.writer.wake.notify_all();
+        }
+        if let Some(flusher) = self.flusher.take() {
+            flusher.join().ok();
+        }
+    }
+}
+
+impl SystemDatabase {
+    /// Initialize or open the system database
+    pub fn init() -> Result<Self, Box<dyn std::error::Error>> {
+        Self::init_named(SYSTEM_DB_DIR, SYSTEM_DB_NAME)
+    }
+
+    /// Initialize or open a system database stored as `~/<dir_name>/<file_name>`,
+    /// honoring `KERADB_HOME` and `KERADB_PROFILE`
+    pub fn init_named(dir_name: &str, file_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::open(&SystemDbLocation::from_env(dir_name, file_name)?)
+    }
+
+    /// Location `init` uses, with the default file name
+    pub fn default_location() -> Result<SystemDbLocation, Box<dyn std::error::Error>> {
+        SystemDbLocation::from_env(SYSTEM_DB_DIR, SYSTEM_DB_NAME)
+    }
+
+    /// Initialize or open the system database of a profile
+    pub fn open(location: &SystemDbLocation) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(profile) = &location.profile {
+            log::info!("Using system database profile '{}'", profile);
+        }
+        Self::open_at(&location.path())
+    }
+
+    /// A fresh system database in a temporary directory that is removed when
+    /// it is dropped, so tests never touch the real connection history
+    pub fn ephemeral() -> Result<Self, Box<dyn std::error::Error>> {
+        let nonce: [u8; 8] = rand::random();
+        let dir = std::env::temp_dir().join(format!("keradb-system-{}-{}", std::process::id(), to_hex(&nonce)));
+        std::fs::create_dir_all(&dir)?;
+        let temp_dir = TempDir(dir);
+
+        let mut system_db = Self::open_at(&temp_dir.0.join(SYSTEM_DB_NAME))?;
+        system_db.temp_dir = Some(temp_dir);
+        Ok(system_db)
+    }
+
+    /// Initialize or open the system database at an explicit location
+    pub fn open_at(db_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(parent) = db_path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        let db = if db_path.exists() {
+            Database::open(db_path)?
+        } else {
+            log::info!("Creating system database at: {}", db_path.display());
+            Database::create(db_path)?
+        };
+        let db = Arc::new(db);
+
+        // Stored records must be in the current schema before replay compares
+        // journal entries against them; the entries themselves are decoded as
+        // current types
+        migrations::migrate(&db)?;
+
+        // Writes journaled but not synced before the last exit
+        let journal_path = Journal::path_for(db_path);
+        let retired_path = Journal::retired_path(&journal_path);
+        let replayed = replay(&db, &retired_path)? + replay(&db, &journal_path)?;
+        if replayed > 0 {
+            log::info!("Recovered {} system database writes from the journal", replayed);
+            db.sync()?;
+        }
+        for path in [&retired_path, &journal_path] {
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+
+        let writer = Arc::new(Writer {
+            db: db.clone(),
+            journal: Mutex::new(Journal::open(journal_path)?),
+            pending: Mutex::new(0),
+            wake: Condvar::new(),
+            flushing: Mutex::new(()),
+            stopped: AtomicBool::new(false),
+        });
+        let flusher = std::thread::Builder::new().name("system-db-flusher".to_string()).spawn({
+            let writer = writer.clone();
+            move || writer.run()
+        })?;
+
+        let system_db = Self {
+            db,
+            writer,
+            flusher: Some(flusher),
+            reported: Mutex::new(HashSet::new()),
+            active_tokens: Mutex::new(None),
+            connections: Mutex::new(()),
+            temp_dir: None,
+        };
+        let malformed = system_db.malformed_records()?;
+        if !malformed.is_empty() {
+            log::warn!(
+                "System database has {} malformed records; they are left in place but ignored",
+                malformed.len()
+            );
+        }
+        Ok(system_db)
+    }
+
+    /// Schema version of the stored records
+    pub fn schema_version(&self) -> Result<u32, Box<dyn std::error::Error>> {
+        migrations::stored_version(&self.db)
+    }
+
+    /// Records in any collection that cannot be read; each is logged the
+    /// first time it is found
+    pub fn malformed_records(&self) -> Result<Vec<MalformedRecord>, Box<dyn std::error::Error>> {
+        let mut malformed = Vec::new();
+        decode_all::<DatabaseConnection>(&self.db, CONNECTIONS_COLLECTION, &mut malformed)?;
+        decode_all::<PerformanceMetric>(&self.db, METRICS_COLLECTION, &mut malformed)?;
+        decode_all::<MetricRollup>(&self.db, ROLLUPS_COLLECTION, &mut malformed)?;
+        decode_all::<ApiToken>(&self.db, TOKENS_COLLECTION, &mut malformed)?;
+        decode_all::<Job>(&self.db, JOBS_COLLECTION, &mut malformed)?;
+        decode_all::<SlowOperation>(&self.db, SLOW_OPS_COLLECTION, &mut malformed)?;
+        decode_all::<SavedQuery>(&self.db, SAVED_QUERIES_COLLECTION, &mut malformed)?;
+        decode_all::<QueryHistoryEntry>(&self.db, QUERY_HISTORY_COLLECTION, &mut malformed)?;
+        decode_all::<AuditEntry>(&self.db, AUDIT_COLLECTION, &mut malformed)?;
+        self.report(&malformed);
+        Ok(malformed)
+    }
+
+    // Every record of `collection` with its document id; malformed records
+    // are logged and left out
+    fn load<T: DeserializeOwned>(&self, collection: &str) -> Result<Vec<(String, T)>, Box<dyn std::error::Error>> {
+        let mut malformed = Vec::new();
+        let records = decode_all::<T>(&self.db, collection, &mut malformed)?;
+        self.report(&malformed);
+        Ok(records)
+    }
+
+    // Like `load`, reading a page at a time and only decoding the records
+    // `keep` accepts in their stored form
+    fn scan<T: DeserializeOwned>(
+        &self,
+        collection: &str,
+        keep: impl Fn(&serde_json::Value) -> bool,
+    ) -> Result<Vec<(String, T)>, Box<dyn std::error::Error>> {
+        let mut records = Vec::new();
+        let mut malformed = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = self.db.find_all(collection, Some(SCAN_PAGE), Some(offset))?;
+            let read = page.len();
+            for doc in page {
+                let value = doc.to_value();
+                if keep(&value) {
+                    decode(collection, value, &mut records, &mut malformed);
+                }
+            }
+            if read < SCAN_PAGE {
+                break;
+            }
+            offset += read;
+        }
+        self.report(&malformed);
+        Ok(records)
+    }
+
+    fn report(&self, malformed: &[MalformedRecord]) {
+        let mut reported = self.reported.lock();
+        for record in malformed {
+            if reported.insert((record.collection.clone(), record.id.clone())) {
+                log::warn!(
+                    "Ignoring malformed system database record {}/{}: {}",
+                    record.collection,
+                    record.id.as_deref().unwrap_or("<no id>"),
+                    record.error
+                );
+            }
+        }
+    }
+
+    /// Sync every deferred write now; called on shutdown
+    pub fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.writer.flush()
+    }
+
+    /// Register a new database connection or update existing
+    pub fn register_connection(&self, path: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let _connections = self.connections.lock();
+        let now = Utc::now();
+        
+        // Check if connection already exists
+        if let Ok((id, existing)) = self.find_connection_by_path(path) {
+            // Update existing connection
+            let updated = DatabaseConnection {
+                id: id.clone(),
+                last_accessed: now,
+                access_count: existing.access_count + 1,
+                ..existing
+            };
+            
+            self.writer.write(JournalEntry::Connection(updated))?;
+            return Ok(id);
+        }
+
+        // Create new connection
+        let connection = DatabaseConnection {
+            id: String::new(), // Will be generated by insert
+            path: path.to_string(),
+            alias: None,
+            created_at: now,
+            last_accessed: now,
+            access_count: 1,
+            collections_count: 0,
+            total_documents: 0,
+            color: None,
+            favorite: false,
+            notes: None,
+            folder: None,
+        };
+
+        let id = self.writer.write(JournalEntry::NewConnection(connection))?;
+        Ok(id.unwrap_or_default())
+    }
+
+    /// Update database statistics
+    pub fn update_connection_stats(
+        &self,
+        path: &str,
+        collections_count: usize,
+        total_documents: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let _connections = self.connections.lock();
+        if let Ok((id, mut conn)) = self.find_connection_by_path(path) {
+            conn.id = id;
+            conn.collections_count = collections_count;
+            conn.total_documents = total_documents;
+            conn.last_accessed = Utc::now();
+
+            self.writer.write(JournalEntry::Connection(conn))?;
+        }
+        Ok(())
+    }
+
+    /// Find connection by database path
+    /// Returns (document_id, connection) tuple
+    fn find_connection_by_path(&self, path: &str) -> Result<(String, DatabaseConnection), Box<dyn std::error::Error>> {
+        self.load::<DatabaseConnection>(CONNECTIONS_COLLECTION)?
+            .into_iter()
+            .find(|(_, conn)| conn.path == path)
+            .ok_or_else(|| "Connection not found".into())
+    }
+
+    /// Get all registered connections
+    pub fn list_connections(&self) -> Result<Vec<DatabaseConnection>, Box<dyn std::error::Error>> {
+        let mut connections = Vec::new();
+        for (doc_id, mut conn) in self.load::<DatabaseConnection>(CONNECTIONS_COLLECTION)? {
+            conn.id = doc_id;
+            connections.push(conn);
+        }
+        
+        // Sort by last accessed (most recent first)
+        connections.sort_by_key(|c| std::cmp::Reverse(c.last_accessed));
+        
+        Ok(connections)
+    }
+
+    /// Find a connection by its id, alias or the name it is stored under.
+    /// Matching the stored name keeps entries recorded under an older naming
+    /// scheme, e.g. before roots were configured, addressable.
+    pub fn find_connection(&self, handle: &str) -> Result<Option<DatabaseConnection>, Box<dyn std::error::Error>> {
+        Ok(self
+            .list_connections()?
+            .into_iter()
+            .find(|conn| conn.id == handle || conn.alias.as_deref() == Some(handle) || conn.path == handle))
+    }
+
+    /// Set or clear the alias of the connection for `path`
+    pub fn set_alias(&self, path: &str, alias: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        let _connections = self.connections.lock();
+        self.check_alias(path, alias)?;
+
+        let (id, mut conn) = self.find_connection_by_path(path)?;
+        conn.id = id;
+        conn.alias = alias.map(str::to_string);
+        self.writer.write(JournalEntry::Connection(conn))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Apply user edits to the profile of the connection for `path` and
+    /// return the updated connection; an empty alias clears it
+    pub fn update_connection_profile(
+        &self,
+        path: &str,
+        update: &ConnectionProfileUpdate,
+    ) -> Result<DatabaseConnection, Box<dyn std::error::Error>> {
+        let _connections = self.connections.lock();
+        let (id, mut conn) = self.find_connection_by_path(path)?;
+
+        if let Some(alias) = &update.alias {
+            let alias = (!alias.is_empty()).then_some(alias.as_str());
+            self.check_alias(path, alias)?;
+            conn.alias = alias.map(str::to_string);
+        }
+        connections::apply_profile_update(&mut conn, update)?;
+        conn.id = id;
+
+        // Journaled like stats updates so a replay cannot restore an older
+        // profile, then synced right away since these are user edits
+        self.writer.write(JournalEntry::Connection(conn.clone()))?;
+        self.writer.flush()?;
+        Ok(conn)
+    }
+
+    /// Check that `alias` is well formed and not used by another connection
+    fn check_alias(&self, path: &str, alias: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(alias) = alias {
+            if !is_valid_alias(alias) {
+                return Err(format!(
+                    "Invalid alias '{}': use up to 64 letters, digits, '-', '_' or '.'",
+                    alias
+                )
+                .into());
+            }
+            if let Some(other) = self.find_connection(alias)? {
+                if other.path != path {
+                    return Err(format!("Alias '{}' is already used by {}", alias, other.path).into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a connection from the system database
+    pub fn remove_connection(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let _connections = self.connections.lock();
+        if let Ok((id, _conn)) = self.find_connection_by_path(path) {
+            self.writer.write(JournalEntry::RemovedConnection { id })?;
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Record a performance metric
+    pub fn record_metric(
+        &self,
+        database_path: &str,
+        operation: &str,
+        duration_ms: u64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let metric = PerformanceMetric {
+            id: String::new(),
+            database_path: database_path.to_string(),
+            operation: operation.to_string(),
+            duration_ms,
+            timestamp: Utc::now(),
+        };
+
+        self.writer.write(JournalEntry::Metric(metric))?;
+        Ok(())
+    }
+
+    /// Get recent metrics for a database
+    pub fn get_metrics(
+        &self,
+        database_path: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<PerformanceMetric>, Box<dyn std::error::Error>> {
+        let filter = MetricsFilter {
+            database_path: Some(database_path.to_string()),
+            ..Default::default()
+        };
+        self.query_metrics(&filter, |_| true, limit)
+    }
+
+    /// Get raw metric samples matching `filter` of the databases `include`
+    /// accepts, most recent first
+    pub fn query_metrics(
+        &self,
+        filter: &MetricsFilter,
+        include: impl Fn(&str) -> bool,
+        limit: Option<usize>,
+    ) -> Result<Vec<PerformanceMetric>, Box<dyn std::error::Error>> {
+        let mut metrics = self.load_metrics(filter)?;
+        metrics.retain(|m| include(&m.database_path));
+
+        // Sort by timestamp (most recent first)
+        metrics.sort_by_key(|m| std::cmp::Reverse(m.timestamp));
+
+        if let Some(limit) = limit {
+            metrics.truncate(limit);
+        }
+
+        Ok(metrics)
+    }
+
+    /// Rollups matching `filter` by bucket start, oldest first. Buckets not
+    /// compacted yet, including the current one, are computed from raw samples.
+    pub fn metric_rollups(
+        &self,
+        filter: &MetricsFilter,
+        granularity: Granularity,
+    ) -> Result<Vec<MetricRollup>, Box<dyn std::error::Error>> {
+        let stored: Vec<MetricRollup> = self
+            .load_rollups(filter, Some(granularity))?
+            .into_iter()
+            .map(|(_, rollup)| rollup)
+            .collect();
+        let watermarks = rollup_watermarks(&stored, granularity);
+
+        // Every sample of the buckets starting in the requested range
+        let sample_filter = MetricsFilter {
+            from: filter.from.map(|from| granularity.bucket_start(from)),
+            to: filter.to.map(|to| granularity.bucket_start(to) + granularity.duration()),
+            ..filter.clone()
+        };
+        let samples = self.load_metrics(&sample_filter)?;
+        let live = metrics::rollup(
+            samples.iter().filter(|m| granularity.bucket_start(m.timestamp) >= watermark(&watermarks, m)),
+            granularity,
+        );
+
+        let mut rollups: Vec<MetricRollup> = stored
+            .into_iter()
+            .chain(live)
+            .filter(|r| filter.matches(&r.database_path, &r.operation, r.bucket_start))
+            .collect();
+        rollups.sort_by_key(|r| r.bucket_start);
+        Ok(rollups)
+    }
+
+    /// Roll up every closed bucket not rolled up yet, then prune raw samples,
+    /// rollups, slow operation log and query history entries past their
+    /// retention
+    pub fn compact_metrics(
+        &self,
+        retention: &MetricsRetention,
+        now: DateTime<Utc>,
+    ) -> Result<CompactionStats, Box<dyn std::error::Error>> {
+        let mut stats = CompactionStats::default();
+        // Samples of the open minute are neither rolled up nor pruned yet
+        let samples = self.load_metrics(&MetricsFilter {
+            to: Some(Granularity::Minute.bucket_start(now)),
+            ..Default::default()
+        })?;
+        let stored = self.load_rollups(&MetricsFilter::default(), None)?;
+
+        for granularity in Granularity::ALL {
+            let existing: Vec<MetricRollup> = stored
+                .iter()
+                .filter(|(_, r)| r.granularity == granularity)
+                .map(|(_, r)| r.clone())
+                .collect();
+            let watermarks = rollup_watermarks(&existing, granularity);
+            let open_bucket = granularity.bucket_start(now);
+
+            let closed = samples.iter().filter(|m| {
+                let bucket = granularity.bucket_start(m.timestamp);
+                bucket >= watermark(&watermarks, m) && bucket < open_bucket
+            });
+            for rollup in metrics::rollup(closed, granularity) {
+                self.db.insert(ROLLUPS_COLLECTION, serde_json::to_value(&rollup)?)?;
+                stats.rollups_created += 1;
+            }
+        }
+
+        let raw_cutoff = retention.raw_cutoff(now);
+        for metric in samples.iter().filter(|m| m.timestamp < raw_cutoff) {
+            self.db.delete(METRICS_COLLECTION, &metric.id)?;
+            stats.samples_pruned += 1;
+        }
+
+        for (id, rollup) in &stored {
+            if rollup.bucket_start < retention.rollup_cutoff(rollup.granularity, now) {
+                self.db.delete(ROLLUPS_COLLECTION, id)?;
+                stats.rollups_pruned += 1;
+            }
+        }
+
+        let slow_op_cutoff = retention.slow_op_cutoff(now);
+        for op in self.load_slow_operations()?.iter().filter(|op| op.timestamp < slow_op_cutoff) {
+            self.db.delete(SLOW_OPS_COLLECTION, &op.id)?;
+            stats.slow_operations_pruned += 1;
+        }
+
+        let history_cutoff = retention.query_history_cutoff(now);
+        for entry in self.load_query_history()?.iter().filter(|e| e.executed_at < history_cutoff) {
+            self.db.delete(QUERY_HISTORY_COLLECTION, &entry.id)?;
+            stats.query_history_pruned += 1;
+        }
+
+        self.db.sync()?;
+        Ok(stats)
+    }
+
+    /// Record an operation that exceeded the slow operation threshold
+    pub fn record_slow_operation(&self, op: &SlowOperation) -> Result<String, Box<dyn std::error::Error>> {
+        let doc = serde_json::to_value(op)?;
+        let id = self.db.insert(SLOW_OPS_COLLECTION, doc)?;
+        self.db.sync()?;
+        Ok(id)
+    }
+
+    /// Get logged slow operations matching `filter`, most recent first
+    pub fn slow_operations(
+        &self,
+        filter: &SlowOperationFilter,
+        include: impl Fn(&str) -> bool,
+        limit: Option<usize>,
+    ) -> Result<Vec<SlowOperation>, Box<dyn std::error::Error>> {
+        let mut ops: Vec<SlowOperation> = self
+            .load_slow_operations()?
+            .into_iter()
+            .filter(|op| filter.matches(op) && include(&op.database_path))
+            .collect();
+
+        ops.sort_by_key(|op| std::cmp::Reverse(op.timestamp));
+        if let Some(limit) = limit {
+            ops.truncate(limit);
+        }
+        Ok(ops)
+    }
+
+    // All logged slow operations, with `id` set to the document id
+    fn load_slow_operations(&self) -> Result<Vec<SlowOperation>, Box<dyn std::error::Error>> {
+        let mut ops = Vec::new();
+        for (doc_id, mut op) in self.load::<SlowOperation>(SLOW_OPS_COLLECTION)? {
+            op.id = doc_id;
+            ops.push(op);
+        }
+        Ok(ops)
+    }
+
+    // All raw samples, with `id` set to the document id
+    fn load_metrics(&self, filter: &MetricsFilter) -> Result<Vec<PerformanceMetric>, Box<dyn std::error::Error>> {
+        let mut metrics = Vec::new();
+        let keep = |value: &serde_json::Value| stored_metric_matches(filter, value, "timestamp");
+        for (doc_id, mut metric) in self.scan::<PerformanceMetric>(METRICS_COLLECTION, keep)? {
+            metric.id = doc_id;
+            metrics.push(metric);
+        }
+        Ok(metrics)
+    }
+
+    // Stored rollups matching `filter` by bucket start, of one granularity or
+    // all, with their document ids
+    fn load_rollups(
+        &self,
+        filter: &MetricsFilter,
+        granularity: Option<Granularity>,
+    ) -> Result<Vec<(String, MetricRollup)>, Box<dyn std::error::Error>> {
+        let granularity = granularity.map(serde_json::to_value).transpose()?;
+        self.scan::<MetricRollup>(ROLLUPS_COLLECTION, |value| {
+            granularity.as_ref().is_none_or(|g| value.get("granularity") == Some(g))
+                && stored_metric_matches(filter, value, "bucket_start")
+        })
+    }
+
+    /// Totals over the connections `include` accepts. Schema and integrity
+    /// details are only reported with `integrity`, since malformed records
+    /// are not tied to a database.
+    pub fn get_system_stats(
+        &self,
+        include: impl Fn(&str) -> bool,
+        integrity: bool,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        let connections: Vec<_> = self
+            .list_connections()?
+            .into_iter()
+            .filter(|conn| include(&conn.path))
+            .collect();
+        let total_databases = connections.len();
+        let total_collections: usize = connections.iter().map(|c| c.collections_count).sum();
+        let total_documents: usize = connections.iter().map(|c| c.total_documents).sum();
+        
+        let mut stats = serde_json::json!({
+            "total_databases": total_databases,
+            "total_collections": total_collections,
+            "total_documents": total_documents,
+            "connections": connections,
+        });
+        if integrity {
+            stats["schema_version"] = serde_json::json!(self.schema_version()?);
+            stats["malformed_records"] = serde_json::to_value(self.malformed_records()?)?;
+        }
+        Ok(stats)
+    }
+
+    /// Store a new saved query; returns its id. Names are unique per database.
+    pub fn create_saved_query(&self, query: &SavedQuery) -> Result<String, Box<dyn std::error::Error>> {
+        self.check_query_name(query)?;
+        let doc = serde_json::to_value(query)?;
+        let id = self.db.insert(SAVED_QUERIES_COLLECTION, doc)?;
+        self.db.sync()?;
+        Ok(id)
+    }
+
+    /// Overwrite a saved query
+    pub fn save_saved_query(&self, query: &SavedQuery) -> Result<(), Box<dyn std::error::Error>> {
+        self.check_query_name(query)?;
+        let doc = serde_json::to_value(query)?;
+        self.db.update(SAVED_QUERIES_COLLECTION, &query.id, doc)?;
+        self.db.sync()?;
+        Ok(())
+    }
+
+    pub fn delete_saved_query(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.db.delete(SAVED_QUERIES_COLLECTION, id)?;
+        self.db.sync()?;
+        Ok(())
+    }
+
+    /// Saved queries of one database, or of all, by name
+    pub fn saved_queries(&self, database_path: Option<&str>) -> Result<Vec<SavedQuery>, Box<dyn std::error::Error>> {
+        let mut queries = Vec::new();
+        for (doc_id, mut query) in self.load::<SavedQuery>(SAVED_QUERIES_COLLECTION)? {
+            query.id = doc_id;
+            if database_path.is_none_or(|db| db == query.database_path) {
+                queries.push(query);
+            }
+        }
+
+        queries.sort_by_key(|q| q.name.to_lowercase());
+        Ok(queries)
+    }
+
+    fn check_query_name(&self, query: &SavedQuery) -> Result<(), Box<dyn std::error::Error>> {
+        let taken = self
+            .saved_queries(Some(&query.database_path))?
+            .iter()
+            .any(|other| other.id != query.id && other.name.eq_ignore_ascii_case(&query.name));
+        if taken {
+            return Err(format!("A query named '{}' already exists for {}", query.name, query.database_path).into());
+        }
+        Ok(())
+    }
+
+    /// Record a run of a query; deferred like metrics
+    pub fn record_query(&self, entry: &QueryHistoryEntry) -> Result<(), Box<dyn std::error::Error>> {
+        let mut entry = entry.clone();
+        entry.id = String::new();
+        self.writer.write(JournalEntry::QueryHistory(entry))?;
+        Ok(())
+    }
+
+    /// Query history of one database, or of all, most recent first
+    pub fn query_history(
+        &self,
+        database_path: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<QueryHistoryEntry>, Box<dyn std::error::Error>> {
+        let mut entries: Vec<QueryHistoryEntry> = self
+            .load_query_history()?
+            .into_iter()
+            .filter(|entry| database_path.is_none_or(|db| db == entry.database_path))
+            .collect();
+
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.executed_at));
+        if let Some(limit) = limit {
+            entries.truncate(limit);
+        }
+        Ok(entries)
+    }
+
+    /// Delete the query history of one database; returns how many entries
+    /// were removed
+    pub fn clear_query_history(&self, database_path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let entries = self.query_history(Some(database_path), None)?;
+        for entry in &entries {
+            self.db.delete(QUERY_HISTORY_COLLECTION, &entry.id)?;
+        }
+        self.db.sync()?;
+        Ok(entries.len())
+    }
+
+    // All query history entries, with `id` set to the document id
+    fn load_query_history(&self) -> Result<Vec<QueryHistoryEntry>, Box<dyn std::error::Error>> {
+        let mut entries = Vec::new();
+        for (doc_id, mut entry) in self.load::<QueryHistoryEntry>(QUERY_HISTORY_COLLECTION)? {
+            entry.id = doc_id;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    /// Append an entry to the audit log. Entries are never updated or
+    /// deleted; they are journaled before this returns, so none is lost.
+    pub fn record_audit(&self, entry: &AuditEntry) -> Result<(), Box<dyn std::error::Error>> {
+        let mut entry = entry.clone();
+        entry.id = String::new();
+        self.writer.write(JournalEntry::Audit(entry))?;
+        Ok(())
+    }
+
+    /// Audit entries matching `filter`, most recent first
+    pub fn audit_log(
+        &self,
+        filter: &AuditFilter,
+        include: impl Fn(&str) -> bool,
+        limit: Option<usize>,
+    ) -> Result<Vec<AuditEntry>, Box<dyn std::error::Error>> {
+        let mut entries = Vec::new();
+        for (doc_id, mut entry) in self.load::<AuditEntry>(AUDIT_COLLECTION)? {
+            entry.id = doc_id;
+            if filter.matches(&entry) && include(&entry.database_path) {
+                entries.push(entry);
+            }
+        }
+
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp));
+        if let Some(limit) = limit {
+            entries.truncate(limit);
+        }
+        Ok(entries)
+    }
+
+    /// Create an API token, returning its record and the plaintext token.
+    /// The plaintext is only available here; it is stored hashed.
+    pub fn create_token(
+        &self,
+        name: &str,
+        scope: TokenScope,
+        databases: Vec<String>,
+    ) -> Result<(ApiToken, String), Box<dyn std::error::Error>> {
+        let secret: [u8; 32] = rand::random();
+        let token = format!("kdb_{}", to_hex(&secret));
+
+        let mut record = ApiToken {
+            id: String::new(), // Will be generated by insert
+            name: name.to_string(),
+            token_hash: hash_token(&token),
+            scope,
+            databases,
+            created_at: Utc::now(),
+            revoked_at: None,
+        };
+
+        let doc = serde_json::to_value(&record)?;
+        record.id = self.db.insert(TOKENS_COLLECTION, doc)?;
+        self.db.sync()?;
+        if let Some(active) = self.active_tokens.lock().as_mut() {
+            active.insert(record.token_hash.clone(), record.clone());
+        }
+
+        Ok((record, token))
+    }
+
+    /// Get all API tokens, including revoked ones
+    pub fn list_tokens(&self) -> Result<Vec<ApiToken>, Box<dyn std::error::Error>> {
+        let mut tokens = Vec::new();
+        for (doc_id, mut token) in self.load::<ApiToken>(TOKENS_COLLECTION)? {
+            token.id = doc_id;
+            tokens.push(token);
+        }
+
+        tokens.sort_by_key(|t| std::cmp::Reverse(t.created_at));
+        Ok(tokens)
+    }
+
+    /// Revoke a token by ID. Returns false if no such token exists.
+    pub fn revoke_token(&self, id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut token = match self.list_tokens()?.into_iter().find(|t| t.id == id) {
+            Some(token) => token,
+            None => return Ok(false),
+        };
+
+        if token.revoked_at.is_none() {
+            token.revoked_at = Some(Utc::now());
+            let doc = serde_json::to_value(&token)?;
+            self.db.update(TOKENS_COLLECTION, id, doc)?;
+            self.db.sync()?;
+            if let Some(active) = self.active_tokens.lock().as_mut() {
+                active.remove(&token.token_hash);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Look up an active (non-revoked) token from its plaintext value. Tokens
+    /// are found by hash in memory; a miss reloads them once, so tokens
+    /// created by another process, e.g. `--create-admin-token`, are picked
+    /// up. Revocations must go through this instance to take effect.
+    pub fn verify_token(&self, token: &str) -> Result<Option<ApiToken>, Box<dyn std::error::Error>> {
+        let token_hash = hash_token(token);
+        let mut active = self.active_tokens.lock();
+        if let Some(token) = active.as_ref().and_then(|active| active.get(&token_hash)) {
+            return Ok(Some(token.clone()));
+        }
+
+        let reloaded: HashMap<_, _> = self
+            .list_tokens()?
+            .into_iter()
+            .filter(|t| t.revoked_at.is_none())
+            .map(|t| (t.token_hash.clone(), t))
+            .collect();
+        let found = reloaded.get(&token_hash).cloned();
+        *active = Some(reloaded);
+        Ok(found)
+    }
+
+    /// Store a new job; returns its id
+    pub fn create_job(&self, job: &Job) -> Result<String, Box<dyn std::error::Error>> {
+        let doc = serde_json::to_value(job)?;
+        let id = self.db.insert(JOBS_COLLECTION, doc)?;
+        self.db.sync()?;
+        Ok(id)
+    }
+
+    /// Overwrite the stored state of a job
+    pub fn save_job(&self, job: &Job) -> Result<(), Box<dyn std::error::Error>> {
+        let doc = serde_json::to_value(job)?;
+        self.db.update(JOBS_COLLECTION, &job.id, doc)?;
+        self.db.sync()?;
+        Ok(())
+    }
+
+    /// Get job history, most recent first
+    pub fn list_jobs(&self, limit: Option<usize>) -> Result<Vec<Job>, Box<dyn std::error::Error>> {
+        let mut jobs = Vec::new();
+        for (doc_id, mut job) in self.load::<Job>(JOBS_COLLECTION)? {
+            job.id = doc_id;
+            jobs.push(job);
+        }
+
+        jobs.sort_by_key(|j| std::cmp::Reverse(j.created_at));
+        if let Some(limit) = limit {
+            jobs.truncate(limit);
+        }
+        Ok(jobs)
+    }
+
+    pub fn get_job(&self, id: &str) -> Result<Option<Job>, Box<dyn std::error::Error>> {
+        Ok(self.list_jobs(None)?.into_iter().find(|j| j.id == id))
+    }
+}
+
+// Records of `collection` that deserialize into `T`, with their document
+// ids; the others are added to `malformed`
+fn decode_all<T: DeserializeOwned>(
+    db: &Database,
+    collection: &str,
+    malformed: &mut Vec<MalformedRecord>,
+) -> Result<Vec<(String, T)>, Box<dyn std::error::Error>> {
+    let mut records = Vec::new();
+    for doc in db.find_all(collection, None, None)? {
+        decode(collection, doc.to_value(), &mut records, malformed);
+    }
+    Ok(records)
+}
+
+fn decode<T: DeserializeOwned>(
+    collection: &str,
+    doc_value: serde_json::Value,
+    records: &mut Vec<(String, T)>,
+    malformed: &mut Vec<MalformedRecord>,
+) {
+    let doc_id = doc_value.get("_id").and_then(|v| v.as_str()).map(str::to_string);
+    let error = match (doc_id.clone(), serde_json::from_value::<T>(doc_value)) {
+        (Some(doc_id), Ok(record)) => {
+            records.push((doc_id, record));
+            return;
+        }
+        (None, _) => "missing _id".to_string(),
+        (Some(_), Err(e)) => e.to_string(),
+    };
+    malformed.push(MalformedRecord {
+        collection: collection.to_string(),
+        id: doc_id,
+        error,
+    });
+}
+
+// Check a stored sample or rollup against `filter` before decoding it.
+// Records missing a field are kept so decoding reports them as malformed.
+fn stored_metric_matches(filter: &MetricsFilter, value: &serde_json::Value, time_field: &str) -> bool {
+    let field = |name: &str| value.get(name).and_then(serde_json::Value::as_str);
+    let (Some(database_path), Some(operation), Some(time)) = (field("database_path"), field("operation"), field(time_field))
+    else {
+        return true;
+    };
+    time.parse::<DateTime<Utc>>()
+        .map_or(true, |time| filter.matches(database_path, operation, time))
+}
+
+// Apply a deferred write in memory; returns the id of a new connection
+fn apply(db: &Database, entry: &JournalEntry) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    match entry {
+        JournalEntry::Metric(metric) => {
+            db.insert(METRICS_COLLECTION, serde_json::to_value(metric)?)?;
+        }
+        JournalEntry::NewConnection(conn) => {
+            return Ok(Some(db.insert(CONNECTIONS_COLLECTION, serde_json::to_value(conn)?)?));
+        }
+        JournalEntry::Connection(conn) => {
+            db.update(CONNECTIONS_COLLECTION, &conn.id, serde_json::to_value(conn)?)?;
+        }
+        JournalEntry::RemovedConnection { id } => {
+            if db.find_by_id(CONNECTIONS_COLLECTION, id).is_ok() {
+                db.delete(CONNECTIONS_COLLECTION, id)?;
+            }
+        }
+        JournalEntry::QueryHistory(entry) => {
+            db.insert(QUERY_HISTORY_COLLECTION, serde_json::to_value(entry)?)?;
+        }
+        JournalEntry::Audit(entry) => {
+            db.insert(AUDIT_COLLECTION, serde_json::to_value(entry)?)?;
+        }
+    }
+    Ok(None)
+}
+
+// Apply the entries of a journal file that did not reach the database;
+// returns how many were applied
+fn replay(db: &Database, path: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+    let entries = Journal::read(path)?;
+    if entries.is_empty() {
+        return Ok(0);
+    }
+
+    let sample_key = |m: &PerformanceMetric| (m.database_path.clone(), m.operation.clone(), m.timestamp, m.duration_ms);
+    let mut stored: HashSet<_> = db
+        .find_all(METRICS_COLLECTION, None, None)?
+        .into_iter()
+        .filter_map(|doc| serde_json::from_value::<PerformanceMetric>(doc.to_value()).ok())
+        .map(|m| sample_key(&m))
+        .collect();
+
+    let run_key = |e: &QueryHistoryEntry| (e.database_path.clone(), e.executed_at);
+    let mut runs: HashSet<_> = db
+        .find_all(QUERY_HISTORY_COLLECTION, None, None)?
+        .into_iter()
+        .filter_map(|doc| serde_json::from_value::<QueryHistoryEntry>(doc.to_value()).ok())
+        .map(|e| run_key(&e))
+        .collect();
+
+    let audit_key = |e: &AuditEntry| (e.database_path.clone(), e.action, e.timestamp, e.target_id.clone());
+    let mut audited: HashSet<_> = db
+        .find_all(AUDIT_COLLECTION, None, None)?
+        .into_iter()
+        .filter_map(|doc| serde_json::from_value::<AuditEntry>(doc.to_value()).ok())
+        .map(|e| audit_key(&e))
+        .collect();
+
+    // A new connection that did not reach the database gets another id when
+    // it is inserted again; later entries are pointed at that one
+    let mut new_ids: HashMap<String, String> = HashMap::new();
+
+    let mut applied = 0;
+    for mut entry in entries {
+        let duplicate = match &mut entry {
+            JournalEntry::Metric(metric) => !stored.insert(sample_key(metric)),
+            JournalEntry::QueryHistory(run) => !runs.insert(run_key(run)),
+            JournalEntry::Audit(audit) => !audited.insert(audit_key(audit)),
+            JournalEntry::NewConnection(conn) => db.find_by_id(CONNECTIONS_COLLECTION, &conn.id).is_ok(),
+            JournalEntry::Connection(DatabaseConnection { id, .. }) | JournalEntry::RemovedConnection { id } => {
+                if let Some(new_id) = new_ids.get(id) {
+                    *id = new_id.clone();
+                }
+                false
+            }
+        };
+        if duplicate {
+            continue;
+        }
+        // A connection removed since is not restored
+        match apply(db, &entry) {
+            Ok(new_id) => {
+                if let (JournalEntry::NewConnection(conn), Some(new_id)) = (&entry, new_id) {
+                    new_ids.insert(conn.id.clone(), new_id);
+                }
+                applied += 1;
+            }
+            Err(e) => log::warn!("Skipping journaled write: {}", e),
+        }
+    }
+    Ok(applied)
+}
+
+// End of the latest stored bucket per database and operation; their samples
+// before it have been rolled up
+fn rollup_watermarks(stored: &[MetricRollup], granularity: Granularity) -> HashMap<(&str, &str), DateTime<Utc>> {
+    let mut watermarks = HashMap::new();
+    for rollup in stored {
+        let end = rollup.bucket_start + granularity.duration();
+        let watermark = watermarks
+            .entry((rollup.database_path.as_str(), rollup.operation.as_str()))
+            .or_insert(end);
+        *watermark = (*watermark).max(end);
+    }
+    watermarks
+}
+
+fn watermark(watermarks: &HashMap<(&str, &str), DateTime<Utc>>, sample: &PerformanceMetric) -> DateTime<Utc> {
+    watermarks
+        .get(&(sample.database_path.as_str(), sample.operation.as_str()))
+        .copied()
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The current user's home directory in an OS-agnostic way
+fn user_home() -> Result<PathBuf, std::io::Error> {
+    let home = if cfg!(target_os = "windows") {
+        std::env::var("USERPROFILE").or_else(|_| {
+            std::env::var("HOMEDRIVE")
+                .and_then(|drive| std::env::var("HOMEPATH").map(|path| format!("{}{}", drive, path)))
+        })
+    } else {
+        std::env::var("HOME")
+    };
+    home.map(PathBuf::from)
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::NotFound, "Could not determine user home directory"))
+}
+
+/// Aliases appear in URLs, so keep them to unreserved characters
+fn is_valid_alias(alias: &str) -> bool {
+    !alias.is_empty()
+        && alias.len() <= 64
+        && alias
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_allows() {
+        assert!(TokenScope::ReadOnly.allows(Permission::Read));
+        assert!(!TokenScope::ReadOnly.allows(Permission::Write));
+        assert!(TokenScope::ReadWrite.allows(Permission::Write));
+        assert!(!TokenScope::ReadWrite.allows(Permission::Admin));
+        assert!(TokenScope::Admin.allows(Permission::Admin));
+    }
+
+    #[test]
+    fn test_verify_token() {
+        let system_db = SystemDatabase::ephemeral().unwrap();
+        let (first, first_secret) = system_db.create_token("first", TokenScope::ReadOnly, Vec::new()).unwrap();
+        assert_eq!(system_db.verify_token(&first_secret).unwrap().unwrap().id, first.id);
+
+        // Created after the tokens were loaded
+        let (second, second_secret) = system_db.create_token("second", TokenScope::Admin, Vec::new()).unwrap();
+        assert_eq!(system_db.verify_token(&second_secret).unwrap().unwrap().id, second.id);
+
+        assert!(system_db.revoke_token(&first.id).unwrap());
+        assert!(system_db.verify_token(&first_secret).unwrap().is_none());
+        assert!(system_db.verify_token("kdb_unknown").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_journal_is_replayed_once() {
+        let dir = std::env::temp_dir().join(format!("keradb-labs-journal-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        let path = dir.join("system.db");
+
+        let system_db = SystemDatabase::open_at(&path).unwrap();
+        system_db.record_metric("a.db", "insert_document", 3).unwrap();
+        let journal_path = Journal::path_for(&path);
+        assert_eq!(Journal::read(&journal_path).unwrap().len(), 1);
+        system_db.flush().unwrap();
+        assert!(Journal::read(&journal_path).unwrap().is_empty());
+        let synced = system_db.get_metrics("a.db", None).unwrap().remove(0);
+        drop(system_db);
+
+        // As left by a crash: one write that was synced, one that was not and
+        // a torn last line
+        let unsynced = PerformanceMetric { duration_ms: 5, ..synced.clone() };
+        let mut journal = Journal::open(journal_path.clone()).unwrap();
+        journal.append(&JournalEntry::Metric(synced)).unwrap();
+        journal.append(&JournalEntry::Metric(unsynced)).unwrap();
+        drop(journal);
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&journal_path)
+            .and_then(|mut file| std::io::Write::write_all(&mut file, b"{\"kind\":\"met"))
+            .unwrap();
+
+        let system_db = SystemDatabase::open_at(&path).unwrap();
+        let mut durations: Vec<u64> = system_db
+            .get_metrics("a.db", None)
+            .unwrap()
+            .iter()
+            .map(|m| m.duration_ms)
+            .collect();
+        durations.sort_unstable();
+        assert_eq!(durations, vec![3, 5]);
+        assert!(Journal::read(&journal_path).unwrap().is_empty());
+
+        drop(system_db);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_limit_applies_to_included_databases() {
+        let system_db = SystemDatabase::ephemeral().unwrap();
+        system_db.record_metric("b.db", "insert_document", 1).unwrap();
+        for _ in 0..3 {
+            system_db.record_metric("a.db", "insert_document", 2).unwrap();
+        }
+
+        let only_b = |db: &str| db == "b.db";
+        let metrics = system_db.query_metrics(&MetricsFilter::default(), only_b, Some(1)).unwrap();
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].database_path, "b.db");
+    }
+
+    #[test]
+    fn test_concurrent_connection_updates_are_kept() {
+        let system_db = SystemDatabase::ephemeral().unwrap();
+        system_db.register_connection("a.db").unwrap();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..25 {
+                        system_db.register_connection("a.db").unwrap();
+                        system_db.update_connection_stats("a.db", 2, 10).unwrap();
+                    }
+                });
+            }
+            scope.spawn(|| system_db.set_alias("a.db", Some("alpha")).unwrap());
+        });
+
+        let conn = system_db.find_connection("a.db").unwrap().unwrap();
+        assert_eq!(conn.access_count, 101);
+        assert_eq!(conn.alias.as_deref(), Some("alpha"));
+    }
+
+    #[test]
+    fn test_journaled_connections_follow_their_new_id() {
+        let dir = std::env::temp_dir().join(format!("keradb-labs-journal-conn-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        let path = dir.join("system.db");
+        drop(SystemDatabase::open_at(&path).unwrap());
+
+        // Connections registered, renamed and removed, none of it synced
+        let now = Utc::now();
+        let conn = |id: &str, path: &str| DatabaseConnection {
+            id: id.to_string(),
+            path: path.to_string(),
+            alias: None,
+            created_at: now,
+            last_accessed: now,
+            access_count: 1,
+            collections_count: 0,
+            total_documents: 0,
+            color: None,
+            favorite: false,
+            notes: None,
+            folder: None,
+        };
+        let mut journal = Journal::open(Journal::path_for(&path)).unwrap();
+        journal.append(&JournalEntry::NewConnection(conn("lost-a", "a.db"))).unwrap();
+        let renamed = DatabaseConnection { alias: Some("alpha".to_string()), ..conn("lost-a", "a.db") };
+        journal.append(&JournalEntry::Connection(renamed)).unwrap();
+        journal.append(&JournalEntry::NewConnection(conn("lost-b", "b.db"))).unwrap();
+        journal.append(&JournalEntry::RemovedConnection { id: "lost-b".to_string() }).unwrap();
+        drop(journal);
+
+        let system_db = SystemDatabase::open_at(&path).unwrap();
+        let connections = system_db.list_connections().unwrap();
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].path, "a.db");
+        assert_eq!(connections[0].alias.as_deref(), Some("alpha"));
+
+        drop(system_db);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_profiles_have_their_own_database() {
+        let home = std::env::temp_dir().join(format!("keradb-labs-home-{}", std::process::id()));
+        std::fs::remove_dir_all(&home).ok();
+        let default = SystemDbLocation::new(&home, SYSTEM_DB_NAME);
+        let work = default.clone().with_profile(Some("work")).unwrap();
+        assert_eq!(default.path(), home.join(SYSTEM_DB_NAME));
+        assert_eq!(work.path(), home.join("profiles").join("work").join(SYSTEM_DB_NAME));
+        assert!(default.clone().with_profile(Some("../work")).is_err());
+        assert!(default.clone().with_profile(Some("..")).is_err());
+
+        SystemDatabase::open(&work).unwrap().register_connection("a.db").unwrap();
+        assert!(SystemDatabase::open(&default).unwrap().list_connections().unwrap().is_empty());
+        assert_eq!(default.profiles().unwrap(), vec!["work"]);
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn test_ephemeral_database_is_removed() {
+        let system_db = SystemDatabase::ephemeral().unwrap();
+        system_db.register_connection("a.db").unwrap();
+        let dir = system_db.temp_dir.as_ref().unwrap().0.clone();
+        assert!(dir.join(SYSTEM_DB_NAME).exists());
+
+        drop(system_db);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_malformed_records_are_reported() {
+        let dir = std::env::temp_dir().join(format!("keradb-labs-malformed-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        let system_db = SystemDatabase::open_at(&dir.join("system.db")).unwrap();
+        assert_eq!(system_db.schema_version().unwrap(), migrations::SCHEMA_VERSION);
+
+        // A connection without its statistics, as a newer version might store it
+        let bad_id = system_db
+            .db
+            .insert(CONNECTIONS_COLLECTION, serde_json::json!({ "id": "", "path": "b.db" }))
+            .unwrap();
+        system_db.register_connection("a.db").unwrap();
+
+        // The bad record neither hides nor duplicates the good one
+        system_db.register_connection("a.db").unwrap();
+        let connections = system_db.list_connections().unwrap();
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].access_count, 2);
+
+        let malformed = system_db.malformed_records().unwrap();
+        assert_eq!(malformed.len(), 1);
+        assert_eq!(malformed[0].collection, CONNECTIONS_COLLECTION);
+        assert_eq!(malformed[0].id.as_deref(), Some(bad_id.as_str()));
+        assert!(malformed[0].error.contains("created_at"));
+
+        drop(system_db);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DatabaseInfo {
+    /// Stable handle accepted in place of the path by every operation
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    pub path: String,
+    pub collections: Vec<CollectionInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CollectionInfo {
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseStats {
+    pub path: String,
+    pub collections: usize,
+    pub total_documents: usize,
+    /// `(name, count)` pairs
+    pub collections_detail: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VectorCollectionInfoResponse {
+    pub name: String,
+    pub count: usize,
+    pub dimensions: usize,
+    pub distance: String,
+}
+
+/// Size of a vector collection as reported by `vector_stats`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VectorCollectionSize {
+    pub name: String,
+    pub vector_count: usize,
+    pub memory_bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VectorSearchResultResponse {
+    pub id: u64,
+    pub score: f32,
+    pub vector: Vec<f32>,
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub metadata: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VectorDocumentResponse {
+    pub id: u64,
+    pub vector: Vec<f32>,
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub metadata: Option<serde_json::Value>,
+    /// Always 0; keradb does not record when a vector was inserted
+    pub created_at: u64,
+}
+
+/// A page of vectors from a collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorPage {
+    pub vectors: Vec<VectorDocumentResponse>,
+    pub total: usize,
+    pub limit: usize,
+    pub skip: usize,
+}
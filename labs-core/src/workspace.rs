@@ -0,0 +1,196 @@
+//! Workspace bundles: the connections, connection profiles and saved queries
+//! of a system database as one portable JSON document, so a teammate can
+//! import them on another machine. Statistics, history, tokens and the audit
+//! log stay behind.
+//!
+//! Paths are remapped on import by prefix rules and then resolved like any
+//! other database path, so Windows drive paths still end up under `/mnt`.
+
+use crate::queries::QuerySpec;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Identifies a workspace bundle
+pub const BUNDLE_FORMAT: &str = "keradb-labs-workspace";
+
+/// Bundle layout written by this build
+pub const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WorkspaceBundle {
+    /// Always `keradb-labs-workspace`
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    #[serde(default)]
+    pub connections: Vec<WorkspaceConnection>,
+    #[serde(default)]
+    pub saved_queries: Vec<WorkspaceQuery>,
+}
+
+/// A registered database and its profile settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WorkspaceConnection {
+    /// Path or logical name on the exporting machine
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub favorite: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+}
+
+/// A saved query and the database it belongs to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WorkspaceQuery {
+    /// Path or logical name on the exporting machine
+    pub database_path: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub query: QuerySpec,
+}
+
+/// Replaces a leading `from` with `to`, e.g. `D:\projects` with
+/// `/home/ada/projects`. Only whole path components match.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PathRemap {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WorkspaceImport {
+    pub bundle: WorkspaceBundle,
+    /// Tried in order; the first matching rule is applied
+    #[serde(default)]
+    pub remap: Vec<PathRemap>,
+}
+
+/// What an import did. Settings present in the bundle replace local ones;
+/// saved queries whose name is already taken are skipped.
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportReport {
+    pub connections_created: usize,
+    pub connections_updated: usize,
+    pub saved_queries_created: usize,
+    /// Imported databases whose file does not exist on this machine yet
+    pub missing: Vec<String>,
+    /// Items left out or imported partially, with the reason
+    pub skipped: Vec<ImportIssue>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportIssue {
+    /// Database path, or `path: query name` for saved queries
+    pub item: String,
+    pub reason: String,
+}
+
+impl ImportReport {
+    pub fn skip(&mut self, item: impl Into<String>, reason: impl ToString) {
+        self.skipped.push(ImportIssue {
+            item: item.into(),
+            reason: reason.to_string(),
+        });
+    }
+}
+
+impl WorkspaceBundle {
+    pub fn new(connections: Vec<WorkspaceConnection>, saved_queries: Vec<WorkspaceQuery>) -> Self {
+        Self {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            exported_at: Utc::now(),
+            connections,
+            saved_queries,
+        }
+    }
+
+    /// Reject files that are not bundles or come from a newer version
+    pub fn validate(&self) -> Result<(), String> {
+        if self.format != BUNDLE_FORMAT {
+            return Err(format!("Not a workspace bundle (format {:?})", self.format));
+        }
+        if self.version > BUNDLE_VERSION {
+            return Err(format!(
+                "Workspace bundle version {} is newer than this build supports ({})",
+                self.version, BUNDLE_VERSION
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Apply the first matching rule to `path`. The rest of a remapped path
+/// takes the separator of `to`, so Windows paths can be mapped to Unix ones.
+pub fn remap_path(path: &str, rules: &[PathRemap]) -> String {
+    for rule in rules {
+        let from = rule.from.trim_end_matches(['/', '\\']);
+        let Some(rest) = strip_prefix_ignoring_separators(path, from) else {
+            continue;
+        };
+        if !(rest.is_empty() || rest.starts_with(['/', '\\'])) {
+            continue;
+        }
+
+        let to = rule.to.trim_end_matches(['/', '\\']);
+        let rest = if to.contains('\\') {
+            rest.replace('/', "\\")
+        } else {
+            rest.replace('\\', "/")
+        };
+        return format!("{}{}", to, rest);
+    }
+    path.to_string()
+}
+
+// `/` and `\` are interchangeable in the prefix; drive letters are matched
+// without regard to case
+fn strip_prefix_ignoring_separators<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    if path.len() < prefix.len() || !path.is_char_boundary(prefix.len()) {
+        return None;
+    }
+    let (head, rest) = path.split_at(prefix.len());
+    let same = head.chars().zip(prefix.chars()).enumerate().all(|(i, (a, b))| {
+        a == b || (matches!(a, '/' | '\\') && matches!(b, '/' | '\\')) || (i == 0 && a.eq_ignore_ascii_case(&b))
+    });
+    same.then_some(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remap_path() {
+        let rules = vec![
+            PathRemap {
+                from: "D:\\projects\\".to_string(),
+                to: "/home/ada/projects".to_string(),
+            },
+            PathRemap {
+                from: "/srv/data".to_string(),
+                to: "/data".to_string(),
+            },
+        ];
+        assert_eq!(remap_path("d:\\projects\\acme\\a.db", &rules), "/home/ada/projects/acme/a.db");
+        assert_eq!(remap_path("D:/projects/a.db", &rules), "/home/ada/projects/a.db");
+        assert_eq!(remap_path("/srv/data/b.db", &rules), "/data/b.db");
+        // Only whole components match
+        assert_eq!(remap_path("/srv/database/b.db", &rules), "/srv/database/b.db");
+        assert_eq!(remap_path("E:\\c.db", &rules), "E:\\c.db");
+    }
+}
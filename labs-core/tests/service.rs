@@ -0,0 +1,695 @@
+//! Behaviour shared by the REST handlers and the Tauri commands, which are
+//! thin adapters over `LabsService`.
+
+use keradb_labs_core::audit::{self, AuditAction, AuditQuery};
+use keradb_labs_core::changes::ChangeOp;
+use keradb_labs_core::connections::{ConnectionProfileUpdate, ConnectionQuery, ConnectionSort};
+use keradb_labs_core::jobs::{JobManager, JobRequest};
+use keradb_labs_core::metrics::{Granularity, MetricsQuery, MetricsRetention};
+use keradb_labs_core::queries::{QueryHistoryQuery, QueryResults, QuerySpec, SavedQueryRequest, SortField};
+use keradb_labs_core::sandbox::DatabaseRoots;
+use keradb_labs_core::slow_log::{self, Caller, SlowOperationQuery};
+use keradb_labs_core::system_db::{JobStatus, SystemDatabase};
+use keradb_labs_core::workspace::{PathRemap, WorkspaceImport};
+use keradb_labs_core::{ErrorCode, LabsService};
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn temp_dir(label: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("keradb-labs-service-{}-{}", label, std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Each test gets its own throwaway system database
+fn service(roots: DatabaseRoots) -> LabsService {
+    LabsService::new(SystemDatabase::ephemeral().unwrap(), roots)
+}
+
+#[test]
+fn test_document_round_trip_by_any_handle() {
+    let dir = temp_dir("documents");
+    let service = service(DatabaseRoots::default());
+    let path = dir.join("app.db").to_string_lossy().into_owned();
+
+    let info = service.open_database(&service.resolve(&path).unwrap(), Some("app")).unwrap();
+    assert_eq!(info.path, path);
+    assert_eq!(info.alias.as_deref(), Some("app"));
+    assert!(info.collections.is_empty());
+
+    let id = service.insert_document("app", "users", json!({ "name": "ada" })).unwrap();
+    let doc = service.find_by_id(&info.id, "users", &id).unwrap();
+    assert_eq!(doc["name"], "ada");
+
+    service.update_document(&path, "users", &id, json!({ "name": "grace" })).unwrap();
+    let docs = service.find_documents("app", "users", None, None).unwrap();
+    assert_eq!(docs.len(), 1);
+    assert_eq!(docs[0]["name"], "grace");
+
+    let stats = service.database_stats("app").unwrap();
+    assert_eq!(stats.path, path);
+    assert_eq!(stats.total_documents, 1);
+
+    service.delete_document("app", "users", &id).unwrap();
+    let missing = service.find_by_id("app", "users", &id).unwrap_err();
+    assert_eq!(missing.code, ErrorCode::DocumentNotFound);
+
+    let operations: Vec<String> = service
+        .metrics("app", None)
+        .unwrap()
+        .into_iter()
+        .map(|metric| metric.operation)
+        .collect();
+    assert!(operations.contains(&"open_database".to_string()));
+    assert!(operations.contains(&"insert_document".to_string()));
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_closed_and_unknown_databases() {
+    let dir = temp_dir("closed");
+    let service = service(DatabaseRoots::default());
+    let path = dir.join("closed.db").to_string_lossy().into_owned();
+
+    let error = service.collections(&path).unwrap_err();
+    assert_eq!(error.code, ErrorCode::DbNotOpen);
+
+    service.create_database(&service.resolve(&path).unwrap(), None).unwrap();
+    assert_eq!(service.list_databases(), vec![path.clone()]);
+
+    assert_eq!(service.close_database(&path).unwrap(), path);
+    assert!(service.list_databases().is_empty());
+    assert_eq!(service.insert_document(&path, "c", json!({})).unwrap_err().code, ErrorCode::DbNotOpen);
+
+    // The connection is remembered until removed
+    assert_eq!(service.connection_history(&ConnectionQuery::default()).unwrap().len(), 1);
+    service.remove_connection(&path).unwrap();
+    assert!(service.connection_history(&ConnectionQuery::default()).unwrap().is_empty());
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_connection_profiles() {
+    let dir = temp_dir("profiles");
+    let service = service(DatabaseRoots::default());
+    let first = dir.join("first.db").to_string_lossy().into_owned();
+    let second = dir.join("second.db").to_string_lossy().into_owned();
+    service.create_database(&service.resolve(&first).unwrap(), None).unwrap();
+    service.create_database(&service.resolve(&second).unwrap(), Some("second")).unwrap();
+
+    let update = ConnectionProfileUpdate {
+        alias: Some("first".to_string()),
+        color: Some("#f80".to_string()),
+        favorite: Some(true),
+        notes: Some("Reference data".to_string()),
+        folder: Some("team/reference".to_string()),
+    };
+    let conn = service.update_connection_profile(&first, &update).unwrap();
+    assert_eq!(conn.alias.as_deref(), Some("first"));
+    assert!(conn.favorite);
+
+    // Profiles survive being reopened, and only the given fields change
+    service.close_database("first").unwrap();
+    service.open_database(&service.resolve(&first).unwrap(), None).unwrap();
+    let unfavorite = ConnectionProfileUpdate {
+        favorite: Some(false),
+        ..Default::default()
+    };
+    let conn = service.update_connection_profile("first", &unfavorite).unwrap();
+    assert_eq!(conn.access_count, 2);
+    assert_eq!(conn.folder.as_deref(), Some("team/reference"));
+    assert!(!conn.favorite);
+
+    let taken = ConnectionProfileUpdate {
+        alias: Some("second".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(service.update_connection_profile("first", &taken).unwrap_err().code, ErrorCode::InvalidRequest);
+    assert_eq!(
+        service.update_connection_profile("missing", &unfavorite).unwrap_err().code,
+        ErrorCode::NotFound
+    );
+
+    let by_name = ConnectionQuery {
+        sort: Some(ConnectionSort::Name),
+        ..Default::default()
+    };
+    let names: Vec<_> = service
+        .connection_history(&by_name)
+        .unwrap()
+        .into_iter()
+        .map(|conn| conn.alias.unwrap())
+        .collect();
+    assert_eq!(names, vec!["first", "second"]);
+
+    let in_team = ConnectionQuery {
+        folder: Some("team".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(service.connection_history(&in_team).unwrap().len(), 1);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_drop_collection_and_delete_database() {
+    let dir = temp_dir("drop");
+    let service = service(DatabaseRoots::default());
+    let path = dir.join("drop.db");
+    let name = path.to_string_lossy().into_owned();
+
+    service.open_database(&service.resolve(&name).unwrap(), None).unwrap();
+    for i in 0..3 {
+        service.insert_document(&name, "items", json!({ "i": i })).unwrap();
+    }
+
+    assert_eq!(service.drop_collection(&name, "items").unwrap(), 3);
+    assert!(service.find_documents(&name, "items", None, None).unwrap().is_empty());
+
+    assert_eq!(service.delete_database(&name).unwrap(), name);
+    assert!(!path.exists());
+    assert!(service.connection_history(&ConnectionQuery::default()).unwrap().is_empty());
+    assert_eq!(service.delete_database(&name).unwrap_err().code, ErrorCode::NotFound);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_vector_operations() {
+    let dir = temp_dir("vectors");
+    let service = service(DatabaseRoots::default());
+    let name = dir.join("vectors.db").to_string_lossy().into_owned();
+    service.open_database(&service.resolve(&name).unwrap(), Some("vec")).unwrap();
+
+    let error = service.create_vector_collection("vec", "empty", 0, "cosine", None).unwrap_err();
+    assert_eq!(error.code, ErrorCode::InvalidRequest);
+
+    service.create_vector_collection("vec", "embeddings", 3, "l2", Some(8)).unwrap();
+    let collections = service.vector_collections("vec").unwrap();
+    assert_eq!(collections.len(), 1);
+    assert_eq!(collections[0].dimensions, 3);
+
+    let id = service
+        .insert_vector("vec", "embeddings", vec![1.0, 0.0, 0.0], Some(json!({ "tag": "x" })))
+        .unwrap();
+    service.insert_vector("vec", "embeddings", vec![0.0, 1.0, 0.0], None).unwrap();
+
+    let error = service.insert_vector("vec", "embeddings", vec![1.0], None).unwrap_err();
+    assert_eq!(error.code, ErrorCode::DimensionMismatch);
+    let error = service.vector_search("vec", "missing", &[1.0, 0.0, 0.0], 1).unwrap_err();
+    assert_eq!(error.code, ErrorCode::CollectionNotFound);
+
+    let vector = service.get_vector("vec", "embeddings", id).unwrap();
+    assert_eq!(vector.metadata, Some(json!({ "tag": "x" })));
+
+    let sizes = service.vector_collection_sizes("vec").unwrap();
+    assert_eq!((sizes[0].name.as_str(), sizes[0].vector_count), ("embeddings", 2));
+
+    let page = service.vectors("vec", "embeddings", Some(1), Some(1)).unwrap();
+    assert_eq!(page.total, 2);
+    assert_eq!(page.vectors.len(), 1);
+    assert_eq!(page.vectors[0].metadata, None);
+
+    let results = service.vector_search("vec", "embeddings", &[1.0, 0.0, 0.0], 2).unwrap();
+    assert!(!results.is_empty());
+
+    assert!(service.delete_vector("vec", "embeddings", id).unwrap());
+    assert_eq!(service.get_vector("vec", "embeddings", id).unwrap_err().code, ErrorCode::VectorNotFound);
+    assert!(service.drop_vector_collection("vec", "embeddings").unwrap());
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_databases_are_confined_to_roots() {
+    let dir = temp_dir("roots");
+    let root = dir.join("data");
+    let roots = DatabaseRoots::new(&[("main".to_string(), root.clone())].into_iter().collect()).unwrap();
+    let service = service(roots);
+
+    let info = service.open_database(&service.resolve("main/users.db").unwrap(), None).unwrap();
+    assert_eq!(info.path, "main/users.db");
+    assert!(root.join("users.db").exists());
+
+    // Connection ids and filesystem paths inside the root map to the logical name
+    let by_path = root.join("users.db").to_string_lossy().into_owned();
+    assert_eq!(service.database_name(&by_path).unwrap(), "main/users.db");
+    assert_eq!(service.database_name(&info.id).unwrap(), "main/users.db");
+
+    let outside = dir.join("outside.db").to_string_lossy().into_owned();
+    let error = service.collections(&outside).unwrap_err();
+    assert_eq!(error.code, ErrorCode::PathOutsideRoots);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_mutations_publish_changes() {
+    let dir = temp_dir("changes");
+    let service = service(DatabaseRoots::default());
+    let name = dir.join("changes.db").to_string_lossy().into_owned();
+    let info = service.open_database(&service.resolve(&name).unwrap(), Some("live")).unwrap();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    let subscription = service
+        .subscribe_changes(Some(&info.id), Some("users"), None, move |event| {
+            sink.lock().unwrap().push((event.seq, event.op, event.id.clone()));
+            true
+        })
+        .unwrap();
+    assert!(!subscription.missed);
+
+    let id = service.insert_document("live", "users", json!({ "name": "ada" })).unwrap();
+    service.insert_document("live", "other", json!({})).unwrap();
+    service.update_document("live", "users", &id, json!({ "name": "grace" })).unwrap();
+    service.delete_document("live", "users", &id).unwrap();
+    service.drop_collection("live", "users").unwrap();
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            (1, ChangeOp::Insert, Some(id.clone())),
+            (3, ChangeOp::Update, Some(id.clone())),
+            (4, ChangeOp::Delete, Some(id)),
+            (5, ChangeOp::Drop, None),
+        ]
+    );
+
+    // A drop stopped early only publishes the documents it deleted
+    for name in ["ada", "grace"] {
+        service.insert_document("live", "users", json!({ "name": name })).unwrap();
+    }
+    assert_eq!(service.drop_collection_with_progress("live", "users", |_, _| false).unwrap(), 1);
+    let (seq, op, id) = events.lock().unwrap().last().cloned().unwrap();
+    assert_eq!((seq, op), (8, ChangeOp::Delete));
+    assert!(id.is_some());
+    let audited = service
+        .audit_log(&AuditQuery { action: Some(AuditAction::DeleteDocument), limit: Some(1), ..Default::default() }, |_| true)
+        .unwrap();
+    assert_eq!(audited[0].target_id, id);
+    assert!(audited[0].before.is_some());
+
+    assert!(service.changes().unsubscribe(subscription.id));
+    assert_eq!(service.changes().last_seq(), 8);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_background_jobs() {
+    let dir = temp_dir("jobs");
+    let service = Arc::new(service(DatabaseRoots::default()));
+    let name = dir.join("jobs.db").to_string_lossy().into_owned();
+    service.open_database(&service.resolve(&name).unwrap(), Some("jobs")).unwrap();
+
+    let jobs = JobManager::new(service.clone());
+    let wait = |id: &str| loop {
+        let job = jobs.get(id).unwrap();
+        if job.status.is_finished() {
+            return job;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    };
+
+    let import = jobs
+        .submit(JobRequest::ImportDocuments {
+            db: "jobs".to_string(),
+            collection: "items".to_string(),
+            documents: (0..5).map(|i| json!({ "i": i })).collect(),
+        })
+        .unwrap();
+    assert_eq!(import.total, 5);
+    assert_eq!(import.database, name);
+
+    let import = wait(&import.id);
+    assert_eq!(import.status, JobStatus::Completed);
+    assert_eq!(import.done, 5);
+    assert_eq!(import.result, Some(json!({ "inserted": 5 })));
+
+    let drop = jobs
+        .submit(JobRequest::DropCollection {
+            db: "jobs".to_string(),
+            collection: "items".to_string(),
+        })
+        .unwrap();
+    assert_eq!(wait(&drop.id).result, Some(json!({ "documents_deleted": 5 })));
+
+    // Jobs submitted locally are audited without a caller
+    let local = AuditQuery {
+        action: Some(AuditAction::DropCollection),
+        actor: Some(audit::LOCAL_ACTOR.to_string()),
+        ..Default::default()
+    };
+    let dropped = service.audit_log(&local, |_| true).unwrap();
+    assert_eq!(dropped.len(), 1);
+    assert!(dropped[0].caller.is_none());
+
+    let error = jobs
+        .submit(JobRequest::DropCollection {
+            db: dir.join("closed.db").to_string_lossy().into_owned(),
+            collection: "items".to_string(),
+        })
+        .unwrap_err();
+    assert_eq!(error.code, ErrorCode::DbNotOpen);
+
+    // History is persisted, most recent first
+    let history = service.system_db().list_jobs(None).unwrap();
+    assert_eq!(history.iter().map(|job| job.kind.as_str()).collect::<Vec<_>>(), vec!["drop_collection", "import_documents"]);
+    assert!(history.iter().all(|job| job.status == JobStatus::Completed));
+    assert_eq!(jobs.get("missing").unwrap_err().code, ErrorCode::NotFound);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_metric_rollups_and_compaction() {
+    let dir = temp_dir("metrics");
+    let service = service(DatabaseRoots::default());
+    let name = dir.join("metrics.db").to_string_lossy().into_owned();
+    service.open_database(&service.resolve(&name).unwrap(), Some("m")).unwrap();
+    for i in 0..4 {
+        service.insert_document("m", "items", json!({ "i": i })).unwrap();
+    }
+
+    let query = MetricsQuery {
+        db: Some("m".to_string()),
+        operation: Some("insert_document".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(service.query_metrics(&query, |_| true).unwrap().len(), 4);
+
+    // The current bucket is computed from raw samples
+    let live = service.metric_rollups(&query).unwrap();
+    assert_eq!(live.iter().map(|r| r.count).sum::<u64>(), 4);
+
+    // Two days on, every bucket is closed: rolled up, then raw samples pruned
+    let later = chrono::Utc::now() + chrono::Duration::days(2);
+    let stats = service.system_db().compact_metrics(&MetricsRetention::default(), later).unwrap();
+    // open_database and insert_document at each granularity, more if a minute boundary passed
+    assert!(stats.rollups_created >= 6);
+    assert_eq!(stats.samples_pruned, 5);
+    assert!(service.query_metrics(&query, |_| true).unwrap().is_empty());
+
+    for granularity in Granularity::ALL {
+        let rollups = service
+            .metric_rollups(&MetricsQuery { granularity: Some(granularity), ..query.clone() })
+            .unwrap();
+        assert_eq!(rollups.iter().map(|r| r.count).sum::<u64>(), 4);
+    }
+
+    // Compacting again creates nothing new
+    let stats = service.system_db().compact_metrics(&MetricsRetention::default(), later).unwrap();
+    assert_eq!(stats.rollups_created, 0);
+
+    // Samples of another database arriving late, in buckets already rolled
+    // up for the first one, are still rolled up before being pruned
+    let late = dir.join("late.db").to_string_lossy().into_owned();
+    service.open_database(&service.resolve(&late).unwrap(), Some("n")).unwrap();
+    service.insert_document("n", "items", json!({ "i": 0 })).unwrap();
+    let stats = service.system_db().compact_metrics(&MetricsRetention::default(), later).unwrap();
+    assert_eq!(stats.samples_pruned, 2);
+    let late_query = MetricsQuery { db: Some("n".to_string()), ..query.clone() };
+    for granularity in Granularity::ALL {
+        let rollups = service
+            .metric_rollups(&MetricsQuery { granularity: Some(granularity), ..late_query.clone() })
+            .unwrap();
+        assert_eq!(rollups.iter().map(|r| r.count).sum::<u64>(), 1);
+    }
+
+    let error = service
+        .metric_rollups(&MetricsQuery { from: Some(later), to: Some(later), ..Default::default() })
+        .unwrap_err();
+    assert_eq!(error.code, ErrorCode::InvalidRequest);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_slow_operation_log() {
+    let dir = temp_dir("slow-ops");
+    // Every operation counts as slow
+    let service = service(DatabaseRoots::default()).with_slow_op_threshold(Some(Duration::ZERO));
+    let name = dir.join("slow.db").to_string_lossy().into_owned();
+    service.open_database(&service.resolve(&name).unwrap(), Some("slow")).unwrap();
+    service.insert_document("slow", "items", json!({ "n": 1 })).unwrap();
+
+    let caller = Caller {
+        token_name: Some("ci".to_string()),
+        ip: Some("10.0.0.7".to_string()),
+        ..Default::default()
+    };
+    let found = slow_log::with_caller(&caller, || service.find_documents("slow", "items", Some(5), None).unwrap());
+    assert_eq!(found.len(), 1);
+
+    let query = SlowOperationQuery {
+        db: Some("slow".to_string()),
+        operation: Some("find_documents".to_string()),
+        ..Default::default()
+    };
+    let logged = service.slow_operations(&query, |_| true).unwrap();
+    assert_eq!(logged.len(), 1);
+    assert_eq!(logged[0].collection.as_deref(), Some("items"));
+    assert_eq!(logged[0].params, Some(json!({ "limit": 5, "skip": null })));
+    assert_eq!(logged[0].result_size, Some(1));
+    assert_eq!(logged[0].caller.as_ref(), Some(&caller));
+
+    // Calls outside a caller scope, such as the desktop app's, have none
+    let inserts = service
+        .slow_operations(&SlowOperationQuery { operation: Some("insert_document".to_string()), ..query.clone() }, |_| true)
+        .unwrap();
+    assert_eq!(inserts[0].caller, None);
+    let slowest = SlowOperationQuery { min_ms: Some(60_000), ..query };
+    assert!(service.slow_operations(&slowest, |_| true).unwrap().is_empty());
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_saved_queries_and_history() {
+    let dir = temp_dir("queries");
+    let service = service(DatabaseRoots::default());
+    let name = dir.join("queries.db").to_string_lossy().into_owned();
+    service.open_database(&service.resolve(&name).unwrap(), Some("q")).unwrap();
+    for (user, age) in [("ada", 36), ("grace", 85), ("linus", 28)] {
+        service.insert_document("q", "users", json!({ "name": user, "age": age })).unwrap();
+    }
+
+    let request = SavedQueryRequest {
+        name: "Under 40".to_string(),
+        description: None,
+        query: QuerySpec::Documents {
+            collection: "users".to_string(),
+            filter: Some(json!({ "age": { "$lt": 40 } })),
+            sort: vec![SortField { field: "age".to_string(), desc: false }],
+            projection: Some(vec!["name".to_string()]),
+            limit: None,
+            skip: None,
+        },
+    };
+    let saved = service.create_saved_query("q", &request).unwrap();
+    assert_eq!(service.create_saved_query("q", &request).unwrap_err().code, ErrorCode::InvalidRequest);
+
+    let QueryResults::Documents { documents, matched } = service.run_saved_query("q", &saved.id).unwrap() else {
+        panic!("expected documents");
+    };
+    assert_eq!(matched, 2);
+    let names: Vec<_> = documents.iter().map(|doc| doc["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["linus", "ada"]);
+    assert!(documents[0].get("age").is_none());
+
+    let renamed = SavedQueryRequest {
+        name: "Young".to_string(),
+        ..request.clone()
+    };
+    assert_eq!(service.update_saved_query("q", &saved.id, &renamed).unwrap().name, "Young");
+    assert_eq!(service.saved_queries("q").unwrap().len(), 1);
+
+    // Ad hoc queries are recorded too, most recent first
+    let invalid = QuerySpec::Documents {
+        collection: "users".to_string(),
+        filter: Some(json!({ "age": { "$regex": "8" } })),
+        sort: Vec::new(),
+        projection: None,
+        limit: None,
+        skip: None,
+    };
+    assert_eq!(service.run_query("q", &invalid).unwrap_err().code, ErrorCode::InvalidRequest);
+    let all = QuerySpec::Documents {
+        collection: "users".to_string(),
+        filter: None,
+        sort: Vec::new(),
+        projection: None,
+        limit: Some(1),
+        skip: None,
+    };
+    assert_eq!(service.run_query("q", &all).unwrap().len(), 1);
+
+    let history = service.query_history("q", &QueryHistoryQuery::default()).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].query, all);
+    assert_eq!(history[1].saved_query_id.as_deref(), Some(saved.id.as_str()));
+    assert_eq!(history[1].result_count, 2);
+
+    assert_eq!(service.rerun_query("q", &history[1].id).unwrap().len(), 2);
+    assert_eq!(service.query_history("q", &QueryHistoryQuery::default()).unwrap().len(), 3);
+
+    service.delete_saved_query("q", &saved.id).unwrap();
+    assert_eq!(service.run_saved_query("q", &saved.id).unwrap_err().code, ErrorCode::NotFound);
+    assert_eq!(service.clear_query_history("q").unwrap(), 3);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_audit_log_keeps_before_images() {
+    let dir = temp_dir("audit");
+    let service = service(DatabaseRoots::default());
+    let name = dir.join("audit.db").to_string_lossy().into_owned();
+    service.open_database(&service.resolve(&name).unwrap(), Some("audited")).unwrap();
+
+    let id = service.insert_document("audited", "users", json!({ "name": "ada", "age": 36 })).unwrap();
+    let caller = Caller {
+        token_id: Some("t1".to_string()),
+        token_name: Some("ci".to_string()),
+        ip: Some("10.0.0.7".to_string()),
+    };
+    slow_log::with_caller(&caller, || {
+        service.update_document("audited", "users", &id, json!({ "name": "ada", "age": 37 })).unwrap();
+        service.delete_document("audited", "users", &id).unwrap();
+    });
+
+    // Most recent first, each delete and update with the document it replaced
+    let entries = service.audit_log(&AuditQuery { db: Some("audited".to_string()), ..Default::default() }, |_| true).unwrap();
+    let actions: Vec<_> = entries.iter().map(|e| e.action).collect();
+    assert_eq!(
+        actions,
+        vec![AuditAction::DeleteDocument, AuditAction::UpdateDocument, AuditAction::InsertDocument]
+    );
+    assert_eq!(entries[0].before.as_ref().unwrap()["age"], 37);
+    assert_eq!(entries[1].before.as_ref().unwrap()["age"], 36);
+    assert_eq!(entries[1].target_id.as_deref(), Some(id.as_str()));
+    assert!(entries[2].before.is_none() && entries[2].caller.is_none());
+
+    let by_actor = |actor: &str| AuditQuery {
+        actor: Some(actor.to_string()),
+        ..Default::default()
+    };
+    assert_eq!(service.audit_log(&by_actor("ci"), |_| true).unwrap().len(), 2);
+    assert_eq!(service.audit_log(&by_actor(audit::LOCAL_ACTOR), |_| true).unwrap().len(), 1);
+
+    let backwards = AuditQuery {
+        from: Some(chrono::Utc::now()),
+        to: Some(chrono::Utc::now() - chrono::Duration::hours(1)),
+        ..Default::default()
+    };
+    assert_eq!(service.audit_log(&backwards, |_| true).unwrap_err().code, ErrorCode::InvalidRequest);
+
+    // The export is oldest first, one entry per line
+    let mut out = Vec::new();
+    assert_eq!(service.export_audit_log(&AuditQuery::default(), |_| true, &mut out).unwrap(), 3);
+    let lines: Vec<serde_json::Value> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["action"], "insert_document");
+    assert_eq!(lines[2]["action"], "delete_document");
+
+    // Bulk deletes keep what they removed
+    for name in ["grace", "linus"] {
+        service.insert_document("audited", "users", json!({ "name": name })).unwrap();
+    }
+    assert_eq!(service.drop_collection("audited", "users").unwrap(), 2);
+    let latest = |action| {
+        let query = AuditQuery { action: Some(action), limit: Some(1), ..Default::default() };
+        service.audit_log(&query, |_| true).unwrap().remove(0)
+    };
+    let before = latest(AuditAction::DropCollection).before.unwrap();
+    let mut names: Vec<_> = before.as_array().unwrap().iter().filter_map(|doc| doc["name"].as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["grace", "linus"]);
+
+    service.insert_document("audited", "posts", json!({ "title": "hello" })).unwrap();
+    service.delete_database("audited").unwrap();
+    let collections = latest(AuditAction::DeleteDatabase).before.unwrap()["collections"].clone();
+    assert!(collections.as_array().unwrap().iter().any(|c| c["name"] == "posts" && c["count"] == 1));
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_workspace_export_and_import() {
+    let dir = temp_dir("workspace");
+    let exporter = service(DatabaseRoots::default());
+    std::fs::create_dir_all(dir.join("team")).unwrap();
+    let path = dir.join("team").join("crm.db").to_string_lossy().into_owned();
+    exporter.open_database(&exporter.resolve(&path).unwrap(), Some("crm")).unwrap();
+    let update = ConnectionProfileUpdate {
+        color: Some("teal".to_string()),
+        favorite: Some(true),
+        folder: Some("clients".to_string()),
+        ..Default::default()
+    };
+    exporter.update_connection_profile("crm", &update).unwrap();
+    let request = SavedQueryRequest {
+        name: "Everyone".to_string(),
+        description: None,
+        query: QuerySpec::Documents {
+            collection: "users".to_string(),
+            filter: None,
+            sort: Vec::new(),
+            projection: None,
+            limit: None,
+            skip: None,
+        },
+    };
+    exporter.create_saved_query("crm", &request).unwrap();
+
+    let bundle = exporter.export_workspace(|_| true).unwrap();
+    assert_eq!(bundle.connections.len(), 1);
+    assert!(exporter.export_workspace(|_| false).unwrap().saved_queries.is_empty());
+
+    // Through JSON and onto another machine where the team folder lives elsewhere
+    let bundle = serde_json::from_str(&serde_json::to_string(&bundle).unwrap()).unwrap();
+    let moved = dir.join("moved").to_string_lossy().into_owned();
+    let import = WorkspaceImport {
+        bundle,
+        remap: vec![PathRemap {
+            from: dir.join("team").to_string_lossy().into_owned(),
+            to: moved.clone(),
+        }],
+    };
+    let importer = service(DatabaseRoots::default());
+    let report = importer.import_workspace(&import, |_| true).unwrap();
+    assert_eq!(report.connections_created, 1);
+    assert_eq!(report.saved_queries_created, 1);
+    assert!(report.skipped.is_empty());
+    let imported = format!("{}/crm.db", moved);
+    assert_eq!(report.missing, vec![imported.clone()]);
+
+    let conn = &importer.connection_history(&ConnectionQuery::default()).unwrap()[0];
+    assert_eq!(conn.path, imported);
+    assert_eq!(conn.alias.as_deref(), Some("crm"));
+    assert_eq!(conn.color.as_deref(), Some("teal"));
+    assert!(conn.favorite);
+    assert_eq!(importer.saved_queries("crm").unwrap()[0].name, "Everyone");
+
+    // Importing again updates instead of duplicating
+    let report = importer.import_workspace(&import, |_| true).unwrap();
+    assert_eq!((report.connections_created, report.connections_updated), (0, 1));
+    assert_eq!(report.saved_queries_created, 0);
+    assert_eq!(report.skipped.len(), 1);
+
+    let mut foreign = import.clone();
+    foreign.bundle.format = "something-else".to_string();
+    assert_eq!(importer.import_workspace(&foreign, |_| true).unwrap_err().code, ErrorCode::InvalidRequest);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -1,162 +1,67 @@
 // Prevents additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod system_db;
-
-use keradb::{Database, VectorConfig, Distance};
-use parking_lot::RwLock;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use keradb_labs_core::audit::{AuditEntry, AuditQuery};
+use keradb_labs_core::changes::{ResumePoint, Subscription};
+use keradb_labs_core::connections::{ConnectionProfileUpdate, ConnectionQuery};
+use keradb_labs_core::document_parser::ArchiveLimits;
+use keradb_labs_core::jobs::{JobManager, JobRequest};
+use keradb_labs_core::metrics::{self, MetricRollup, MetricsQuery, MetricsRetention};
+use keradb_labs_core::parsing::{self, ParseProgress};
+use keradb_labs_core::queries::{QueryHistoryQuery, QueryResults, QuerySpec, SavedQueryRequest};
+use keradb_labs_core::sandbox::DatabaseRoots;
+use keradb_labs_core::slow_log::SlowOperationQuery;
+use keradb_labs_core::system_db::{
+    DatabaseConnection, Job, PerformanceMetric, QueryHistoryEntry, SavedQuery, SlowOperation, SystemDatabase,
+};
+use keradb_labs_core::types::{
+    CollectionInfo, DatabaseInfo, DatabaseStats, VectorCollectionInfoResponse, VectorDocumentResponse,
+    VectorPage, VectorSearchResultResponse,
+};
+use keradb_labs_core::workspace::{ImportReport, PathRemap, WorkspaceImport};
+use keradb_labs_core::{ApiError, ApiResult, LabsService};
+use std::path::PathBuf;
 use std::sync::Arc;
-use system_db::SystemDatabase;
-use tauri::State;
-
-// App state
-struct AppState {
-    databases: Arc<RwLock<HashMap<String, Arc<Database>>>>,
-    system_db: Arc<SystemDatabase>,
-}
-
-// Request/Response types
-#[derive(Serialize, Deserialize)]
-struct DatabaseInfo {
-    path: String,
-    collections: Vec<CollectionInfo>,
-}
-
-#[derive(Serialize, Deserialize)]
-struct CollectionInfo {
-    name: String,
-    count: usize,
-}
-
-#[derive(Serialize, Deserialize)]
-struct InsertRequest {
-    collection: String,
-    document: serde_json::Value,
-}
+use tauri::{Manager, State, Window};
 
-#[derive(Serialize, Deserialize)]
-struct UpdateRequest {
-    collection: String,
-    id: String,
-    document: serde_json::Value,
-}
+/// Event carrying a `ParseProgress` while `parse_document_file` runs
+const PARSE_PROGRESS_EVENT: &str = "parse-progress";
 
-#[derive(Serialize, Deserialize)]
-struct DeleteRequest {
-    collection: String,
-    id: String,
-}
+/// Event carrying a `ChangeEvent` for every subscription of the window
+const DATABASE_CHANGE_EVENT: &str = "database-change";
 
-#[derive(Serialize, Deserialize)]
-struct QueryParams {
-    limit: Option<usize>,
-    skip: Option<usize>,
-}
+/// Event carrying a `Job` whenever a background job changes state or progresses
+const JOB_PROGRESS_EVENT: &str = "job-progress";
 
-// Tauri Commands
+// Commands are thin adapters over LabsService; `db_path` accepts a
+// connection id, alias or path everywhere.
 
 #[tauri::command]
-fn open_database(path: String, state: State<AppState>) -> Result<DatabaseInfo, String> {
-    let start = std::time::Instant::now();
-    
-    log::info!("Opening database: {}", path);
-    
-    // Open or create database
-    let db = match Database::open(&path) {
-        Ok(db) => db,
-        Err(_) => Database::create(&path).map_err(|e| e.to_string())?,
-    };
-
-    let collections: Vec<CollectionInfo> = db
-        .list_collections()
-        .into_iter()
-        .map(|(name, count)| CollectionInfo { name, count })
-        .collect();
-
-    let total_docs: usize = collections.iter().map(|c| c.count).sum();
-
-    // Store database in state
-    let mut databases = state.databases.write();
-    databases.insert(path.clone(), Arc::new(db));
-
-    // Register in system database
-    if let Err(e) = state.system_db.register_connection(&path) {
-        log::warn!("Failed to register connection in system db: {}", e);
-    }
-    
-    // Update stats
-    if let Err(e) = state.system_db.update_connection_stats(&path, collections.len(), total_docs) {
-        log::warn!("Failed to update connection stats: {}", e);
-    }
-
-    // Record metric
-    let duration = start.elapsed().as_millis() as u64;
-    if let Err(e) = state.system_db.record_metric(&path, "open_database", duration) {
-        log::warn!("Failed to record metric: {}", e);
-    }
-
-    Ok(DatabaseInfo {
-        path,
-        collections,
-    })
+fn open_database(
+    path: String,
+    alias: Option<String>,
+    service: State<Arc<LabsService>>,
+) -> ApiResult<DatabaseInfo> {
+    service.open_database(&service.resolve(&path)?, alias.as_deref())
 }
 
 #[tauri::command]
-fn create_database(path: String, state: State<AppState>) -> Result<DatabaseInfo, String> {
-    let start = std::time::Instant::now();
-    
-    log::info!("Creating database: {}", path);
-    
-    // Create new database
-    let db = Database::create(&path).map_err(|e| e.to_string())?;
-
-    let collections: Vec<CollectionInfo> = vec![];
-
-    // Store database in state
-    let mut databases = state.databases.write();
-    databases.insert(path.clone(), Arc::new(db));
-
-    // Register in system database
-    if let Err(e) = state.system_db.register_connection(&path) {
-        log::warn!("Failed to register connection in system db: {}", e);
-    }
-
-    // Record metric
-    let duration = start.elapsed().as_millis() as u64;
-    if let Err(e) = state.system_db.record_metric(&path, "create_database", duration) {
-        log::warn!("Failed to record metric: {}", e);
-    }
-
-    Ok(DatabaseInfo {
-        path,
-        collections,
-    })
+fn create_database(
+    path: String,
+    alias: Option<String>,
+    service: State<Arc<LabsService>>,
+) -> ApiResult<DatabaseInfo> {
+    service.create_database(&service.resolve(&path)?, alias.as_deref())
 }
 
 #[tauri::command]
-fn list_databases(state: State<AppState>) -> Result<Vec<String>, String> {
-    let databases = state.databases.read();
-    let db_list: Vec<String> = databases.keys().cloned().collect();
-    Ok(db_list)
+fn list_databases(service: State<Arc<LabsService>>) -> ApiResult<Vec<String>> {
+    Ok(service.list_databases())
 }
 
 #[tauri::command]
-fn get_collections(db_path: String, state: State<AppState>) -> Result<Vec<CollectionInfo>, String> {
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let collections: Vec<CollectionInfo> = db
-        .list_collections()
-        .into_iter()
-        .map(|(name, count)| CollectionInfo { name, count })
-        .collect();
-
-    Ok(collections)
+fn get_collections(db_path: String, service: State<Arc<LabsService>>) -> ApiResult<Vec<CollectionInfo>> {
+    service.collections(&db_path)
 }
 
 #[tauri::command]
@@ -164,27 +69,9 @@ fn insert_document(
     db_path: String,
     collection: String,
     document: serde_json::Value,
-    state: State<AppState>,
-) -> Result<String, String> {
-    let start = std::time::Instant::now();
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let id = db.insert(&collection, document).map_err(|e| e.to_string())?;
-
-    // Sync to disk
-    db.sync().map_err(|e| e.to_string())?;
-
-    // Record metric
-    let duration = start.elapsed().as_millis() as u64;
-    if let Err(e) = state.system_db.record_metric(&db_path, "insert_document", duration) {
-        log::warn!("Failed to record metric: {}", e);
-    }
-
-    Ok(id)
+    service: State<Arc<LabsService>>,
+) -> ApiResult<String> {
+    service.insert_document(&db_path, &collection, document)
 }
 
 #[tauri::command]
@@ -193,24 +80,9 @@ fn find_documents(
     collection: String,
     limit: Option<usize>,
     skip: Option<usize>,
-    state: State<AppState>,
-) -> Result<Vec<serde_json::Value>, String> {
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let docs = db
-        .find_all(&collection, limit, skip)
-        .map_err(|e| e.to_string())?;
-
-    let docs_json: Vec<serde_json::Value> = docs
-        .into_iter()
-        .map(|doc| doc.to_value())
-        .collect();
-
-    Ok(docs_json)
+    service: State<Arc<LabsService>>,
+) -> ApiResult<Vec<serde_json::Value>> {
+    service.find_documents(&db_path, &collection, limit, skip)
 }
 
 #[tauri::command]
@@ -218,19 +90,9 @@ fn find_by_id(
     db_path: String,
     collection: String,
     doc_id: String,
-    state: State<AppState>,
-) -> Result<serde_json::Value, String> {
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let doc = db
-        .find_by_id(&collection, &doc_id)
-        .map_err(|e| e.to_string())?;
-
-    Ok(doc.to_value())
+    service: State<Arc<LabsService>>,
+) -> ApiResult<serde_json::Value> {
+    service.find_by_id(&db_path, &collection, &doc_id)
 }
 
 #[tauri::command]
@@ -239,19 +101,9 @@ fn update_document(
     collection: String,
     id: String,
     document: serde_json::Value,
-    state: State<AppState>,
-) -> Result<serde_json::Value, String> {
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let doc = db
-        .update(&collection, &id, document)
-        .map_err(|e| e.to_string())?;
-
-    Ok(doc.to_value())
+    service: State<Arc<LabsService>>,
+) -> ApiResult<serde_json::Value> {
+    service.update_document(&db_path, &collection, &id, document)
 }
 
 #[tauri::command]
@@ -259,101 +111,166 @@ fn delete_document(
     db_path: String,
     collection: String,
     id: String,
-    state: State<AppState>,
-) -> Result<serde_json::Value, String> {
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
+    service: State<Arc<LabsService>>,
+) -> ApiResult<serde_json::Value> {
+    service.delete_document(&db_path, &collection, &id)
+}
 
-    let doc = db
-        .delete(&collection, &id)
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+fn get_stats(db_path: String, service: State<Arc<LabsService>>) -> ApiResult<DatabaseStats> {
+    service.database_stats(&db_path)
+}
 
-    Ok(doc.to_value())
+#[tauri::command]
+fn get_system_stats(service: State<Arc<LabsService>>) -> ApiResult<serde_json::Value> {
+    service.system_stats(|_| true, true)
 }
 
 #[tauri::command]
-fn get_stats(db_path: String, state: State<AppState>) -> Result<serde_json::Value, String> {
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
+fn get_connection_history(
+    query: Option<ConnectionQuery>,
+    service: State<Arc<LabsService>>,
+) -> ApiResult<Vec<DatabaseConnection>> {
+    service.connection_history(&query.unwrap_or_default())
+}
 
-    let collections = db.list_collections();
-    let total_docs: usize = collections.iter().map(|(_, count)| count).sum();
+#[tauri::command]
+fn update_connection_profile(
+    db_path: String,
+    update: ConnectionProfileUpdate,
+    service: State<Arc<LabsService>>,
+) -> ApiResult<DatabaseConnection> {
+    service.update_connection_profile(&db_path, &update)
+}
 
-    Ok(serde_json::json!({
-        "path": db_path,
-        "collections": collections.len(),
-        "total_documents": total_docs,
-        "collections_detail": collections
-    }))
+#[tauri::command]
+fn get_database_metrics(
+    db_path: String,
+    limit: Option<usize>,
+    service: State<Arc<LabsService>>,
+) -> ApiResult<Vec<PerformanceMetric>> {
+    service.metrics(&db_path, limit)
 }
 
 #[tauri::command]
-fn get_system_stats(state: State<AppState>) -> Result<serde_json::Value, String> {
-    let stats = state
-        .system_db
-        .get_system_stats()
-        .map_err(|e| e.to_string())?;
+fn query_metrics(query: MetricsQuery, service: State<Arc<LabsService>>) -> ApiResult<Vec<PerformanceMetric>> {
+    service.query_metrics(&query, |_| true)
+}
 
-    Ok(stats)
+#[tauri::command]
+fn get_metric_rollups(query: MetricsQuery, service: State<Arc<LabsService>>) -> ApiResult<Vec<MetricRollup>> {
+    service.metric_rollups(&query)
 }
 
 #[tauri::command]
-fn get_connection_history(state: State<AppState>) -> Result<serde_json::Value, String> {
-    let connections = state
-        .system_db
-        .list_connections()
-        .map_err(|e| e.to_string())?;
+fn get_slow_operations(query: SlowOperationQuery, service: State<Arc<LabsService>>) -> ApiResult<Vec<SlowOperation>> {
+    service.slow_operations(&query, |_| true)
+}
 
-    Ok(serde_json::to_value(connections).map_err(|e| e.to_string())?)
+#[tauri::command]
+fn get_audit_log(query: AuditQuery, service: State<Arc<LabsService>>) -> ApiResult<Vec<AuditEntry>> {
+    service.audit_log(&query, |_| true)
 }
 
+/// Write the matching audit entries to `path` as NDJSON; returns the count
 #[tauri::command]
-fn get_database_metrics(
+fn export_audit_log(query: AuditQuery, path: String, service: State<Arc<LabsService>>) -> ApiResult<usize> {
+    let file = std::fs::File::create(&path)
+        .map_err(|e| ApiError::invalid_request(format!("Cannot create {}: {}", path, e)))?;
+    let mut out = std::io::BufWriter::new(file);
+    let written = service.export_audit_log(&query, |_| true, &mut out)?;
+    std::io::Write::flush(&mut out).map_err(ApiError::internal)?;
+    Ok(written)
+}
+
+/// Write the workspace bundle to `path`; returns the number of connections
+#[tauri::command]
+fn export_workspace(path: String, service: State<Arc<LabsService>>) -> ApiResult<usize> {
+    let bundle = service.export_workspace(|_| true)?;
+    let json = serde_json::to_vec_pretty(&bundle).map_err(ApiError::internal)?;
+    std::fs::write(&path, json).map_err(|e| ApiError::invalid_request(format!("Cannot write {}: {}", path, e)))?;
+    Ok(bundle.connections.len())
+}
+
+#[tauri::command]
+fn import_workspace(
+    path: String,
+    remap: Vec<PathRemap>,
+    service: State<Arc<LabsService>>,
+) -> ApiResult<ImportReport> {
+    let contents = std::fs::read(&path).map_err(|e| ApiError::invalid_request(format!("Cannot read {}: {}", path, e)))?;
+    let bundle = serde_json::from_slice(&contents)
+        .map_err(|e| ApiError::invalid_request(format!("Invalid workspace bundle {}: {}", path, e)))?;
+    service.import_workspace(&WorkspaceImport { bundle, remap }, |_| true)
+}
+
+#[tauri::command]
+fn run_query(db_path: String, query: QuerySpec, service: State<Arc<LabsService>>) -> ApiResult<QueryResults> {
+    service.run_query(&db_path, &query)
+}
+
+#[tauri::command]
+fn list_saved_queries(db_path: String, service: State<Arc<LabsService>>) -> ApiResult<Vec<SavedQuery>> {
+    service.saved_queries(&db_path)
+}
+
+#[tauri::command]
+fn create_saved_query(
     db_path: String,
-    limit: Option<usize>,
-    state: State<AppState>,
-) -> Result<serde_json::Value, String> {
-    let metrics = state
-        .system_db
-        .get_metrics(&db_path, limit)
-        .map_err(|e| e.to_string())?;
+    request: SavedQueryRequest,
+    service: State<Arc<LabsService>>,
+) -> ApiResult<SavedQuery> {
+    service.create_saved_query(&db_path, &request)
+}
 
-    Ok(serde_json::to_value(metrics).map_err(|e| e.to_string())?)
+#[tauri::command]
+fn update_saved_query(
+    db_path: String,
+    id: String,
+    request: SavedQueryRequest,
+    service: State<Arc<LabsService>>,
+) -> ApiResult<SavedQuery> {
+    service.update_saved_query(&db_path, &id, &request)
 }
 
 #[tauri::command]
-fn remove_connection(db_path: String, state: State<AppState>) -> Result<String, String> {
-    // Remove from active connections
-    {
-        let mut databases = state.databases.write();
-        databases.remove(&db_path);
-    }
+fn delete_saved_query(db_path: String, id: String, service: State<Arc<LabsService>>) -> ApiResult<SavedQuery> {
+    service.delete_saved_query(&db_path, &id)
+}
 
-    // Remove from system database
-    state
-        .system_db
-        .remove_connection(&db_path)
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+fn run_saved_query(db_path: String, id: String, service: State<Arc<LabsService>>) -> ApiResult<QueryResults> {
+    service.run_saved_query(&db_path, &id)
+}
 
-    Ok(format!("Connection removed: {}", db_path))
+#[tauri::command]
+fn get_query_history(
+    db_path: String,
+    limit: Option<usize>,
+    service: State<Arc<LabsService>>,
+) -> ApiResult<Vec<QueryHistoryEntry>> {
+    service.query_history(&db_path, &QueryHistoryQuery { limit })
 }
 
 #[tauri::command]
-fn close_database(db_path: String, state: State<AppState>) -> Result<String, String> {
-    // Remove from active connections
-    {
-        let mut databases = state.databases.write();
-        databases.remove(&db_path);
-    }
+fn rerun_query(db_path: String, id: String, service: State<Arc<LabsService>>) -> ApiResult<QueryResults> {
+    service.rerun_query(&db_path, &id)
+}
+
+#[tauri::command]
+fn clear_query_history(db_path: String, service: State<Arc<LabsService>>) -> ApiResult<usize> {
+    service.clear_query_history(&db_path)
+}
 
-    log::info!("Database closed: {}", db_path);
+#[tauri::command]
+fn remove_connection(db_path: String, service: State<Arc<LabsService>>) -> ApiResult<String> {
+    let db_path = service.remove_connection(&db_path)?;
+    Ok(format!("Connection removed: {}", db_path))
+}
 
+#[tauri::command]
+fn close_database(db_path: String, service: State<Arc<LabsService>>) -> ApiResult<String> {
+    let db_path = service.close_database(&db_path)?;
     Ok(format!("Database closed successfully: {}", db_path))
 }
 
@@ -361,40 +278,9 @@ fn close_database(db_path: String, state: State<AppState>) -> Result<String, Str
 fn drop_collection(
     db_path: String,
     collection: String,
-    state: State<AppState>,
-) -> Result<serde_json::Value, String> {
-    let start = std::time::Instant::now();
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    // Delete all documents in the collection
-    let docs = db
-        .find_all(&collection, None, None)
-        .map_err(|e| e.to_string())?;
-    
-    let mut deleted_count = 0;
-    for doc in docs {
-        if let Err(e) = db.delete(&collection, &doc.id) {
-            log::warn!("Failed to delete document {}: {}", doc.id, e);
-        } else {
-            deleted_count += 1;
-        }
-    }
-
-    // Sync to disk
-    db.sync().map_err(|e| e.to_string())?;
-
-    // Record metric
-    let duration = start.elapsed().as_millis() as u64;
-    if let Err(e) = state.system_db.record_metric(&db_path, "drop_collection", duration) {
-        log::warn!("Failed to record metric: {}", e);
-    }
-
-    log::info!("Collection '{}' dropped from database: {} ({} documents deleted)", 
-               collection, db_path, deleted_count);
+    service: State<Arc<LabsService>>,
+) -> ApiResult<serde_json::Value> {
+    let deleted_count = service.drop_collection(&db_path, &collection)?;
 
     Ok(serde_json::json!({
         "message": "Collection dropped successfully",
@@ -404,26 +290,8 @@ fn drop_collection(
 }
 
 #[tauri::command]
-fn delete_database(db_path: String, state: State<AppState>) -> Result<String, String> {
-    // First, close the database connection
-    {
-        let mut databases = state.databases.write();
-        databases.remove(&db_path);
-    }
-
-    // Delete the database file
-    std::fs::remove_file(&db_path).map_err(|e| {
-        log::error!("Failed to delete database file {}: {}", db_path, e);
-        format!("Failed to delete database file: {}", e)
-    })?;
-
-    // Remove from system database
-    if let Err(e) = state.system_db.remove_connection(&db_path) {
-        log::warn!("Failed to remove connection from system db: {}", e);
-    }
-
-    log::info!("Database deleted: {}", db_path);
-
+fn delete_database(db_path: String, service: State<Arc<LabsService>>) -> ApiResult<String> {
+    let db_path = service.delete_database(&db_path)?;
     Ok(format!("Database deleted successfully: {}", db_path))
 }
 
@@ -431,39 +299,6 @@ fn delete_database(db_path: String, state: State<AppState>) -> Result<String, St
 // Vector Database Commands
 // ============================================================
 
-fn parse_distance(s: &str) -> Distance {
-    match s.to_lowercase().as_str() {
-        "euclidean" | "l2" => Distance::Euclidean,
-        "dot" | "dot_product" | "dotproduct" => Distance::DotProduct,
-        "manhattan" | "l1" => Distance::Manhattan,
-        _ => Distance::Cosine,
-    }
-}
-
-#[derive(Serialize, Deserialize)]
-struct VectorCollectionInfoResponse {
-    name: String,
-    count: usize,
-    dimensions: usize,
-    distance: String,
-}
-
-#[derive(Serialize, Deserialize)]
-struct VectorDocumentResponse {
-    id: u64,
-    vector: Vec<f32>,
-    metadata: Option<serde_json::Value>,
-    created_at: u64,
-}
-
-#[derive(Serialize, Deserialize)]
-struct VectorSearchResultResponse {
-    id: u64,
-    score: f32,
-    vector: Vec<f32>,
-    metadata: Option<serde_json::Value>,
-}
-
 #[tauri::command]
 fn create_vector_collection(
     db_path: String,
@@ -472,25 +307,11 @@ fn create_vector_collection(
     distance: String,
     m: Option<usize>,
     ef_construction: Option<usize>,
-    state: State<AppState>,
-) -> Result<serde_json::Value, String> {
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let mut config = VectorConfig::new(dimensions)
-        .with_distance(parse_distance(&distance));
-    
-    if let Some(m_val) = m {
-        config = config.with_m(m_val);
-    }
-
-    db.create_vector_collection(&name, config)
-        .map_err(|e| e.to_string())?;
-
-    log::info!("Vector collection '{}' created in database: {}", name, db_path);
+    service: State<Arc<LabsService>>,
+) -> ApiResult<serde_json::Value> {
+    // keradb does not expose ef_construction yet; accepted for API compatibility
+    let _ = ef_construction;
+    service.create_vector_collection(&db_path, &name, dimensions, &distance, m)?;
 
     Ok(serde_json::json!({
         "message": "Vector collection created successfully",
@@ -503,76 +324,27 @@ fn create_vector_collection(
 #[tauri::command]
 fn list_vector_collections(
     db_path: String,
-    state: State<AppState>,
-) -> Result<Vec<VectorCollectionInfoResponse>, String> {
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let collections = db.list_vector_collections();
-    
-    let collection_infos: Vec<VectorCollectionInfoResponse> = collections
-        .iter()
-        .filter_map(|(name, count)| {
-            db.vector_stats(name).ok().map(|stats| {
-                VectorCollectionInfoResponse {
-                    name: name.clone(),
-                    count: *count,
-                    dimensions: stats.dimensions,
-                    distance: stats.distance.name().to_string(),
-                }
-            })
-        })
-        .collect();
-
-    Ok(collection_infos)
+    service: State<Arc<LabsService>>,
+) -> ApiResult<Vec<VectorCollectionInfoResponse>> {
+    service.vector_collections(&db_path)
 }
 
 #[tauri::command]
 fn get_vector_collection_stats(
     db_path: String,
     collection: String,
-    state: State<AppState>,
-) -> Result<serde_json::Value, String> {
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let stats = db.vector_stats(&collection)
-        .map_err(|e| e.to_string())?;
-
-    Ok(serde_json::json!({
-        "name": stats.name,
-        "vector_count": stats.vector_count,
-        "dimensions": stats.dimensions,
-        "distance": stats.distance.name(),
-        "memory_bytes": stats.memory_bytes,
-        "hnsw_m": stats.hnsw_layers,
-        "lazy_embedding": stats.lazy_embedding,
-        "compression_mode": format!("{:?}", stats.compression_mode)
-    }))
+    service: State<Arc<LabsService>>,
+) -> ApiResult<serde_json::Value> {
+    service.vector_collection_stats(&db_path, &collection)
 }
 
 #[tauri::command]
 fn drop_vector_collection(
     db_path: String,
     collection: String,
-    state: State<AppState>,
-) -> Result<serde_json::Value, String> {
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let dropped = db.drop_vector_collection(&collection)
-        .map_err(|e| e.to_string())?;
-
-    log::info!("Vector collection '{}' dropped from database: {}", collection, db_path);
+    service: State<Arc<LabsService>>,
+) -> ApiResult<serde_json::Value> {
+    let dropped = service.drop_vector_collection(&db_path, &collection)?;
 
     Ok(serde_json::json!({
         "dropped": dropped,
@@ -586,27 +358,14 @@ fn insert_vector(
     collection: String,
     vector: Vec<f32>,
     metadata: Option<serde_json::Value>,
-    state: State<AppState>,
-) -> Result<serde_json::Value, String> {
-    let start = std::time::Instant::now();
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let id = db.insert_vector(&collection, vector.clone(), metadata)
-        .map_err(|e| e.to_string())?;
-
-    // Record metric
-    let duration = start.elapsed().as_millis() as u64;
-    if let Err(e) = state.system_db.record_metric(&db_path, "insert_vector", duration) {
-        log::warn!("Failed to record metric: {}", e);
-    }
+    service: State<Arc<LabsService>>,
+) -> ApiResult<serde_json::Value> {
+    let dimensions = vector.len();
+    let id = service.insert_vector(&db_path, &collection, vector, metadata)?;
 
     Ok(serde_json::json!({
         "id": id,
-        "dimensions": vector.len()
+        "dimensions": dimensions
     }))
 }
 
@@ -616,55 +375,9 @@ fn get_vectors(
     collection: String,
     limit: Option<usize>,
     skip: Option<usize>,
-    state: State<AppState>,
-) -> Result<serde_json::Value, String> {
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let stats = db.vector_stats(&collection)
-        .map_err(|e| e.to_string())?;
-    
-    let limit = limit.unwrap_or(100);
-    let skip = skip.unwrap_or(0);
-    
-    let mut vectors: Vec<VectorDocumentResponse> = Vec::new();
-    let mut found = 0;
-    let mut skipped = 0;
-    
-    for id in 0..stats.vector_count as u64 + skip as u64 + 100 {
-        if let Ok(Some(doc)) = db.get_vector(&collection, id) {
-            if skipped < skip {
-                skipped += 1;
-                continue;
-            }
-            
-            vectors.push(VectorDocumentResponse {
-                id: doc.id,
-                vector: doc.embedding.clone().unwrap_or_default(),
-                metadata: if doc.metadata == serde_json::Value::Null { 
-                    None 
-                } else { 
-                    Some(doc.metadata.clone()) 
-                },
-                created_at: 0,
-            });
-            
-            found += 1;
-            if found >= limit {
-                break;
-            }
-        }
-    }
-
-    Ok(serde_json::json!({
-        "vectors": vectors,
-        "total": stats.vector_count,
-        "limit": limit,
-        "skip": skip
-    }))
+    service: State<Arc<LabsService>>,
+) -> ApiResult<VectorPage> {
+    service.vectors(&db_path, &collection, limit, skip)
 }
 
 #[tauri::command]
@@ -673,39 +386,9 @@ fn vector_search(
     collection: String,
     vector: Vec<f32>,
     k: usize,
-    state: State<AppState>,
-) -> Result<Vec<VectorSearchResultResponse>, String> {
-    let start = std::time::Instant::now();
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let results = db.vector_search(&collection, &vector, k)
-        .map_err(|e| e.to_string())?;
-
-    let response: Vec<VectorSearchResultResponse> = results
-        .into_iter()
-        .map(|r| VectorSearchResultResponse {
-            id: r.document.id,
-            score: r.score,
-            vector: r.document.embedding.clone().unwrap_or_default(),
-            metadata: if r.document.metadata == serde_json::Value::Null { 
-                None 
-            } else { 
-                Some(r.document.metadata.clone()) 
-            },
-        })
-        .collect();
-
-    // Record metric
-    let duration = start.elapsed().as_millis() as u64;
-    if let Err(e) = state.system_db.record_metric(&db_path, "vector_search", duration) {
-        log::warn!("Failed to record metric: {}", e);
-    }
-
-    Ok(response)
+    service: State<Arc<LabsService>>,
+) -> ApiResult<Vec<VectorSearchResultResponse>> {
+    service.vector_search(&db_path, &collection, &vector, k)
 }
 
 #[tauri::command]
@@ -713,28 +396,9 @@ fn get_vector(
     db_path: String,
     collection: String,
     id: u64,
-    state: State<AppState>,
-) -> Result<VectorDocumentResponse, String> {
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let doc = db.get_vector(&collection, id)
-        .map_err(|e| e.to_string())?
-        .ok_or_else(|| "Vector not found".to_string())?;
-
-    Ok(VectorDocumentResponse {
-        id: doc.id,
-        vector: doc.embedding.clone().unwrap_or_default(),
-        metadata: if doc.metadata == serde_json::Value::Null { 
-            None 
-        } else { 
-            Some(doc.metadata.clone()) 
-        },
-        created_at: 0, // VectorDocument doesn't have created_at
-    })
+    service: State<Arc<LabsService>>,
+) -> ApiResult<VectorDocumentResponse> {
+    service.get_vector(&db_path, &collection, id)
 }
 
 #[tauri::command]
@@ -742,16 +406,9 @@ fn delete_vector(
     db_path: String,
     collection: String,
     id: u64,
-    state: State<AppState>,
-) -> Result<serde_json::Value, String> {
-    let databases = state.databases.read();
-    
-    let db = databases
-        .get(&db_path)
-        .ok_or_else(|| "Database not found".to_string())?;
-
-    let deleted = db.delete_vector(&collection, id)
-        .map_err(|e| e.to_string())?;
+    service: State<Arc<LabsService>>,
+) -> ApiResult<serde_json::Value> {
+    let deleted = service.delete_vector(&db_path, &collection, id)?;
 
     Ok(serde_json::json!({
         "deleted": deleted,
@@ -759,19 +416,122 @@ fn delete_vector(
     }))
 }
 
+// ============================================================
+// Document Parsing Commands
+// ============================================================
+
+/// Parse a local file with the same parser and result shape as the REST
+/// `/api/parse-document` endpoint, without copying the file through IPC.
+/// Progress is emitted to the calling window as `parse-progress` events.
+#[tauri::command]
+async fn parse_document_file(path: String, window: Window) -> ApiResult<serde_json::Value> {
+    let path = PathBuf::from(path);
+    tauri::async_runtime::spawn_blocking(move || {
+        parsing::parse_file(&path, ArchiveLimits::default(), |progress: ParseProgress| {
+            if let Err(e) = window.emit(PARSE_PROGRESS_EVENT, progress) {
+                log::warn!("Failed to emit parse progress: {}", e);
+            }
+        })
+    })
+    .await
+    .map_err(ApiError::internal)?
+}
+
+// ============================================================
+// Change Stream Commands
+// ============================================================
+
+/// Emit `database-change` events to the calling window until unsubscribed or
+/// the window closes. `channel` replaces the event name so several listeners
+/// in one window only see their own subscription. Missed events after `after`
+/// in `epoch` are emitted before this returns, so listen first.
+#[tauri::command]
+fn subscribe_changes(
+    db_path: Option<String>,
+    collection: Option<String>,
+    after: Option<u64>,
+    epoch: Option<String>,
+    channel: Option<String>,
+    window: Window,
+    service: State<Arc<LabsService>>,
+) -> ApiResult<Subscription> {
+    let channel = channel.unwrap_or_else(|| DATABASE_CHANGE_EVENT.to_string());
+    // Tauri panics on event names outside this set
+    if channel.is_empty() || !channel.chars().all(|c| c.is_ascii_alphanumeric() || "-/:_".contains(c)) {
+        return Err(ApiError::invalid_request(format!("Invalid event channel: {}", channel)));
+    }
+
+    let after = after.map(|seq| ResumePoint {
+        epoch: epoch.unwrap_or_default(),
+        seq,
+    });
+    service.subscribe_changes(db_path.as_deref(), collection.as_deref(), after, move |event| {
+        window.emit(&channel, event).is_ok()
+    })
+}
+
+#[tauri::command]
+fn unsubscribe_changes(id: u64, service: State<Arc<LabsService>>) -> ApiResult<bool> {
+    Ok(service.changes().unsubscribe(id))
+}
+
+// ============================================================
+// Background Job Commands
+// ============================================================
+
+/// Start a background job; progress is emitted to every window as
+/// `job-progress` events
+#[tauri::command]
+fn submit_job(request: JobRequest, jobs: State<Arc<JobManager>>) -> ApiResult<Job> {
+    jobs.submit(request)
+}
+
+#[tauri::command]
+fn get_job(id: String, jobs: State<Arc<JobManager>>) -> ApiResult<Job> {
+    jobs.get(&id)
+}
+
+#[tauri::command]
+fn list_jobs(limit: Option<usize>, jobs: State<Arc<JobManager>>) -> ApiResult<Vec<Job>> {
+    jobs.list(limit)
+}
+
+#[tauri::command]
+fn cancel_job(id: String, jobs: State<Arc<JobManager>>) -> ApiResult<Job> {
+    jobs.cancel(&id)
+}
+
 fn main() {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
-    // Initialize system database
-    let system_db = SystemDatabase::init().expect("Failed to initialize system database");
-    
+    // Initialize system database; the desktop app keeps its own history
+    // separate from the REST backend's. KERADB_HOME and KERADB_PROFILE
+    // select another directory or a named profile.
+    let system_db = SystemDatabase::init_named(".nosqlite", ".nosqlite_system.db")
+        .expect("Failed to initialize system database");
+
     log::info!("System database initialized");
 
+    let service = Arc::new(LabsService::new(system_db, DatabaseRoots::default()));
+    let jobs = JobManager::new(service.clone());
+    metrics::spawn_compaction(service.clone(), MetricsRetention::default());
+
     tauri::Builder::default()
-        .manage(AppState {
-            databases: Arc::new(RwLock::new(HashMap::new())),
-            system_db: Arc::new(system_db),
+        .setup({
+            let jobs = jobs.clone();
+            move |app| {
+                let handle = app.handle();
+                jobs.subscribe(move |job| {
+                    if let Err(e) = handle.emit_all(JOB_PROGRESS_EVENT, job) {
+                        log::warn!("Failed to emit job progress: {}", e);
+                    }
+                    true
+                });
+                Ok(())
+            }
         })
+        .manage(service)
+        .manage(jobs)
         .invoke_handler(tauri::generate_handler![
             open_database,
             create_database,
@@ -785,11 +545,29 @@ fn main() {
             get_stats,
             get_system_stats,
             get_connection_history,
+            update_connection_profile,
             get_database_metrics,
+            query_metrics,
+            get_metric_rollups,
+            get_slow_operations,
+            get_audit_log,
+            export_audit_log,
+            export_workspace,
+            import_workspace,
             remove_connection,
             close_database,
             drop_collection,
             delete_database,
+            // Queries
+            run_query,
+            list_saved_queries,
+            create_saved_query,
+            update_saved_query,
+            delete_saved_query,
+            run_saved_query,
+            get_query_history,
+            rerun_query,
+            clear_query_history,
             // Vector commands
             create_vector_collection,
             list_vector_collections,
@@ -800,7 +578,25 @@ fn main() {
             vector_search,
             get_vector,
             delete_vector,
+            // Document parsing
+            parse_document_file,
+            // Change stream
+            subscribe_changes,
+            unsubscribe_changes,
+            // Background jobs
+            submit_job,
+            get_job,
+            list_jobs,
+            cancel_job,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Sync deferred metric and connection writes before exiting
+            if let tauri::RunEvent::Exit = event {
+                if let Err(e) = app.state::<Arc<LabsService>>().system_db().flush() {
+                    log::error!("Failed to flush the system database: {}", e);
+                }
+            }
+        });
 }